@@ -6,6 +6,7 @@ use crate::structure::TypedVec;
 use nom::digit;
 use nom::types::CompleteStr;
 use nom::*;
+use std::str::CharIndices;
 
 named!(
     integer<CompleteStr, f64>,
@@ -46,17 +47,20 @@ named!(float<CompleteStr, f64>,
     )
 );
 
+// float first, so the integer part of a float isn't taken for a whole number
 named!(number<CompleteStr, f64>,
-    alt!(integer | float)
+    alt!(float | integer)
 );
 
 // risp_comment
+// the whitespace after it is left alone: an empty comment doesn't take the next
+// line, and the line break is left to separate it from what follows in an expression
 named!(
     risp_comment<CompleteStr, Risp>,
-    ws!(
+    preceded!(
+        opt!(multispace),
         do_parse!(
             tag!(";") >>
-            opt!(multispace) >>
             take_until!("\n") >>
             (
                 Risp::LComment
@@ -76,6 +80,14 @@ named!(
     )
 );
 
+// risp_keyword
+// a prelude word only counts as such when it isn't the prefix of a longer symbol,
+// so `list?` or `get-in` are read as symbols instead of `list` and `get`
+named!(
+    risp_keyword<CompleteStr, Risp>,
+    terminated!(risp_prelude, not!(risp_symbol))
+);
+
 // risp_integer
 named!(
     risp_integer<CompleteStr, Risp>,
@@ -118,31 +130,99 @@ named!(
     )
 );
 
+// unescape
+// reads the escape sequence following a '\', one of \n \t \r \0 \\ \" \'
+// or a unicode scalar like \u{1F980}
+fn unescape(chars: &mut CharIndices) -> Result<char, String> {
+    match chars.next().map(|(_, c)| c) {
+        Some('n') => Ok('\n'),
+        Some('t') => Ok('\t'),
+        Some('r') => Ok('\r'),
+        Some('0') => Ok('\0'),
+        Some('\\') => Ok('\\'),
+        Some('"') => Ok('"'),
+        Some('\'') => Ok('\''),
+        Some('u') => {
+            if chars.next().map(|(_, c)| c) != Some('{') {
+                return Err("Unicode escape must look like '\\u{...}'".to_string());
+            }
+            let mut hex = String::new();
+            loop {
+                match chars.next().map(|(_, c)| c) {
+                    Some('}') => break,
+                    Some(c) if c.is_ascii_hexdigit() && hex.len() < 6 => hex.push(c),
+                    _ => return Err("Unicode escape must look like '\\u{...}'".to_string()),
+                }
+            }
+            u32::from_str_radix(&hex, 16)
+                .ok()
+                .and_then(std::char::from_u32)
+                .ok_or_else(|| format!("'\\u{{{}}}' isn't a valid unicode character", hex))
+        }
+        Some(c) => Err(format!("Unknown escape sequence '\\{}'", c)),
+        None => Err("Unfinished escape sequence".to_string()),
+    }
+}
+
 // risp_string
-named!(
-    risp_string<CompleteStr, Risp>,
-    do_parse!(
-        tag!("\"") >>
-        content: take_until!("\"") >>
-        tag!("\"") >>
-        (
-            Risp::LString(content.to_string())
-        )
-    )
-);
+// a invalid escape sequence reads as a syntax error instead of failing the whole parse
+fn risp_string(input: CompleteStr) -> IResult<CompleteStr, Risp> {
+    let (rest, _) = tag!(input, "\"")?;
+    let mut content = String::new();
+    let mut err = None;
+    let mut chars = rest.0.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => {
+                let val = match err {
+                    Some(e) => Risp::LSyntaxErr(e),
+                    None => Risp::LString(content),
+                };
+                return Ok((CompleteStr(&rest.0[i + 1..]), val));
+            }
+            '\\' => match unescape(&mut chars) {
+                Ok(c) => content.push(c),
+                Err(e) => err = err.or(Some(e)),
+            },
+            c => content.push(c),
+        }
+    }
+    Err(Err::Error(error_position!(input, ErrorKind::Tag)))
+}
+
+// risp_raw_string
+// r"..." or r#"..."#, with as many '#' as needed to hold quotes, and no escapes
+fn risp_raw_string(input: CompleteStr) -> IResult<CompleteStr, Risp> {
+    let (rest, _) = tag!(input, "r")?;
+    let hashes = rest.0.chars().take_while(|c| *c == '#').count();
+    let (rest, _) = tag!(CompleteStr(&rest.0[hashes..]), "\"")?;
+    let closing = format!("\"{}", "#".repeat(hashes));
+    match rest.0.find(&closing) {
+        Some(end) => Ok((
+            CompleteStr(&rest.0[end + closing.len()..]),
+            Risp::LString(rest.0[..end].to_string()),
+        )),
+        None => Err(Err::Error(error_position!(input, ErrorKind::Tag))),
+    }
+}
 
 // risp_char
-named!(
-    risp_char<CompleteStr, Risp>,
-    do_parse!(
-        tag!("\'") >>
-        content: anychar >>
-        tag!("\'") >>
-        (
-            Risp::LChar(content)
-        )
-    )
-);
+fn risp_char(input: CompleteStr) -> IResult<CompleteStr, Risp> {
+    let (rest, _) = tag!(input, "\'")?;
+    let mut chars = rest.0.char_indices();
+    let val = match chars.next() {
+        Some((_, '\\')) => match unescape(&mut chars) {
+            Ok(c) => Risp::LChar(c),
+            Err(e) => Risp::LSyntaxErr(e),
+        },
+        Some((_, c)) if c != '\'' => Risp::LChar(c),
+        _ => return Err(Err::Error(error_position!(input, ErrorKind::Tag))),
+    };
+    match chars.next() {
+        Some((i, '\'')) => Ok((CompleteStr(&rest.0[i + 1..]), val)),
+        _ => Err(Err::Error(error_position!(input, ErrorKind::Tag))),
+    }
+}
 
 // risp_true
 named!(
@@ -172,6 +252,13 @@ named!(
     alt!(risp_true | risp_false)
 );
 
+// risp_atom
+// the text of a value that isn't made of other values, as it was written
+named!(
+    pub(crate) risp_atom<CompleteStr, CompleteStr>,
+    recognize!(alt!(risp_float | risp_integer | risp_bool | risp_raw_string | risp_keyword | risp_symbol | risp_string | risp_char))
+);
+
 // risp_sexpr
 named!(
     risp_sexpr<CompleteStr, Risp>,
@@ -203,7 +290,7 @@ named!(
     risp_val<CompleteStr, Risp>,
     do_parse!(
         opt!(multispace) >>
-        val: alt!( risp_comment | risp_sexpr | risp_qexpr | risp_int_vec_literal | risp_float | risp_integer | risp_bool | risp_prelude | risp_symbol | risp_string | risp_char) >>
+        val: alt!( risp_comment | risp_sexpr | risp_qexpr | risp_int_vec_literal | risp_map_literal | risp_set_literal | risp_float | risp_integer | risp_bool | risp_raw_string | risp_keyword | risp_symbol | risp_string | risp_char) >>
         opt!(ws!(tag!("."))) >>
         (val)
     )
@@ -219,13 +306,48 @@ named!(risp_int_vec_literal<CompleteStr, Risp>,
         )
     )
 );
+
+named!(risp_map_literal<CompleteStr, Risp>,
+    do_parse!(
+        terminated!(tag!("#{"), opt!(multispace)) >>
+        list: separated_list!(multispace, risp_val) >>
+        preceded!(opt!(multispace), tag!("}")) >>
+        (
+            if list.len() % 2 == 0 {
+                let mut pairs = Vec::new();
+                let mut it = list.into_iter();
+                while let (Some(k), Some(v)) = (it.next(), it.next()) {
+                    pairs.push((k, v));
+                }
+                Risp::LMap(pairs)
+            } else {
+                Risp::LSyntaxErr(format!(
+                    "Map literal needs an even number of forms, got {}",
+                    list.len()
+                ))
+            }
+        )
+    )
+);
+
+named!(risp_set_literal<CompleteStr, Risp>,
+    do_parse!(
+        terminated!(tag!("#s{"), opt!(multispace)) >>
+        list: separated_list!(multispace, risp_val) >>
+        preceded!(opt!(multispace), tag!("}")) >>
+        (
+            Risp::LSet(list)
+        )
+    )
+);
+
 pub fn parse_risp(input: &str) -> Option<Risp> {
     let val = risp_val(CompleteStr(input));
     // println!("Got parse: {:?}", val);
     match val {
         Ok(v) => {
             let (rest, result) = v;
-            if rest == CompleteStr("") {
+            if rest.trim().is_empty() {
                 Some(result)
             } else {
                 Some(Risp::LSyntaxErr(format!(
@@ -238,9 +360,45 @@ pub fn parse_risp(input: &str) -> Option<Risp> {
     }
 }
 
+/// Parses every top level form of a program, like a whole .rspr file, in order.
+/// Stops at the first form that can't be parsed, with a syntax error in its place.
+/// A leading `#!` line is skipped, so scripts can be made executable
+pub fn parse_risp_program(input: &str) -> Vec<Risp> {
+    let input = if input.starts_with("#!") {
+        &input[input.find('\n').unwrap_or(input.len())..]
+    } else {
+        input
+    };
+    // a comment runs until a line break, so the last line needs one too
+    let input = format!("{}\n", input);
+    let mut rest = CompleteStr(input.as_str());
+    let mut forms = Vec::new();
+    loop {
+        rest = CompleteStr(rest.trim_start());
+        if rest.is_empty() {
+            return forms;
+        }
+        match risp_val(rest) {
+            Ok((next, form)) => {
+                let failed = matches!(form, Risp::LSyntaxErr(_));
+                forms.push(form);
+                if failed {
+                    return forms;
+                }
+                rest = next;
+            }
+            Err(e) => {
+                forms.push(Risp::LSyntaxErr(format!("syntax err: {:?}", e)));
+                return forms;
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::structure::Prelude;
 
     #[test]
     fn parse_multiline() {
@@ -254,6 +412,91 @@ mod tests {
         }
     }
 
+    #[test]
+    fn parse_keyword_prefixed_symbol() {
+        assert_eq!(
+            Some(Risp::LSymbol("list?".to_string())),
+            parse_risp("list?")
+        );
+        assert_eq!(Some(Risp::LPrelude(Prelude::List)), parse_risp("list"));
+        assert_eq!(Some(Risp::LPrelude(Prelude::Gte)), parse_risp(">="));
+    }
+
+    #[test]
+    fn parse_map_literal() {
+        let map = parse_risp("#{\"a\" 1 b {2 3}}");
+        assert_eq!(
+            Some(Risp::LMap(vec![
+                (
+                    Risp::LString("a".to_string()),
+                    Risp::LNumber(NumType::Int(1))
+                ),
+                (
+                    Risp::LSymbol("b".to_string()),
+                    Risp::Qexpr(vec![
+                        Risp::LNumber(NumType::Int(2)),
+                        Risp::LNumber(NumType::Int(3))
+                    ])
+                ),
+            ])),
+            map
+        );
+        assert_eq!(Some(Risp::LMap(vec![])), parse_risp("#{}"));
+        match parse_risp("#{1}") {
+            Some(Risp::LSyntaxErr(_)) => {}
+            e => panic!("expected a syntax error, got {:?}", e),
+        }
+    }
+
+    #[test]
+    fn parse_set_literal() {
+        assert_eq!(
+            Some(Risp::LSet(vec![
+                Risp::LSymbol("a".to_string()),
+                Risp::LChar('b'),
+            ])),
+            parse_risp("#s{a 'b'}")
+        );
+        assert_eq!(Some(Risp::LSet(vec![])), parse_risp("#s{}"));
+    }
+
+    #[test]
+    fn parse_string_escapes() {
+        assert_eq!(
+            Some(Risp::LString("a \"quote\"\n\tand \\ 🦀".to_string())),
+            parse_risp(r#""a \"quote\"\n\tand \\ \u{1F980}""#)
+        );
+        assert_eq!(
+            Some(Risp::LString("two\nlines".to_string())),
+            parse_risp("\"two\nlines\"")
+        );
+        match parse_risp(r#""bad \q escape""#) {
+            Some(Risp::LSyntaxErr(_)) => {}
+            e => panic!("expected a syntax error, got {:?}", e),
+        }
+    }
+
+    #[test]
+    fn parse_raw_string() {
+        assert_eq!(
+            Some(Risp::LString(r#"C:\no "escapes" here"#.to_string())),
+            parse_risp(r###"r#"C:\no "escapes" here"#"###)
+        );
+        assert_eq!(
+            Some(Risp::LString(r"\n".to_string())),
+            parse_risp(r#"r"\n""#)
+        );
+        assert_eq!(Some(Risp::LSymbol("r".to_string())), parse_risp("r"));
+    }
+
+    #[test]
+    fn parse_char_escapes() {
+        assert_eq!(Some(Risp::LChar('\n')), parse_risp(r"'\n'"));
+        assert_eq!(Some(Risp::LChar('\'')), parse_risp(r"'\''"));
+        assert_eq!(Some(Risp::LChar('é')), parse_risp(r"'\u{e9}'"));
+        assert_eq!(Some(Risp::LChar('é')), parse_risp("'é'"));
+    }
+
     #[test]
     fn parse_comment() {
         let comment = parse_risp("; a comment\n");
@@ -261,5 +504,32 @@ mod tests {
             None => panic!("comment parse error"),
             _ => {}
         }
+        assert_eq!(
+            vec![
+                Risp::LComment,
+                Risp::LNumber(NumType::Int(1)),
+                Risp::LComment,
+            ],
+            parse_risp_program(";\n1 ;;; two  ")
+        );
+    }
+
+    #[test]
+    fn parse_program() {
+        let program = parse_risp_program("(def {a} 1)\n; one\n(fun {f x}\n  {+ x a})\n; last");
+        assert_eq!(4, program.len());
+        assert_eq!(Risp::LComment, program[1]);
+        assert_eq!(Risp::LComment, program[3]);
+
+        let program = parse_risp_program("(+ 1 2) (+ 3");
+        match program.as_slice() {
+            [Risp::Sexpr(_), Risp::LSyntaxErr(_)] => {}
+            e => panic!("expected a form and a syntax error, got {:?}", e),
+        }
+        assert!(parse_risp_program("  \n").is_empty());
+        assert_eq!(
+            vec![Risp::LNumber(NumType::Int(1))],
+            parse_risp_program("#!/usr/bin/env -S rispreter run\n1")
+        );
     }
 }
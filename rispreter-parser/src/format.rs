@@ -0,0 +1,244 @@
+use crate::complete_parser::parse_risp_program;
+use crate::structure::Risp;
+use crate::syntax::{read_syntax, Node, Syntax};
+
+/// Width `format_program` breaks lines at, when none is given
+pub const DEFAULT_WIDTH: usize = 80;
+
+/// Formats a program, like a whole .rspr file: every form is put on one line when
+/// it fits in `width`, or otherwise broken into a line per element, indented
+/// under its parent. The bodies of `fun` and `\`, the values of `def` and `=`
+/// and the clauses of `select` and `match` go two spaces in, the arguments of
+/// other calls line up under the first one. Comments and single blank lines are
+/// kept where they were, the `.` that can end a form is dropped. Fails with the
+/// syntax error of a program that can't be parsed
+pub fn format_program(input: &str, width: usize) -> Result<String, String> {
+    if let Some(Risp::LSyntaxErr(err)) = parse_risp_program(input).pop() {
+        return Err(err);
+    }
+    let nodes = read_syntax(input).map_err(|err| err.msg)?;
+    let shebang = if input.starts_with("#!") {
+        &input[..input.find('\n').unwrap_or(input.len())]
+    } else {
+        ""
+    };
+
+    let mut out = String::new();
+    if !shebang.is_empty() {
+        out.push_str(shebang.trim_end());
+        out.push('\n');
+    }
+    for (i, node) in nodes.iter().enumerate() {
+        match &node.syntax {
+            Syntax::Blank => out.push('\n'),
+            Syntax::Comment {
+                text,
+                trailing: true,
+            } => {
+                out.push_str(" ;");
+                out.push_str(text);
+            }
+            _ => {
+                if i > 0 {
+                    out.push('\n');
+                }
+                layout(node, width, 0, &mut out);
+            }
+        }
+    }
+    if !nodes.is_empty() {
+        out.push('\n');
+    }
+    // what is read back has to be the same program, comments included
+    if !read_syntax(&out).is_ok_and(|formatted| same(&nodes, &formatted)) {
+        return Err("Formatting would change what the program means".to_string());
+    }
+    Ok(out)
+}
+
+/// Whether two programs are written the same but for where things are
+fn same(nodes: &[Node], others: &[Node]) -> bool {
+    nodes.len() == others.len()
+        && nodes
+            .iter()
+            .zip(others)
+            .all(|(node, other)| match (&node.syntax, &other.syntax) {
+                (
+                    Syntax::List { open, items, .. },
+                    Syntax::List {
+                        open: other_open,
+                        items: other_items,
+                        ..
+                    },
+                ) => open == other_open && same(items, other_items),
+                (syntax, other) => syntax == other,
+            })
+}
+
+/// A form on a single line, unless there's a comment in it that ends the line
+fn flat(node: &Node) -> Option<String> {
+    match &node.syntax {
+        Syntax::Atom(atom) => Some(atom.clone()),
+        Syntax::List { open, close, items } => {
+            let mut items = items.iter().filter(|item| item.syntax != Syntax::Blank);
+            let items = items.try_fold(Vec::new(), |mut flats, item| {
+                flats.push(flat(item)?);
+                Some(flats)
+            })?;
+            Some(format!("{}{}{}", open, items.join(" "), close))
+        }
+        Syntax::Comment { .. } | Syntax::Blank => None,
+    }
+}
+
+/// Column the next character of `out` goes in
+fn column(out: &str) -> usize {
+    out[out.rfind('\n').map_or(0, |at| at + 1)..]
+        .chars()
+        .count()
+}
+
+/// Writes a form to `out`, starting at its last column, with room left for the
+/// `closers` of its parents that come right after it
+fn layout(node: &Node, width: usize, closers: usize, out: &mut String) {
+    if let Some(flat) = flat(node) {
+        if column(out) + flat.chars().count() + closers <= width {
+            return out.push_str(&flat);
+        }
+    }
+    let (open, close, items) = match &node.syntax {
+        Syntax::List { open, close, items } => (open, close, items),
+        Syntax::Comment { text, .. } => {
+            out.push(';');
+            return out.push_str(text);
+        }
+        Syntax::Atom(atom) => return out.push_str(atom),
+        Syntax::Blank => return,
+    };
+    let start = column(out);
+    out.push_str(open);
+    // how many items go on the first line, and where the others line up
+    let (first_line, indent) = match items.first().and_then(Node::atom) {
+        Some(head) if *open == "(" || *open == "{" => match head {
+            "fun" | "\\" | "def" | "=" | "put" | "match" => (2, start + 2),
+            "select" | "do" | "let" => (1, start + 2),
+            head if start + open.len() + head.chars().count() < width / 2 => {
+                (2, start + open.len() + head.chars().count() + 1)
+            }
+            _ => (1, start + 2),
+        },
+        _ => (1, start + open.len()),
+    };
+    let mut on_first_line = true;
+    for (i, item) in items.iter().enumerate() {
+        match &item.syntax {
+            Syntax::Blank => {
+                out.push('\n');
+                continue;
+            }
+            Syntax::Comment {
+                text,
+                trailing: true,
+            } if i > 0 => {
+                out.push_str(" ;");
+                out.push_str(text);
+                on_first_line = false;
+                continue;
+            }
+            _ => {}
+        }
+        let own_line = matches!(item.syntax, Syntax::Comment { .. });
+        on_first_line = on_first_line && i < first_line && !own_line;
+        if i > 0 && on_first_line {
+            out.push(' ');
+        } else if i > 0 {
+            out.push('\n');
+            out.push_str(&" ".repeat(indent));
+        }
+        let closers = if i + 1 == items.len() { closers + 1 } else { 0 };
+        layout(item, width, closers, out);
+    }
+    // the closing can't go after a comment, it would be a part of it
+    if items.last().is_some_and(|last| !last.is_form()) {
+        out.push('\n');
+        out.push_str(&" ".repeat(start));
+    }
+    out.push(*close);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_breaks_long_forms() {
+        let program = "(fun {fib n} { select { (== n 0) 0 } { (== n 1) 1 } { otherwise (+ (fib (- n 1)) (fib (- n 2))) } })";
+        assert_eq!(
+            "(fun {fib n} {select {(== n 0) 0} {(== n 1) 1} {otherwise (+ (fib (- n 1)) (fib (- n 2)))}})\n",
+            format_program(program, 100).unwrap()
+        );
+        assert_eq!(
+            "(fun {fib n}
+  {select
+    {(== n 0) 0}
+    {(== n 1) 1}
+    {otherwise (+ (fib (- n 1)) (fib (- n 2)))}})
+",
+            format_program(program, 50).unwrap()
+        );
+        assert_eq!(
+            "(str-concat \"a long string\"
+            (str-upper \"and another\")
+            r\"raw\\path\")
+",
+            format_program(
+                "(str-concat \"a long string\" (str-upper \"and another\") r\"raw\\path\").",
+                40
+            )
+            .unwrap()
+        );
+        assert_eq!(
+            "#{\"key\"\n  {1 2 3}\n  other\n  #[1.50 -2]}\n",
+            format_program("#{\"key\" {1 2 3} other #[1.50 -2]}", 14).unwrap()
+        );
+    }
+
+    #[test]
+    fn format_keeps_comments_and_blank_lines() {
+        let program = "#!/usr/bin/env -S rispreter run
+; header
+
+
+(def {a} 1)   ; one
+(fun {f x} {+ x
+  ; the body
+  a ; plus
+
+
+  1 ;
+})
+;; last";
+        let formatted = "#!/usr/bin/env -S rispreter run
+; header
+
+(def {a} 1) ; one
+(fun {f x}
+  {+ x
+     ; the body
+     a ; plus
+
+     1 ;
+  })
+;; last
+";
+        assert_eq!(formatted, format_program(program, 80).unwrap());
+        assert_eq!(formatted, format_program(formatted, 80).unwrap());
+        assert_eq!("", format_program(" \n", 80).unwrap());
+    }
+
+    #[test]
+    fn format_fails_on_syntax_errors() {
+        assert!(format_program("(+ 1 2) (+ 3", 80).is_err());
+        assert!(format_program("\"open", 80).is_err());
+    }
+}
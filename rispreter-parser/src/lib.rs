@@ -1,5 +1,7 @@
 extern crate nom;
 
 pub mod complete_parser;
+pub mod format;
 mod prelude;
 pub mod structure;
+pub mod syntax;
@@ -155,6 +155,10 @@ named!(select<CompleteStr, Risp>,
     do_parse!(tag!("select") >> (Risp::LPrelude(Prelude::Select)))
 );
 
+named!(lmatch<CompleteStr, Risp>,
+    do_parse!(tag!("match") >> (Risp::LPrelude(Prelude::Match)))
+);
+
 named!(take<CompleteStr, Risp>,
     do_parse!(tag!("take") >> (Risp::LPrelude(Prelude::Take)))
 );
@@ -179,6 +183,34 @@ named!(filter<CompleteStr, Risp>,
     do_parse!(tag!("filter") >> (Risp::LPrelude(Prelude::Filter)))
 );
 
+named!(assoc<CompleteStr, Risp>,
+    do_parse!(tag!("assoc") >> (Risp::LPrelude(Prelude::Assoc)))
+);
+
+named!(dissoc<CompleteStr, Risp>,
+    do_parse!(tag!("dissoc") >> (Risp::LPrelude(Prelude::Dissoc)))
+);
+
+named!(get_in<CompleteStr, Risp>,
+    do_parse!(tag!("get-in") >> (Risp::LPrelude(Prelude::GetIn)))
+);
+
+named!(keys<CompleteStr, Risp>,
+    do_parse!(tag!("keys") >> (Risp::LPrelude(Prelude::Keys)))
+);
+
+named!(vals<CompleteStr, Risp>,
+    do_parse!(tag!("vals") >> (Risp::LPrelude(Prelude::Vals)))
+);
+
+named!(contains<CompleteStr, Risp>,
+    do_parse!(tag!("contains?") >> (Risp::LPrelude(Prelude::Contains)))
+);
+
+named!(merge<CompleteStr, Risp>,
+    do_parse!(tag!("merge") >> (Risp::LPrelude(Prelude::Merge)))
+);
+
 named!(list_op<CompleteStr, Risp>,
     alt!(
         fst |
@@ -201,6 +233,289 @@ named!(list_op<CompleteStr, Risp>,
     )
 );
 
+named!(union<CompleteStr, Risp>,
+    do_parse!(tag!("union") >> (Risp::LPrelude(Prelude::Union)))
+);
+
+named!(intersection<CompleteStr, Risp>,
+    do_parse!(tag!("intersection") >> (Risp::LPrelude(Prelude::Intersection)))
+);
+
+named!(difference<CompleteStr, Risp>,
+    do_parse!(tag!("difference") >> (Risp::LPrelude(Prelude::Difference)))
+);
+
+named!(subset<CompleteStr, Risp>,
+    do_parse!(tag!("subset?") >> (Risp::LPrelude(Prelude::Subset)))
+);
+
+named!(set_to_list<CompleteStr, Risp>,
+    do_parse!(tag!("set->list") >> (Risp::LPrelude(Prelude::SetToList)))
+);
+
+named!(list_to_set<CompleteStr, Risp>,
+    do_parse!(tag!("list->set") >> (Risp::LPrelude(Prelude::ListToSet)))
+);
+
+named!(str_concat<CompleteStr, Risp>,
+    do_parse!(tag!("str-concat") >> (Risp::LPrelude(Prelude::StrConcat)))
+);
+
+named!(str_len<CompleteStr, Risp>,
+    do_parse!(tag!("str-len") >> (Risp::LPrelude(Prelude::StrLen)))
+);
+
+named!(substr<CompleteStr, Risp>,
+    do_parse!(tag!("substr") >> (Risp::LPrelude(Prelude::Substr)))
+);
+
+named!(str_split<CompleteStr, Risp>,
+    do_parse!(tag!("str-split") >> (Risp::LPrelude(Prelude::StrSplit)))
+);
+
+named!(str_join<CompleteStr, Risp>,
+    do_parse!(tag!("str-join") >> (Risp::LPrelude(Prelude::StrJoin)))
+);
+
+named!(str_trim<CompleteStr, Risp>,
+    do_parse!(tag!("str-trim") >> (Risp::LPrelude(Prelude::StrTrim)))
+);
+
+named!(str_upper<CompleteStr, Risp>,
+    do_parse!(tag!("str-upper") >> (Risp::LPrelude(Prelude::StrUpper)))
+);
+
+named!(str_lower<CompleteStr, Risp>,
+    do_parse!(tag!("str-lower") >> (Risp::LPrelude(Prelude::StrLower)))
+);
+
+named!(str_index_of<CompleteStr, Risp>,
+    do_parse!(tag!("str-index-of") >> (Risp::LPrelude(Prelude::StrIndexOf)))
+);
+
+named!(str_replace<CompleteStr, Risp>,
+    do_parse!(tag!("str-replace") >> (Risp::LPrelude(Prelude::StrReplace)))
+);
+
+named!(starts_with<CompleteStr, Risp>,
+    do_parse!(tag!("starts-with?") >> (Risp::LPrelude(Prelude::StartsWith)))
+);
+
+named!(ends_with<CompleteStr, Risp>,
+    do_parse!(tag!("ends-with?") >> (Risp::LPrelude(Prelude::EndsWith)))
+);
+
+named!(str_to_chars<CompleteStr, Risp>,
+    do_parse!(tag!("str->chars") >> (Risp::LPrelude(Prelude::StrToChars)))
+);
+
+named!(chars_to_str<CompleteStr, Risp>,
+    do_parse!(tag!("chars->str") >> (Risp::LPrelude(Prelude::CharsToStr)))
+);
+
+named!(println<CompleteStr, Risp>,
+    do_parse!(tag!("println") >> (Risp::LPrelude(Prelude::Println)))
+);
+
+named!(print<CompleteStr, Risp>,
+    do_parse!(tag!("print") >> (Risp::LPrelude(Prelude::Print)))
+);
+
+named!(show<CompleteStr, Risp>,
+    do_parse!(tag!("show") >> (Risp::LPrelude(Prelude::Show)))
+);
+
+named!(format<CompleteStr, Risp>,
+    do_parse!(tag!("format") >> (Risp::LPrelude(Prelude::Format)))
+);
+
+named!(read_file<CompleteStr, Risp>,
+    do_parse!(tag!("read-file") >> (Risp::LPrelude(Prelude::ReadFile)))
+);
+
+named!(write_file<CompleteStr, Risp>,
+    do_parse!(tag!("write-file") >> (Risp::LPrelude(Prelude::WriteFile)))
+);
+
+named!(append_file<CompleteStr, Risp>,
+    do_parse!(tag!("append-file") >> (Risp::LPrelude(Prelude::AppendFile)))
+);
+
+named!(file_exists<CompleteStr, Risp>,
+    do_parse!(tag!("file-exists?") >> (Risp::LPrelude(Prelude::FileExists)))
+);
+
+named!(list_dir<CompleteStr, Risp>,
+    do_parse!(tag!("list-dir") >> (Risp::LPrelude(Prelude::ListDir)))
+);
+
+named!(make_dir<CompleteStr, Risp>,
+    do_parse!(tag!("make-dir") >> (Risp::LPrelude(Prelude::MakeDir)))
+);
+
+named!(remove_file<CompleteStr, Risp>,
+    do_parse!(tag!("remove-file") >> (Risp::LPrelude(Prelude::RemoveFile)))
+);
+
+named!(read_lines<CompleteStr, Risp>,
+    do_parse!(tag!("read-lines") >> (Risp::LPrelude(Prelude::ReadLines)))
+);
+
+named!(getenv<CompleteStr, Risp>,
+    do_parse!(tag!("getenv") >> (Risp::LPrelude(Prelude::Getenv)))
+);
+
+named!(setenv<CompleteStr, Risp>,
+    do_parse!(tag!("setenv") >> (Risp::LPrelude(Prelude::Setenv)))
+);
+
+named!(exit<CompleteStr, Risp>,
+    do_parse!(tag!("exit") >> (Risp::LPrelude(Prelude::Exit)))
+);
+
+named!(run<CompleteStr, Risp>,
+    do_parse!(tag!("run") >> (Risp::LPrelude(Prelude::Run)))
+);
+
+named!(load<CompleteStr, Risp>,
+    do_parse!(tag!("load") >> (Risp::LPrelude(Prelude::Load)))
+);
+
+named!(import<CompleteStr, Risp>,
+    do_parse!(tag!("import") >> (Risp::LPrelude(Prelude::Import)))
+);
+
+named!(lbreak<CompleteStr, Risp>,
+    do_parse!(tag!("break") >> (Risp::LPrelude(Prelude::Break)))
+);
+
+named!(trace<CompleteStr, Risp>,
+    do_parse!(tag!("trace") >> (Risp::LPrelude(Prelude::Trace)))
+);
+
+named!(untrace<CompleteStr, Risp>,
+    do_parse!(tag!("untrace") >> (Risp::LPrelude(Prelude::Untrace)))
+);
+
+named!(profile<CompleteStr, Risp>,
+    do_parse!(tag!("profile") >> (Risp::LPrelude(Prelude::Profile)))
+);
+
+named!(deftest<CompleteStr, Risp>,
+    do_parse!(tag!("deftest") >> (Risp::LPrelude(Prelude::Deftest)))
+);
+
+named!(assert<CompleteStr, Risp>,
+    do_parse!(tag!("assert") >> (Risp::LPrelude(Prelude::Assert)))
+);
+
+named!(assert_eq<CompleteStr, Risp>,
+    do_parse!(tag!("assert-eq") >> (Risp::LPrelude(Prelude::AssertEq)))
+);
+
+named!(assert_error<CompleteStr, Risp>,
+    do_parse!(tag!("assert-error") >> (Risp::LPrelude(Prelude::AssertError)))
+);
+
+named!(map_op<CompleteStr, Risp>,
+    alt!(
+        assoc |
+        dissoc |
+        get_in |
+        keys |
+        vals |
+        contains |
+        merge
+    )
+);
+
+named!(set_op<CompleteStr, Risp>,
+    alt!(
+        union |
+        intersection |
+        difference |
+        subset |
+        set_to_list |
+        list_to_set
+    )
+);
+
+named!(str_op<CompleteStr, Risp>,
+    alt!(
+        str_concat |
+        str_len |
+        substr |
+        str_split |
+        str_join |
+        str_trim |
+        str_upper |
+        str_lower |
+        str_index_of |
+        str_replace |
+        starts_with |
+        ends_with |
+        str_to_chars |
+        chars_to_str
+    )
+);
+
+named!(io_op<CompleteStr, Risp>,
+    alt!(
+        println |
+        print |
+        show |
+        format
+    )
+);
+
+named!(fs_op<CompleteStr, Risp>,
+    alt!(
+        read_file |
+        write_file |
+        append_file |
+        file_exists |
+        list_dir |
+        make_dir |
+        remove_file |
+        read_lines
+    )
+);
+
+named!(proc_op<CompleteStr, Risp>,
+    alt!(
+        getenv |
+        setenv |
+        exit |
+        run
+    )
+);
+
+named!(mod_op<CompleteStr, Risp>,
+    alt!(
+        load |
+        import
+    )
+);
+
+named!(debug_op<CompleteStr, Risp>,
+    alt!(
+        lbreak |
+        trace |
+        untrace |
+        profile
+    )
+);
+
+named!(test_op<CompleteStr, Risp>,
+    alt!(
+        deftest |
+        // before `assert`, so they aren't taken for it
+        assert_eq |
+        assert_error |
+        assert
+    )
+);
+
 named!(log_op<CompleteStr, Risp>,
     alt!(
         not |
@@ -213,12 +528,24 @@ named!(log_op<CompleteStr, Risp>,
 named!(
     pub risp_prelude<CompleteStr, Risp>,
     alt!(
+        // before list_op, so `list->set` and `list-dir` aren't taken for `list`
+        set_op |
+        fs_op |
+        // before `get`, so `getenv` isn't taken for it
+        proc_op |
+        mod_op |
         list_op |
+        map_op |
+        str_op |
+        io_op |
+        debug_op |
+        test_op |
         log_op |
         lambda |
         ldo |
         llet |
         select |
+        lmatch |
         fun |
         curry |
         uncurry |
@@ -232,10 +559,10 @@ named!(
         rif |
         eq |
         neq |
-        gt |
-        lt |
         gte |
         lte |
+        gt |
+        lt |
         get |
         put
     )
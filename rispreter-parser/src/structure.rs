@@ -15,6 +15,8 @@ pub enum Risp {
     LComment,
     LBool(bool),
     LVec(TypedVec),
+    LMap(Vec<(Risp, Risp)>),
+    LSet(Vec<Risp>),
     Sexpr(Vec<Risp>),
     Qexpr(Vec<Risp>),
 }
@@ -56,6 +58,7 @@ pub enum Prelude {
     Def,
     Put,
     Select,
+    Match,
     List,
     Head,
     Tail,
@@ -68,6 +71,59 @@ pub enum Prelude {
     Elemen,
     Map,
     Filter,
+    Assoc,
+    Dissoc,
+    GetIn,
+    Keys,
+    Vals,
+    Contains,
+    Merge,
+    Union,
+    Intersection,
+    Difference,
+    Subset,
+    SetToList,
+    ListToSet,
+    StrConcat,
+    StrLen,
+    Substr,
+    StrSplit,
+    StrJoin,
+    StrTrim,
+    StrUpper,
+    StrLower,
+    StrIndexOf,
+    StrReplace,
+    StartsWith,
+    EndsWith,
+    StrToChars,
+    CharsToStr,
+    Println,
+    Print,
+    Show,
+    Format,
+    ReadFile,
+    WriteFile,
+    AppendFile,
+    FileExists,
+    ListDir,
+    MakeDir,
+    RemoveFile,
+    ReadLines,
+    Getenv,
+    Setenv,
+    Exit,
+    Run,
+    Load,
+    Import,
+    Break,
+    Trace,
+    Untrace,
+    Profile,
+    Deftest,
+    Assert,
+    AssertEq,
+    AssertError,
     Add,
     Sub,
     Mul,
@@ -0,0 +1,221 @@
+use crate::complete_parser::risp_atom;
+use nom::types::CompleteStr;
+
+/// Openers of the forms made of other forms, longest first
+const OPENERS: [(&str, char); 5] = [
+    ("#s{", '}'),
+    ("#{", '}'),
+    ("#[", ']'),
+    ("(", ')'),
+    ("{", '}'),
+];
+
+/// Where something is in the source, as byte offsets from its start
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// A form as it was written, with the comments and blank lines around it
+#[derive(Debug, PartialEq)]
+pub enum Syntax {
+    /// the text of a value that isn't made of others, like `12.5`, `"a"` or `fun`
+    Atom(String),
+    List {
+        open: &'static str,
+        close: char,
+        items: Vec<Node>,
+    },
+    /// the text after the `;`, `trailing` when it follows a form on the same line
+    Comment { text: String, trailing: bool },
+    /// one or more empty lines between two forms
+    Blank,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Node {
+    pub syntax: Syntax,
+    pub span: Span,
+}
+
+/// Why and where a source can't be read
+#[derive(Debug, PartialEq)]
+pub struct SyntaxError {
+    pub msg: String,
+    pub span: Span,
+}
+
+impl Node {
+    /// Text of an atom
+    pub fn atom(&self) -> Option<&str> {
+        match &self.syntax {
+            Syntax::Atom(atom) => Some(atom),
+            _ => None,
+        }
+    }
+
+    /// Elements of a list, without its comments and blank lines
+    pub fn forms(&self) -> impl Iterator<Item = &Node> {
+        let items = match &self.syntax {
+            Syntax::List { items, .. } => items.as_slice(),
+            _ => &[],
+        };
+        items.iter().filter(|item| item.is_form())
+    }
+
+    /// Whether this is a value, and not a comment or a blank line
+    pub fn is_form(&self) -> bool {
+        match self.syntax {
+            Syntax::Atom(_) | Syntax::List { .. } => true,
+            Syntax::Comment { .. } | Syntax::Blank => false,
+        }
+    }
+}
+
+/// Reads a program as it was written, comments and blank lines included, with
+/// where every form is. Checks the brackets are balanced and the atoms can be
+/// read, but not what `parse_risp_program` does for them, like escapes in strings.
+/// A leading `#!` line is skipped, the `.` that can end a form too
+pub fn read_syntax(input: &str) -> Result<Vec<Node>, SyntaxError> {
+    let mut rest = input;
+    if input.starts_with("#!") {
+        rest = &input[input.find('\n').unwrap_or(input.len())..];
+    }
+    read_nodes(input, &mut rest, None)
+}
+
+/// Reads the forms up to `close`, with where the list it closes was opened, or
+/// to the end of the input without one
+fn read_nodes(
+    input: &str,
+    rest: &mut &str,
+    close: Option<(char, usize)>,
+) -> Result<Vec<Node>, SyntaxError> {
+    let offset = |rest: &str| input.len() - rest.len();
+    let mut nodes: Vec<Node> = Vec::new();
+    loop {
+        let skipped = rest.len()
+            - rest
+                .trim_start_matches(|c: char| c.is_whitespace() || c == '.')
+                .len();
+        let newlines = rest[..skipped].matches('\n').count();
+        let last_is_form = nodes.last().is_some_and(Node::is_form);
+        if newlines > 1
+            && nodes
+                .last()
+                .is_some_and(|last| last.syntax != Syntax::Blank)
+        {
+            let start = offset(rest);
+            nodes.push(Node {
+                syntax: Syntax::Blank,
+                span: Span {
+                    start,
+                    end: start + skipped,
+                },
+            });
+        }
+        *rest = &rest[skipped..];
+        let start = offset(rest);
+        let next = match rest.chars().next() {
+            Some(next) => next,
+            None => match close {
+                None => break,
+                Some((close, opened)) => {
+                    return Err(SyntaxError {
+                        msg: format!("Missing a closing '{}'", close),
+                        span: Span {
+                            start: opened,
+                            end: start,
+                        },
+                    })
+                }
+            },
+        };
+        if Some(next) == close.map(|(close, _)| close) {
+            *rest = &rest[1..];
+            break;
+        }
+        let syntax = if next == ';' {
+            let end = rest.find('\n').unwrap_or(rest.len());
+            let text = rest[1..end].trim_end().to_string();
+            *rest = &rest[end..];
+            Syntax::Comment {
+                text,
+                trailing: newlines == 0 && last_is_form,
+            }
+        } else if let Some((open, close)) = OPENERS.iter().find(|(open, _)| rest.starts_with(open))
+        {
+            *rest = &rest[open.len()..];
+            Syntax::List {
+                open,
+                close: *close,
+                items: read_nodes(input, rest, Some((*close, start)))?,
+            }
+        } else {
+            match risp_atom(CompleteStr(rest)) {
+                Ok((next, atom)) => {
+                    *rest = next.0;
+                    Syntax::Atom(atom.to_string())
+                }
+                Err(_) => {
+                    let line = rest.lines().next().unwrap_or("");
+                    return Err(SyntaxError {
+                        msg: format!("Can't read '{}'", line),
+                        span: Span {
+                            start,
+                            end: start + line.len(),
+                        },
+                    });
+                }
+            }
+        };
+        nodes.push(Node {
+            syntax,
+            span: Span {
+                start,
+                end: offset(rest),
+            },
+        });
+    }
+    if nodes
+        .last()
+        .is_some_and(|last| last.syntax == Syntax::Blank)
+    {
+        nodes.pop();
+    }
+    Ok(nodes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_syntax_spans() {
+        let program = "#!/bin/rispreter\n(def {a} 1) ; one\n\n\n{b \"c\"}.";
+        let nodes = read_syntax(program).unwrap();
+        let spans: Vec<&str> = nodes
+            .iter()
+            .map(|node| &program[node.span.start..node.span.end])
+            .collect();
+        assert_eq!(vec!["(def {a} 1)", "; one", "\n\n\n", "{b \"c\"}"], spans);
+        assert_eq!(
+            Syntax::Comment {
+                text: " one".to_string(),
+                trailing: true
+            },
+            nodes[1].syntax
+        );
+        let def: Vec<&str> = nodes[0].forms().filter_map(Node::atom).collect();
+        assert_eq!(vec!["def", "1"], def);
+
+        let err = read_syntax("(+ 1\n  (- 2 3)").unwrap_err();
+        assert_eq!("Missing a closing ')'", err.msg);
+        assert_eq!(Span { start: 0, end: 14 }, err.span);
+        assert_eq!(
+            Span { start: 3, end: 6 },
+            read_syntax("(a ]b)").unwrap_err().span
+        );
+    }
+}
@@ -2,9 +2,13 @@
 
 extern crate test;
 
+use rispreter_repl::lval::lval_profile::CountingAlloc;
 use rispreter_repl::repl::RispRepl;
 use std::io;
 
+#[global_allocator]
+static ALLOC: CountingAlloc = CountingAlloc;
+
 fn main() -> io::Result<()> {
     let risp_repl = RispRepl::new();
     //Ok(risp_repl.run_instruction("(fib 10)"))
@@ -1,14 +1,78 @@
 use crate::lval::lval_def::*;
 use crate::lval::lval_env::Lenv;
+use crate::lval::lval_error::Lerror;
 use crate::lval::lval_eval::*;
-use crate::read::read;
-use rispreter_parser::complete_parser::parse_risp;
+use crate::read::{read, read_spanned};
+use rispreter_parser::complete_parser::{parse_risp, parse_risp_program};
+use rispreter_parser::structure::Risp;
+use rispreter_parser::syntax::read_syntax;
+use std::path::Path;
 use std::rc::Rc;
 
 pub fn eval_rispreter(lenv: &Rc<Lenv>, input: &str) -> Lval {
     lval_eval(lenv, &mut read(parse_risp(input)))
 }
 
+/// Evaluates every form of a program in order, giving back the value of the last
+/// one, or the first error
+pub fn eval_rispreter_program(lenv: &Rc<Lenv>, input: &str) -> Lval {
+    let context = lenv.context();
+    // the forms only need to know where they were written while it's recorded
+    let spanned = match context.coverage().is_recording() {
+        true => read_syntax(input).ok().map(|nodes| {
+            let name = context
+                .current_file()
+                .map_or("<input>".to_string(), |file| file.display().to_string());
+            (context.coverage().add_source(name, input), nodes)
+        }),
+        false => None,
+    };
+    let mut nodes = spanned
+        .iter()
+        .flat_map(|(_, nodes)| nodes.iter().filter(|node| node.is_form()));
+    let mut res = Lval::lval_sexpr();
+    for form in parse_risp_program(input) {
+        if form == Risp::LComment {
+            continue;
+        }
+        let mut lval = match (&spanned, nodes.next()) {
+            (Some((source, _)), Some(node)) => read_spanned(form, node, *source),
+            _ => read(Some(form)),
+        };
+        res = lval_eval(lenv, &mut lval);
+        if let LvalType::LVAL_ERR(_) = res.ltype {
+            break;
+        }
+    }
+    res
+}
+
+/// Evaluates a .rspr file as a program, `load` and `import` inside it are resolved
+/// from its directory
+pub fn eval_rispreter_file(lenv: &Rc<Lenv>, path: &Path) -> Lval {
+    let io_err = |err: std::io::Error| {
+        Lval::lval_err(Lerror::IoError {
+            path: path.display().to_string(),
+            msg: err.to_string(),
+        })
+    };
+    let program = match std::fs::read_to_string(path) {
+        Ok(program) => program,
+        Err(err) => return io_err(err),
+    };
+    // pipes like /dev/stdin can be read but not resolved
+    let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    let context = lenv.context();
+    if let Err(chain) = context.enter_file(path) {
+        return Lval::lval_err(Lerror::CircularImport {
+            chain: chain.iter().map(|f| f.display().to_string()).collect(),
+        });
+    }
+    let res = eval_rispreter_program(lenv, &program);
+    context.leave_file();
+    res
+}
+
 #[cfg(test)]
 pub mod tests {
     use super::*;
@@ -4,7 +4,11 @@ extern crate rispreter_parser;
 #[macro_use]
 extern crate clap;
 extern crate fnv;
+extern crate serde_json;
 pub mod eval;
+pub mod lsp;
 pub mod lval;
 pub mod read;
 pub mod repl;
+#[cfg(test)]
+mod test_util;
@@ -0,0 +1,452 @@
+use crate::lval::lval_error::Lerror;
+use rispreter_parser::complete_parser::{parse_risp, parse_risp_program};
+use rispreter_parser::structure::Risp;
+use rispreter_parser::syntax::{read_syntax, Node, Span, Syntax, SyntaxError};
+
+/// A name a document binds with `def` or `fun`
+#[derive(Debug, PartialEq)]
+pub struct Definition {
+    pub name: String,
+    /// where the name is in its `{name ...}` list
+    pub span: Span,
+    /// the formals of a `fun`, `None` for a `def`
+    pub formals: Option<Vec<String>>,
+    /// the comment lines right above the definition
+    pub doc: Vec<String>,
+}
+
+impl Definition {
+    /// How a function is called, like `(double x)`, or the name of a value
+    pub fn signature(&self) -> String {
+        match &self.formals {
+            Some(formals) => {
+                let call: Vec<&str> = std::iter::once(&self.name)
+                    .chain(formals)
+                    .map(String::as_str)
+                    .collect();
+                format!("({})", call.join(" "))
+            }
+            None => self.name.clone(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Diagnostic {
+    pub span: Span,
+    pub severity: Severity,
+    pub msg: String,
+}
+
+/// An open .rspr file, read once for everything the server is asked about it
+pub struct Document {
+    pub text: String,
+    nodes: Result<Vec<Node>, SyntaxError>,
+}
+
+impl Document {
+    pub fn new(text: String) -> Self {
+        let nodes = read_syntax(&text);
+        Document { text, nodes }
+    }
+
+    /// Syntax errors, and the symbols that aren't bound where they are: by the
+    /// `def` and `fun` of the document, the formals of the function, the pattern
+    /// of the `match` clause or the `=` of the scope they're in, or by what
+    /// `known` says is bound outside of the document
+    pub fn diagnostics(&self, known: impl Fn(&str) -> bool) -> Vec<Diagnostic> {
+        let nodes = match &self.nodes {
+            Ok(nodes) => nodes,
+            Err(err) => {
+                return vec![Diagnostic {
+                    span: err.span,
+                    severity: Severity::Error,
+                    msg: err.msg.clone(),
+                }]
+            }
+        };
+        let mut diagnostics = Vec::new();
+        let parsed = parse_risp_program(&self.text);
+        let failed = parsed
+            .iter()
+            .enumerate()
+            .find_map(|(i, form)| syntax_error(form).map(|msg| (i, msg)));
+        if let Some((i, msg)) = failed {
+            // the parser has a form for every comment and form of the program
+            let end = self.text.len();
+            diagnostics.push(Diagnostic {
+                span: nodes
+                    .iter()
+                    .filter(|node| node.syntax != Syntax::Blank)
+                    .nth(i)
+                    .map_or(Span { start: end, end }, |node| node.span),
+                severity: Severity::Error,
+                msg: msg.to_string(),
+            });
+        }
+        let mut scope = Vec::new();
+        for node in nodes {
+            globals(node, &mut scope);
+        }
+        let nodes: Vec<&Node> = nodes.iter().filter(|node| node.is_form()).collect();
+        in_scope(Vec::new(), &nodes, &mut scope, &known, &mut diagnostics);
+        diagnostics
+    }
+
+    /// Names bound with `def` and `fun` anywhere in the document, in order
+    pub fn definitions(&self) -> Vec<Definition> {
+        let mut definitions = Vec::new();
+        if let Ok(nodes) = &self.nodes {
+            let mut doc = Vec::new();
+            for node in nodes {
+                collect_definitions(node, &doc, &mut definitions);
+                match &node.syntax {
+                    Syntax::Comment {
+                        text,
+                        trailing: false,
+                    } => doc.push(text.trim().to_string()),
+                    _ => doc.clear(),
+                }
+            }
+        }
+        definitions
+    }
+
+    /// The atom at a byte offset, like a symbol being hovered
+    pub fn atom_at(&self, offset: usize) -> Option<(&str, Span)> {
+        let mut nodes = self.nodes.as_ref().ok()?.as_slice();
+        loop {
+            let node = nodes
+                .iter()
+                .find(|node| node.span.start <= offset && offset <= node.span.end)?;
+            match &node.syntax {
+                Syntax::Atom(atom) => return Some((atom, node.span)),
+                Syntax::List { items, .. } => nodes = items,
+                _ => return None,
+            }
+        }
+    }
+
+    /// The part of a symbol before a byte offset, what is being completed
+    pub fn prefix_at(&self, offset: usize) -> &str {
+        let before = &self.text[..offset.min(self.text.len())];
+        let start = before
+            .rfind(|c: char| c.is_whitespace() || "(){}[]\"';#".contains(c))
+            .map_or(0, |at| at + 1);
+        &before[start..]
+    }
+
+    /// Byte offset of an LSP position, a line and a column in UTF-16 code units
+    pub fn offset(&self, line: usize, character: usize) -> usize {
+        let line_start: usize = self
+            .text
+            .split('\n')
+            .take(line)
+            .map(|line| line.len() + 1)
+            .sum();
+        let line_start = line_start.min(self.text.len());
+        let mut units = 0;
+        for (at, c) in self.text[line_start..].char_indices() {
+            if units >= character || c == '\n' {
+                return line_start + at;
+            }
+            units += c.len_utf16();
+        }
+        self.text.len()
+    }
+
+    /// LSP position of a byte offset, as a line and a column in UTF-16 code units
+    pub fn position(&self, offset: usize) -> (usize, usize) {
+        let before = &self.text[..offset.min(self.text.len())];
+        let line_start = before.rfind('\n').map_or(0, |at| at + 1);
+        (
+            before.matches('\n').count(),
+            before[line_start..].encode_utf16().count(),
+        )
+    }
+}
+
+/// The first syntax error in a parsed form, like a bad escape in a string
+fn syntax_error(form: &Risp) -> Option<&str> {
+    match form {
+        Risp::LSyntaxErr(msg) => Some(msg),
+        Risp::Sexpr(items) | Risp::Qexpr(items) | Risp::LSet(items) => {
+            items.iter().find_map(syntax_error)
+        }
+        Risp::LMap(pairs) => pairs
+            .iter()
+            .find_map(|(key, val)| syntax_error(key).or_else(|| syntax_error(val))),
+        _ => None,
+    }
+}
+
+/// Head of an S-expression, or of a Q-expression evaluated as one, the keyword or
+/// function it calls
+fn head(node: &Node) -> Option<&str> {
+    match &node.syntax {
+        Syntax::List { open: "(", .. } | Syntax::List { open: "{", .. } => {
+            node.forms().next().and_then(Node::atom)
+        }
+        _ => None,
+    }
+}
+
+/// Symbols of the `{name ...}` list of a `def`, `=`, `put`, `fun` or `\`
+fn names(node: &Node) -> impl Iterator<Item = &str> {
+    node.forms()
+        .nth(1)
+        .into_iter()
+        .flat_map(|names| names.forms().filter_map(Node::atom))
+}
+
+/// Names a form binds in the global env wherever it is, with `def` and `fun`
+fn globals<'a>(node: &'a Node, bound: &mut Vec<&'a str>) {
+    match head(node) {
+        Some("def") => bound.extend(names(node)),
+        Some("fun") => bound.extend(names(node).next()),
+        _ => {}
+    }
+    for item in node.forms() {
+        globals(item, bound);
+    }
+}
+
+/// Names a form binds in the env it's evaluated in, with `=` and `put`, but not
+/// those bound in the scopes it opens
+fn locals<'a>(node: &'a Node, bound: &mut Vec<&'a str>) {
+    match head(node) {
+        Some("=") | Some("put") => bound.extend(names(node)),
+        Some("fun") | Some("\\") | Some("let") | Some("match") => return,
+        _ => {}
+    }
+    for item in node.forms() {
+        locals(item, bound);
+    }
+}
+
+/// Symbols a `match` pattern binds: all but `_` and `&`, and the predicates of
+/// type patterns like `(num? n)`
+fn pattern_names<'a>(pattern: &'a Node, bound: &mut Vec<&'a str>) {
+    match &pattern.syntax {
+        Syntax::Atom(atom) => {
+            if let Some(Risp::LSymbol(_)) = parse_risp(atom) {
+                if atom != "_" && atom != "&" {
+                    bound.push(atom);
+                }
+            }
+        }
+        Syntax::List { open: "(", .. } => {
+            if let Some(pattern) = pattern.forms().nth(1) {
+                pattern_names(pattern, bound);
+            }
+        }
+        Syntax::List { open: "{", .. } => {
+            for item in pattern.forms() {
+                pattern_names(item, bound);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Checks forms that have a scope of their own, where `bound` and what they bind
+/// with `=` are bound on top of the names of the enclosing `scope`
+fn in_scope<'a>(
+    mut bound: Vec<&'a str>,
+    forms: &[&'a Node],
+    scope: &mut Vec<&'a str>,
+    known: &dyn Fn(&str) -> bool,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let depth = scope.len();
+    scope.append(&mut bound);
+    for form in forms {
+        locals(form, scope);
+    }
+    for form in forms {
+        unbound(form, scope, known, diagnostics);
+    }
+    scope.truncate(depth);
+}
+
+fn unbound<'a>(
+    node: &'a Node,
+    scope: &mut Vec<&'a str>,
+    known: &dyn Fn(&str) -> bool,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let mut forms = node.forms();
+    match head(node) {
+        // what a module binds is only known once it's loaded
+        Some("import") => {}
+        Some("def") | Some("=") | Some("put") => {
+            for item in forms.skip(2) {
+                unbound(item, scope, known, diagnostics);
+            }
+        }
+        Some(fun @ "fun") | Some(fun @ "\\") => {
+            // the name of a `fun` is global, only its formals are local
+            let formals = names(node).skip(if fun == "fun" { 1 } else { 0 });
+            let body: Vec<&Node> = forms.skip(2).collect();
+            in_scope(formals.collect(), &body, scope, known, diagnostics);
+        }
+        Some("let") => {
+            let body: Vec<&Node> = forms.skip(1).collect();
+            in_scope(Vec::new(), &body, scope, known, diagnostics);
+        }
+        Some("match") => {
+            if let Some(value) = forms.nth(1) {
+                unbound(value, scope, known, diagnostics);
+            }
+            for clause in forms {
+                match clause.syntax {
+                    Syntax::List { open: "{", .. } => {
+                        let mut clause = clause.forms();
+                        let mut bound = Vec::new();
+                        if let Some(pattern) = clause.next() {
+                            pattern_names(pattern, &mut bound);
+                        }
+                        let body: Vec<&Node> = clause.collect();
+                        in_scope(bound, &body, scope, known, diagnostics);
+                    }
+                    _ => unbound(clause, scope, known, diagnostics),
+                }
+            }
+        }
+        _ => match &node.syntax {
+            Syntax::List { .. } => {
+                for item in forms {
+                    unbound(item, scope, known, diagnostics);
+                }
+            }
+            Syntax::Atom(atom) => {
+                if let Some(Risp::LSymbol(sym)) = parse_risp(atom) {
+                    if !scope.contains(&atom.as_str())
+                        && !known(&sym)
+                        && sym != "&"
+                        && !sym.contains('/')
+                    {
+                        diagnostics.push(Diagnostic {
+                            span: node.span,
+                            severity: Severity::Warning,
+                            msg: Lerror::SymbolNotBinded { sym }.to_string(),
+                        });
+                    }
+                }
+            }
+            _ => {}
+        },
+    }
+}
+
+fn collect_definitions(node: &Node, doc: &[String], definitions: &mut Vec<Definition>) {
+    let fun = match head(node) {
+        Some("fun") => true,
+        Some("def") => false,
+        _ => {
+            for item in node.forms() {
+                collect_definitions(item, &[], definitions);
+            }
+            return;
+        }
+    };
+    let names: Vec<&Node> = match node.forms().nth(1) {
+        Some(names) => names.forms().filter(|name| name.atom().is_some()).collect(),
+        None => return,
+    };
+    let mut names = names.into_iter();
+    if fun {
+        if let Some(name) = names.next() {
+            definitions.push(Definition {
+                name: name.atom().unwrap().to_string(),
+                span: name.span,
+                formals: Some(names.filter_map(Node::atom).map(String::from).collect()),
+                doc: doc.to_vec(),
+            });
+        }
+    } else {
+        definitions.extend(names.map(|name| Definition {
+            name: name.atom().unwrap().to_string(),
+            span: name.span,
+            formals: None,
+            doc: doc.to_vec(),
+        }));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn document_definitions_and_diagnostics() {
+        let doc = Document::new(
+            "; doubles\n; a number\n(fun {double x} {* 2 x})\n(def {a b} 1 (double c))\nmath/pi (import \"math\" {pi})"
+                .to_string(),
+        );
+        let definitions = doc.definitions();
+        let names: Vec<(&str, Option<usize>, usize)> = definitions
+            .iter()
+            .map(|def| {
+                (
+                    &def.name[..],
+                    def.formals.as_ref().map(Vec::len),
+                    def.doc.len(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            vec![("double", Some(1), 2), ("a", None, 0), ("b", None, 0)],
+            names
+        );
+
+        let diagnostics = doc.diagnostics(|_| false);
+        assert_eq!(1, diagnostics.len(), "{:?}", diagnostics);
+        assert_eq!(Severity::Warning, diagnostics[0].severity);
+        assert_eq!(
+            "c",
+            &doc.text[diagnostics[0].span.start..diagnostics[0].span.end]
+        );
+
+        let doc = Document::new("(+ 1 2)\n; fine\n(str-len \"\\q\")".to_string());
+        let diagnostics = doc.diagnostics(|_| true);
+        assert_eq!(Severity::Error, diagnostics[0].severity);
+        assert_eq!((2, 0), doc.position(diagnostics[0].span.start));
+    }
+
+    #[test]
+    fn document_diagnostics_are_scoped() {
+        let unbound = |text: &str| -> Vec<String> {
+            let doc = Document::new(text.to_string());
+            doc.diagnostics(|_| false)
+                .iter()
+                .map(|diagnostic| doc.text[diagnostic.span.start..diagnostic.span.end].to_string())
+                .collect()
+        };
+        assert!(unbound("(def {x} 1) (match x {{a b} a})").is_empty());
+        assert!(unbound("(match {1 2} {{a & as} (> a 1) as} {(num? n) n} {_ 0})").is_empty());
+        assert!(unbound("(let {do (= {y} 1) y})").is_empty());
+        assert!(unbound("(fun {f x} {+ x (g x)}) (fun {g y} {y})").is_empty());
+        assert_eq!(
+            vec!["x", "a", "y"],
+            unbound("(fun {f x} {x}) x (match 1 {a a}) a (\\ {y} {y}) (let {= {y} 1}) y")
+        );
+    }
+
+    #[test]
+    fn document_positions() {
+        let doc = Document::new("(def {ç} 1)\n(str-len \"🦀\" ç)".to_string());
+        let offset = doc.offset(1, 14);
+        assert_eq!("ç", &doc.text[offset..offset + 'ç'.len_utf8()]);
+        assert_eq!((1, 14), doc.position(offset));
+        assert_eq!(Some("ç"), doc.atom_at(offset).map(|(atom, _)| atom));
+        assert_eq!("str-", doc.prefix_at(doc.offset(1, 5)));
+        assert_eq!(doc.text.len(), doc.offset(7, 0));
+    }
+}
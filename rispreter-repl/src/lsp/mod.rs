@@ -0,0 +1,456 @@
+use crate::lval::lval_def::{Lval, LvalType};
+use crate::lval::lval_doc::{builtin_doc, BUILTIN_DOCS};
+use crate::lval::lval_env::Lenv;
+use rispreter_parser::format::{format_program, DEFAULT_WIDTH};
+use rispreter_parser::syntax::Span;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+use std::rc::Rc;
+
+mod analysis;
+
+use analysis::{Document, Severity};
+
+/// JSON-RPC error code of the messages that aren't JSON
+const PARSE_ERROR: i64 = -32700;
+/// JSON-RPC error code of the requests for a method the server doesn't have
+const METHOD_NOT_FOUND: i64 = -32601;
+
+/// LSP kinds of the completion items
+const FUNCTION: u8 = 3;
+const VARIABLE: u8 = 6;
+const KEYWORD: u8 = 14;
+
+/// A Language Server Protocol server for .rspr files, talking JSON-RPC. Besides
+/// the documents it's given, it knows the bindings of the env it's started from,
+/// like the ones of the prelude
+pub struct LspServer {
+    documents: HashMap<String, Document>,
+    globals: Vec<(String, Lval)>,
+    shut_down: bool,
+}
+
+impl LspServer {
+    pub fn new(env: &Rc<Lenv>) -> Self {
+        LspServer {
+            documents: HashMap::new(),
+            globals: env.bindings(),
+            shut_down: false,
+        }
+    }
+
+    /// Answers the messages of a client until it asks to exit, or goes away.
+    /// Returns the status the process should exit with: 0 when the client asked
+    /// to shut down first, 1 otherwise
+    pub fn run(&mut self, mut input: impl BufRead, mut output: impl Write) -> io::Result<i32> {
+        while let Some(message) = read_message(&mut input)? {
+            let message = match message {
+                Ok(message) => message,
+                // its length was read, so the next message can still be
+                Err(err) => {
+                    let response = json!({
+                        "jsonrpc": "2.0",
+                        "id": Value::Null,
+                        "error": {"code": PARSE_ERROR, "message": format!("Parse error: {}", err)},
+                    });
+                    write_message(&mut output, &response)?;
+                    continue;
+                }
+            };
+            let method = message["method"].as_str().unwrap_or("");
+            if method == "exit" {
+                break;
+            }
+            let params = &message["params"];
+            let result = match method {
+                "initialize" => Some(capabilities()),
+                "shutdown" => {
+                    self.shut_down = true;
+                    Some(Value::Null)
+                }
+                "textDocument/didOpen" => {
+                    let document = &params["textDocument"];
+                    let text = document["text"].as_str().unwrap_or("");
+                    self.open(document["uri"].as_str().unwrap_or(""), text, &mut output)?;
+                    None
+                }
+                "textDocument/didChange" => {
+                    // the server asks for the whole text on every change
+                    let changes = params["contentChanges"].as_array();
+                    if let Some(text) = changes.and_then(|changes| changes.last()) {
+                        let uri = params["textDocument"]["uri"].as_str().unwrap_or("");
+                        self.open(uri, text["text"].as_str().unwrap_or(""), &mut output)?;
+                    }
+                    None
+                }
+                "textDocument/didClose" => {
+                    let uri = params["textDocument"]["uri"].as_str().unwrap_or("");
+                    self.documents.remove(uri);
+                    publish_diagnostics(uri, json!([]), &mut output)?;
+                    None
+                }
+                "textDocument/hover" => Some(self.at_position(params, Self::hover)),
+                "textDocument/completion" => Some(self.at_position(params, Self::completion)),
+                "textDocument/definition" => Some(self.at_position(params, Self::definition)),
+                "textDocument/formatting" => Some(self.at_position(params, Self::formatting)),
+                _ => None,
+            };
+            // notifications have no id, and get no answer
+            let id = match message.get("id") {
+                Some(id) => id,
+                None => continue,
+            };
+            let response = match result {
+                Some(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+                None => json!({
+                    "jsonrpc": "2.0",
+                    "id": id,
+                    "error": {"code": METHOD_NOT_FOUND, "message": format!("Unknown method '{}'", method)},
+                }),
+            };
+            write_message(&mut output, &response)?;
+        }
+        Ok(if self.shut_down { 0 } else { 1 })
+    }
+
+    /// Reads a document again, and publishes what's wrong with it
+    fn open(&mut self, uri: &str, text: &str, output: &mut impl Write) -> io::Result<()> {
+        let document = Document::new(text.to_string());
+        let globals = &self.globals;
+        let known =
+            |sym: &str| builtin_doc(sym).is_some() || globals.iter().any(|(name, _)| name == sym);
+        let diagnostics: Vec<Value> = document
+            .diagnostics(known)
+            .into_iter()
+            .map(|diagnostic| {
+                json!({
+                    "range": range(&document, diagnostic.span),
+                    "severity": match diagnostic.severity {
+                        Severity::Error => 1,
+                        Severity::Warning => 2,
+                    },
+                    "source": "rispreter",
+                    "message": diagnostic.msg,
+                })
+            })
+            .collect();
+        self.documents.insert(uri.to_string(), document);
+        publish_diagnostics(uri, Value::Array(diagnostics), output)
+    }
+
+    /// Answers a request about a position of a document, null for the documents
+    /// that aren't open
+    fn at_position(
+        &self,
+        params: &Value,
+        answer: fn(&Self, &str, &Document, usize) -> Value,
+    ) -> Value {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or("");
+        let document = match self.documents.get(uri) {
+            Some(document) => document,
+            None => return Value::Null,
+        };
+        let position = &params["position"];
+        let offset = document.offset(
+            position["line"].as_u64().unwrap_or(0) as usize,
+            position["character"].as_u64().unwrap_or(0) as usize,
+        );
+        answer(self, uri, document, offset)
+    }
+
+    fn hover(&self, _: &str, document: &Document, offset: usize) -> Value {
+        let (atom, span) = match document.atom_at(offset) {
+            Some(atom) => atom,
+            None => return Value::Null,
+        };
+        let definitions = document.definitions();
+        let text = if let Some((usage, summary)) = builtin_doc(atom) {
+            format!("`{}`\n\n{}", usage, summary)
+        } else if let Some(def) = definitions.iter().find(|def| def.name == atom) {
+            let signature = format!("`{}`", def.signature());
+            if def.doc.is_empty() {
+                signature
+            } else {
+                format!("{}\n\n{}", signature, def.doc.join("\n"))
+            }
+        } else if let Some((_, val)) = self.globals.iter().find(|(name, _)| name == atom) {
+            match &val.ltype {
+                LvalType::LVAL_LAMBDA(lambda) => {
+                    let mut usage = vec![atom.to_string()];
+                    usage.extend(lambda.formals.cell.iter().map(|formal| formal.to_string()));
+                    format!("`({})`\n\n`{}`", usage.join(" "), val)
+                }
+                _ => format!("`{}` : {}", atom, val.type_name()),
+            }
+        } else {
+            return Value::Null;
+        };
+        json!({
+            "contents": {"kind": "markdown", "value": text},
+            "range": range(document, span),
+        })
+    }
+
+    fn completion(&self, _: &str, document: &Document, offset: usize) -> Value {
+        let prefix = document.prefix_at(offset);
+        let mut items: Vec<Value> = Vec::new();
+        let mut labels: Vec<String> = Vec::new();
+        let mut add = |label: &str, kind: u8, detail: String| {
+            if label.starts_with(prefix) && !labels.iter().any(|seen| seen == label) {
+                labels.push(label.to_string());
+                items.push(json!({"label": label, "kind": kind, "detail": detail}));
+            }
+        };
+        for def in document.definitions() {
+            match def.formals {
+                Some(_) => add(&def.name, FUNCTION, def.signature()),
+                None => add(&def.name, VARIABLE, String::new()),
+            }
+        }
+        for (name, usage, _) in BUILTIN_DOCS {
+            add(name, KEYWORD, usage.to_string());
+        }
+        for (name, val) in &self.globals {
+            match val.ltype {
+                LvalType::LVAL_LAMBDA(_) => add(name, FUNCTION, val.to_string()),
+                _ => add(name, VARIABLE, val.type_name().to_string()),
+            }
+        }
+        Value::Array(items)
+    }
+
+    fn definition(&self, uri: &str, document: &Document, offset: usize) -> Value {
+        let atom = match document.atom_at(offset) {
+            Some((atom, _)) => atom,
+            None => return Value::Null,
+        };
+        match document.definitions().iter().find(|def| def.name == atom) {
+            Some(def) => json!({"uri": uri, "range": range(document, def.span)}),
+            None => Value::Null,
+        }
+    }
+
+    /// The whole document formatted, as a single edit. None for the documents
+    /// with syntax errors, that can't be formatted
+    fn formatting(&self, _: &str, document: &Document, _: usize) -> Value {
+        match format_program(&document.text, DEFAULT_WIDTH) {
+            Ok(formatted) if formatted != document.text => {
+                let all = Span {
+                    start: 0,
+                    end: document.text.len(),
+                };
+                json!([{"range": range(document, all), "newText": formatted}])
+            }
+            Ok(_) => json!([]),
+            Err(_) => Value::Null,
+        }
+    }
+}
+
+fn capabilities() -> Value {
+    json!({
+        "capabilities": {
+            "textDocumentSync": 1,
+            "hoverProvider": true,
+            "completionProvider": {},
+            "definitionProvider": true,
+            "documentFormattingProvider": true,
+        },
+        "serverInfo": {"name": "rispreter", "version": env!("CARGO_PKG_VERSION")},
+    })
+}
+
+fn range(document: &Document, span: Span) -> Value {
+    let (start_line, start_character) = document.position(span.start);
+    let (end_line, end_character) = document.position(span.end);
+    json!({
+        "start": {"line": start_line, "character": start_character},
+        "end": {"line": end_line, "character": end_character},
+    })
+}
+
+fn publish_diagnostics(uri: &str, diagnostics: Value, output: &mut impl Write) -> io::Result<()> {
+    let notification = json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/publishDiagnostics",
+        "params": {"uri": uri, "diagnostics": diagnostics},
+    });
+    write_message(output, &notification)
+}
+
+/// Reads a message with its `Content-Length` header, None at the end of the input.
+/// A content that isn't JSON is an error of the message, not of the input
+fn read_message(input: &mut impl BufRead) -> io::Result<Option<serde_json::Result<Value>>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim();
+        if header.is_empty() {
+            break;
+        }
+        if let Some(value) = header.strip_prefix("Content-Length:") {
+            length = value.trim().parse::<usize>().ok();
+        }
+    }
+    let length = length.ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            "a message without its Content-Length",
+        )
+    })?;
+    let mut content = vec![0; length];
+    input.read_exact(&mut content)?;
+    Ok(Some(serde_json::from_slice(&content)))
+}
+
+fn write_message(output: &mut impl Write, message: &Value) -> io::Result<()> {
+    let content = message.to_string();
+    write!(
+        output,
+        "Content-Length: {}\r\n\r\n{}",
+        content.len(),
+        content
+    )?;
+    output.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repl::RispRepl;
+
+    fn messages(requests: &[Value]) -> Vec<u8> {
+        let mut input = Vec::new();
+        for request in requests {
+            write_message(&mut input, request).unwrap();
+        }
+        input
+    }
+
+    fn request(id: u64, method: &str, params: Value) -> Value {
+        json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params})
+    }
+
+    fn at(line: u64, character: u64) -> Value {
+        json!({"textDocument": {"uri": "file:///a.rspr"}, "position": {"line": line, "character": character}})
+    }
+
+    #[test]
+    fn lsp_session() {
+        let repl = RispRepl::new();
+        repl.load_prelude();
+        let text = "; doubles a number\n(fun {double x} {* 2 x})\n(def {y}   (double (fib z)))\n";
+        let input = messages(&[
+            request(1, "initialize", json!({})),
+            json!({"jsonrpc": "2.0", "method": "initialized", "params": {}}),
+            json!({"jsonrpc": "2.0", "method": "textDocument/didOpen", "params": {
+                "textDocument": {"uri": "file:///a.rspr", "languageId": "rispreter", "version": 1, "text": text},
+            }}),
+            request(2, "textDocument/hover", at(2, 12)),
+            request(3, "textDocument/hover", at(2, 20)),
+            request(4, "textDocument/hover", at(1, 2)),
+            request(5, "textDocument/completion", at(2, 14)),
+            request(6, "textDocument/definition", at(2, 13)),
+            request(7, "textDocument/formatting", at(0, 0)),
+            json!({"jsonrpc": "2.0", "method": "textDocument/didChange", "params": {
+                "textDocument": {"uri": "file:///a.rspr", "version": 2},
+                "contentChanges": [{"text": "(+ 1\n"}],
+            }}),
+            request(8, "textDocument/rename", at(0, 0)),
+            request(9, "shutdown", Value::Null),
+            json!({"jsonrpc": "2.0", "method": "exit"}),
+        ]);
+        let mut output = Vec::new();
+        let status = LspServer::new(&repl.env())
+            .run(io::Cursor::new(input), &mut output)
+            .unwrap();
+        assert_eq!(0, status);
+
+        let mut output = io::Cursor::new(output);
+        let mut answers = Vec::new();
+        while let Some(message) = read_message(&mut output).unwrap() {
+            answers.push(message.unwrap());
+        }
+        assert_eq!(11, answers.len(), "{:#?}", answers);
+        assert_eq!(
+            json!(true),
+            answers[0]["result"]["capabilities"]["hoverProvider"]
+        );
+
+        let diagnostics = &answers[1]["params"]["diagnostics"];
+        assert_eq!(1, diagnostics.as_array().unwrap().len(), "{}", diagnostics);
+        assert_eq!(json!(2), diagnostics[0]["severity"]);
+        assert_eq!(
+            json!({"line": 2, "character": 24}),
+            diagnostics[0]["range"]["start"]
+        );
+
+        let hover = |i: usize| {
+            answers[i]["result"]["contents"]["value"]
+                .as_str()
+                .unwrap()
+                .to_string()
+        };
+        assert_eq!("`(double x)`\n\ndoubles a number", hover(2));
+        assert!(
+            hover(3).starts_with("`(fib n)`\n\n`(\\ {n} {select"),
+            "{}",
+            hover(3)
+        );
+        assert_eq!(
+            "`(fun {name formals ...} {body})`\n\nDefines a named function in the global env",
+            hover(4)
+        );
+
+        let labels: Vec<&str> = answers[5]["result"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|item| item["label"].as_str().unwrap())
+            .collect();
+        assert_eq!(vec!["double", "do"], labels);
+
+        assert_eq!(
+            json!({"uri": "file:///a.rspr", "range": {
+                "start": {"line": 1, "character": 6},
+                "end": {"line": 1, "character": 12},
+            }}),
+            answers[6]["result"]
+        );
+        assert_eq!(
+            json!("; doubles a number\n(fun {double x} {* 2 x})\n(def {y} (double (fib z)))\n"),
+            answers[7]["result"][0]["newText"]
+        );
+
+        let diagnostics = &answers[8]["params"]["diagnostics"];
+        assert_eq!(json!("Missing a closing ')'"), diagnostics[0]["message"]);
+        assert_eq!(json!(1), diagnostics[0]["severity"]);
+        assert_eq!(json!(METHOD_NOT_FOUND), answers[9]["error"]["code"]);
+        assert_eq!(Value::Null, answers[10]["result"]);
+    }
+
+    #[test]
+    fn lsp_answers_messages_that_are_not_json() {
+        let mut input = b"Content-Length: 5\r\n\r\n{oops".to_vec();
+        input.extend(messages(&[
+            request(1, "shutdown", Value::Null),
+            json!({"jsonrpc": "2.0", "method": "exit"}),
+        ]));
+        let mut output = Vec::new();
+        let status = LspServer::new(&Lenv::new())
+            .run(io::Cursor::new(input), &mut output)
+            .unwrap();
+        assert_eq!(0, status);
+
+        let mut output = io::Cursor::new(output);
+        let error = read_message(&mut output).unwrap().unwrap().unwrap();
+        assert_eq!(json!(PARSE_ERROR), error["error"]["code"]);
+        assert_eq!(Value::Null, error["id"]);
+        let shutdown = read_message(&mut output).unwrap().unwrap().unwrap();
+        assert_eq!(json!(1), shutdown["id"]);
+    }
+}
@@ -1,7 +1,14 @@
+use crate::eval::eval_rispreter_file;
+use crate::lval::lval_debug::{Lreason, Lstop};
 use crate::lval::lval_def::*;
 use crate::lval::lval_env::Lenv;
 use crate::lval::lval_error::{Lerror, LvalTypeMeta};
 use crate::lval::lval_eval;
+use crate::lval::lval_key::Lkey;
+use crate::read::read;
+use rispreter_parser::complete_parser::parse_risp;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
 //use crate::lval::lval_lambda::LLambda;
 
@@ -28,7 +35,7 @@ impl Lbuiltin {
     }
 
     pub fn lbuiltin_mod() -> Lbuiltin {
-        Lbuiltin(modl, "mod".to_string())
+        Lbuiltin(modl, "%".to_string())
     }
 
     pub fn lbuiltin_head() -> Lbuiltin {
@@ -68,27 +75,27 @@ impl Lbuiltin {
     }
 
     pub fn lbuiltin_eq() -> Lbuiltin {
-        Lbuiltin(eq, "eq".to_string())
+        Lbuiltin(eq, "==".to_string())
     }
 
     pub fn lbuiltin_neq() -> Lbuiltin {
-        Lbuiltin(neq, "neq".to_string())
+        Lbuiltin(neq, "!=".to_string())
     }
 
     pub fn lbuiltin_gt() -> Lbuiltin {
-        Lbuiltin(gt, "gt".to_string())
+        Lbuiltin(gt, ">".to_string())
     }
 
     pub fn lbuiltin_lt() -> Lbuiltin {
-        Lbuiltin(lt, "lt".to_string())
+        Lbuiltin(lt, "<".to_string())
     }
 
     pub fn lbuiltin_gte() -> Lbuiltin {
-        Lbuiltin(gte, "gte".to_string())
+        Lbuiltin(gte, ">=".to_string())
     }
 
     pub fn lbuiltin_lte() -> Lbuiltin {
-        Lbuiltin(lte, "lte".to_string())
+        Lbuiltin(lte, "<=".to_string())
     }
 
     pub fn lbuiltin_if() -> Lbuiltin {
@@ -159,6 +166,10 @@ impl Lbuiltin {
         Lbuiltin(select, "select".to_string())
     }
 
+    pub fn lbuiltin_match() -> Lbuiltin {
+        Lbuiltin(lmatch, "match".to_string())
+    }
+
     pub fn lbuiltin_take() -> Lbuiltin {
         Lbuiltin(take, "take".to_string())
     }
@@ -183,6 +194,242 @@ impl Lbuiltin {
         Lbuiltin(filter, "filter".to_string())
     }
 
+    pub fn lbuiltin_assoc() -> Lbuiltin {
+        Lbuiltin(assoc, "assoc".to_string())
+    }
+
+    pub fn lbuiltin_dissoc() -> Lbuiltin {
+        Lbuiltin(dissoc, "dissoc".to_string())
+    }
+
+    pub fn lbuiltin_get_in() -> Lbuiltin {
+        Lbuiltin(get_in, "get-in".to_string())
+    }
+
+    pub fn lbuiltin_keys() -> Lbuiltin {
+        Lbuiltin(keys, "keys".to_string())
+    }
+
+    pub fn lbuiltin_vals() -> Lbuiltin {
+        Lbuiltin(vals, "vals".to_string())
+    }
+
+    pub fn lbuiltin_contains() -> Lbuiltin {
+        Lbuiltin(contains, "contains?".to_string())
+    }
+
+    pub fn lbuiltin_merge() -> Lbuiltin {
+        Lbuiltin(merge, "merge".to_string())
+    }
+
+    pub fn lbuiltin_union() -> Lbuiltin {
+        Lbuiltin(union, "union".to_string())
+    }
+
+    pub fn lbuiltin_intersection() -> Lbuiltin {
+        Lbuiltin(intersection, "intersection".to_string())
+    }
+
+    pub fn lbuiltin_difference() -> Lbuiltin {
+        Lbuiltin(difference, "difference".to_string())
+    }
+
+    pub fn lbuiltin_subset() -> Lbuiltin {
+        Lbuiltin(subset, "subset?".to_string())
+    }
+
+    pub fn lbuiltin_set_to_list() -> Lbuiltin {
+        Lbuiltin(set_to_list, "set->list".to_string())
+    }
+
+    pub fn lbuiltin_list_to_set() -> Lbuiltin {
+        Lbuiltin(list_to_set, "list->set".to_string())
+    }
+
+    pub fn lbuiltin_str_concat() -> Lbuiltin {
+        Lbuiltin(str_concat, "str-concat".to_string())
+    }
+
+    pub fn lbuiltin_str_len() -> Lbuiltin {
+        Lbuiltin(str_len, "str-len".to_string())
+    }
+
+    pub fn lbuiltin_substr() -> Lbuiltin {
+        Lbuiltin(substr, "substr".to_string())
+    }
+
+    pub fn lbuiltin_str_split() -> Lbuiltin {
+        Lbuiltin(str_split, "str-split".to_string())
+    }
+
+    pub fn lbuiltin_str_join() -> Lbuiltin {
+        Lbuiltin(str_join, "str-join".to_string())
+    }
+
+    pub fn lbuiltin_str_trim() -> Lbuiltin {
+        Lbuiltin(str_trim, "str-trim".to_string())
+    }
+
+    pub fn lbuiltin_str_upper() -> Lbuiltin {
+        Lbuiltin(str_upper, "str-upper".to_string())
+    }
+
+    pub fn lbuiltin_str_lower() -> Lbuiltin {
+        Lbuiltin(str_lower, "str-lower".to_string())
+    }
+
+    pub fn lbuiltin_str_index_of() -> Lbuiltin {
+        Lbuiltin(str_index_of, "str-index-of".to_string())
+    }
+
+    pub fn lbuiltin_str_replace() -> Lbuiltin {
+        Lbuiltin(str_replace, "str-replace".to_string())
+    }
+
+    pub fn lbuiltin_starts_with() -> Lbuiltin {
+        Lbuiltin(starts_with, "starts-with?".to_string())
+    }
+
+    pub fn lbuiltin_ends_with() -> Lbuiltin {
+        Lbuiltin(ends_with, "ends-with?".to_string())
+    }
+
+    pub fn lbuiltin_str_to_chars() -> Lbuiltin {
+        Lbuiltin(str_to_chars, "str->chars".to_string())
+    }
+
+    pub fn lbuiltin_chars_to_str() -> Lbuiltin {
+        Lbuiltin(chars_to_str, "chars->str".to_string())
+    }
+
+    pub fn lbuiltin_println() -> Lbuiltin {
+        Lbuiltin(println, "println".to_string())
+    }
+
+    pub fn lbuiltin_print() -> Lbuiltin {
+        Lbuiltin(print, "print".to_string())
+    }
+
+    pub fn lbuiltin_show() -> Lbuiltin {
+        Lbuiltin(show, "show".to_string())
+    }
+
+    pub fn lbuiltin_format() -> Lbuiltin {
+        Lbuiltin(format, "format".to_string())
+    }
+
+    pub fn lbuiltin_read_file() -> Lbuiltin {
+        Lbuiltin(read_file, "read-file".to_string())
+    }
+
+    pub fn lbuiltin_write_file() -> Lbuiltin {
+        Lbuiltin(write_file, "write-file".to_string())
+    }
+
+    pub fn lbuiltin_append_file() -> Lbuiltin {
+        Lbuiltin(append_file, "append-file".to_string())
+    }
+
+    pub fn lbuiltin_file_exists() -> Lbuiltin {
+        Lbuiltin(file_exists, "file-exists?".to_string())
+    }
+
+    pub fn lbuiltin_list_dir() -> Lbuiltin {
+        Lbuiltin(list_dir, "list-dir".to_string())
+    }
+
+    pub fn lbuiltin_make_dir() -> Lbuiltin {
+        Lbuiltin(make_dir, "make-dir".to_string())
+    }
+
+    pub fn lbuiltin_remove_file() -> Lbuiltin {
+        Lbuiltin(remove_file, "remove-file".to_string())
+    }
+
+    pub fn lbuiltin_read_lines() -> Lbuiltin {
+        Lbuiltin(read_lines, "read-lines".to_string())
+    }
+
+    pub fn lbuiltin_getenv() -> Lbuiltin {
+        Lbuiltin(getenv, "getenv".to_string())
+    }
+
+    pub fn lbuiltin_setenv() -> Lbuiltin {
+        Lbuiltin(setenv, "setenv".to_string())
+    }
+
+    pub fn lbuiltin_exit() -> Lbuiltin {
+        Lbuiltin(exit, "exit".to_string())
+    }
+
+    pub fn lbuiltin_run() -> Lbuiltin {
+        Lbuiltin(run, "run".to_string())
+    }
+
+    pub fn lbuiltin_load() -> Lbuiltin {
+        Lbuiltin(load, "load".to_string())
+    }
+
+    pub fn lbuiltin_import() -> Lbuiltin {
+        Lbuiltin(import, "import".to_string())
+    }
+
+    pub fn lbuiltin_out() -> Lbuiltin {
+        Lbuiltin(out, "out".to_string())
+    }
+
+    pub fn lbuiltin_break() -> Lbuiltin {
+        Lbuiltin(lbreak, "break".to_string())
+    }
+
+    pub fn lbuiltin_trace() -> Lbuiltin {
+        Lbuiltin(trace, "trace".to_string())
+    }
+
+    pub fn lbuiltin_untrace() -> Lbuiltin {
+        Lbuiltin(untrace, "untrace".to_string())
+    }
+
+    pub fn lbuiltin_profile() -> Lbuiltin {
+        Lbuiltin(profile, "profile".to_string())
+    }
+
+    pub fn lbuiltin_deftest() -> Lbuiltin {
+        Lbuiltin(deftest, "deftest".to_string())
+    }
+
+    pub fn lbuiltin_assert() -> Lbuiltin {
+        Lbuiltin(lassert, "assert".to_string())
+    }
+
+    pub fn lbuiltin_assert_eq() -> Lbuiltin {
+        Lbuiltin(lassert_eq, "assert-eq".to_string())
+    }
+
+    pub fn lbuiltin_assert_error() -> Lbuiltin {
+        Lbuiltin(lassert_error, "assert-error".to_string())
+    }
+
+    pub fn name(&self) -> &str {
+        &self.1
+    }
+
+    /// The builtin with a name, which is the keyword the reader reads it from,
+    /// or the name the REPL binds it to
+    pub fn from_name(name: &str) -> Option<Lbuiltin> {
+        match read(parse_risp(name)).ltype {
+            LvalType::LVAL_FUN(builtin) if builtin.name() == name => Some(builtin),
+            _ => Lbuiltin::repl_builtins()
+                .into_iter()
+                .find(|builtin| builtin.name() == name),
+        }
+    }
+
+    /// The builtins that aren't keywords, only the env of the REPL binds them, by
+    /// their names, so they don't take names away from programs
+    pub fn repl_builtins() -> Vec<Lbuiltin> {
+        vec![Lbuiltin::lbuiltin_out()]
+    }
 }
 
 impl PartialEq for Lbuiltin {
@@ -777,6 +1024,9 @@ fn var(env: Option<&Rc<Lenv>>, lval: &mut Lval, func: &str) -> Lval {
 
     for i in 0..syms.cell.len() {
         if let LvalType::LVAL_SYM(str) = &syms.cell[i].ltype {
+            if let LvalType::LVAL_LAMBDA(lambda) = &lval.cell[i + 1].ltype {
+                lambda.local_lenv.set_name(str);
+            }
             match func {
                 "def" => {
                     env.unwrap()
@@ -846,6 +1096,9 @@ fn fun(env: Option<&Rc<Lenv>>, lval: &mut Lval) -> Lval {
     let fun_name = &args.clone().cell[0].ltype;
     let lambda = Lval::lval_lambda(args.lval_split(1).1, body);
     if let LvalType::LVAL_SYM(ref str) = fun_name {
+        if let LvalType::LVAL_LAMBDA(ref llambda) = lambda.ltype {
+            llambda.local_lenv.set_name(str);
+        }
         env.unwrap().def(str.to_string(), lambda).unwrap();
         Lval::lval_sexpr()
     } else {
@@ -995,6 +1248,169 @@ fn select(env: Option<&Rc<Lenv>>, lval: &mut Lval) -> Lval {
     Lval::lval_err(Lerror::GenericError {msg : format!("Selection Not found")})
 }
 
+/// Matches a value against `{pattern body}` or `{pattern guard body}` clauses,
+/// evaluating the body of the first clause that fits in a fresh child environment
+/// holding the pattern bindings.
+///
+/// Patterns can be literals, symbols (which bind), the `_` wildcard, Q-expressions
+/// with an optional `& rest` tail, and type patterns like `(num? n)`. A symbol
+/// repeated in a pattern only matches equal values, and a guard has to give back
+/// a bool.
+/// # Examples
+/// ```
+/// # use rispreter_repl::lval::lval_env::Lenv;
+/// # use rispreter_repl::eval::eval_rispreter;
+/// let env = Lenv::new();
+///
+/// let res = eval_rispreter(&env, "(match {1 2 3} {{x & xs} x} {_ 0})");
+/// assert_eq!(1f64, res);
+///
+/// let res = eval_rispreter(&env, "(match 5 {(num? n) (> n 9) 1} {(num? n) (+ n 1)})");
+/// assert_eq!(6f64, res);
+///
+/// let res = eval_rispreter(&env, "(match {1 2} {{x x} \"same\"} {_ \"diff\"})");
+/// assert_eq!("\"diff\"", res.to_string());
+/// ```
+fn lmatch(env: Option<&Rc<Lenv>>, lval: &mut Lval) -> Lval {
+    if lval.cell.is_empty() {
+        return Lval::lval_err(Lerror::WrongNumberOfArgs {
+            lval: Box::new(lval.clone()),
+            expect: 1,
+            got: 0,
+        });
+    }
+
+    let value = lval.lval_pop();
+    let mut patterns = Lval::lval_qexpr();
+    for clause in lval.cell.iter_mut() {
+        if clause.ltype != LvalType::LVAL_QEXPR {
+            return Lval::lval_err(Lerror::WrongType {
+                lval: clause.clone(),
+                expect: LvalTypeMeta::LvalQexpr,
+                got: Box::new(clause.ltype.clone()),
+            });
+        }
+        if clause.cell.len() != 2 && clause.cell.len() != 3 {
+            return Lval::lval_err(Lerror::WrongNumberOfArgs {
+                lval: clause.clone(),
+                expect: 2,
+                got: clause.cell.len(),
+            });
+        }
+
+        let pattern = clause.lval_pop();
+        let local = Lenv::from(env.unwrap());
+        let matched = match_pattern(&pattern, &value, &local);
+        patterns.add_cell(pattern);
+        match matched {
+            Ok(true) => {}
+            Ok(false) => continue,
+            Err(err) => return err,
+        }
+
+        if clause.cell.len() == 2 {
+            let guard = lval_eval::lval_eval(&local, &mut clause.lval_pop());
+            match &guard.ltype {
+                LvalType::LVAL_BOOL(true) => {}
+                LvalType::LVAL_BOOL(false) => continue,
+                LvalType::LVAL_ERR(_) => return guard,
+                t => {
+                    return Lval::lval_err(Lerror::WrongType {
+                        lval: Box::new(guard.clone()),
+                        expect: LvalTypeMeta::LvalBool,
+                        got: Box::new(t.clone()),
+                    })
+                }
+            }
+        }
+        return lval_eval::lval_eval(&local, &mut clause.lval_pop());
+    }
+
+    Lval::lval_err(Lerror::NonExhaustiveMatch {
+        lval: Box::new(value),
+        patterns: Box::new(patterns),
+    })
+}
+
+/// Tests `value` against `pattern`, binding the pattern symbols in `env`.
+/// Returns an error `Lval` when the pattern itself is malformed.
+fn match_pattern(pattern: &Lval, value: &Lval, env: &Rc<Lenv>) -> Result<bool, Lval> {
+    match &pattern.ltype {
+        LvalType::LVAL_SYM(s) if s == "_" => Ok(true),
+        // bound already by the same pattern, so the values have to be the same
+        LvalType::LVAL_SYM(s) => match env.local(s) {
+            Some(bound) => Ok(bound == *value),
+            None => {
+                env.put(s.to_string(), value.clone()).unwrap();
+                Ok(true)
+            }
+        },
+        LvalType::LVAL_QEXPR => {
+            if value.ltype != LvalType::LVAL_QEXPR {
+                return Ok(false);
+            }
+            for (i, p) in pattern.cell.iter().enumerate() {
+                if p.ltype == LvalType::LVAL_SYM("&".to_string()) {
+                    if pattern.cell.len() != i + 2 {
+                        return Err(Lval::lval_err(Lerror::GenericError {
+                            msg: format!(
+                                "Pattern '{}' invalid. Symbol '&' not followed by single pattern",
+                                pattern
+                            ),
+                        }));
+                    }
+                    let mut rest = Lval::lval_qexpr();
+                    rest.cell = value.cell.iter().skip(i).cloned().collect();
+                    return match_pattern(&pattern.cell[i + 1], &rest, env);
+                }
+                match value.cell.get(i) {
+                    Some(v) => {
+                        if !match_pattern(p, v, env)? {
+                            return Ok(false);
+                        }
+                    }
+                    None => return Ok(false),
+                }
+            }
+            Ok(pattern.cell.len() == value.cell.len())
+        }
+        LvalType::LVAL_SEXPR if pattern.cell.len() == 2 => {
+            let is_type = match &pattern.cell[0].ltype {
+                LvalType::LVAL_SYM(pred) => type_pattern(pred, &value.ltype),
+                _ => None,
+            };
+            match is_type {
+                Some(true) => match_pattern(&pattern.cell[1], value, env),
+                Some(false) => Ok(false),
+                None => Err(Lval::lval_err(Lerror::GenericError {
+                    msg: format!("'{}' is not a valid type pattern", pattern),
+                })),
+            }
+        }
+        _ => Ok(pattern == value),
+    }
+}
+
+/// Checks a type predicate of a type pattern like `(num? n)` against a value type,
+/// `None` if the predicate is unknown.
+fn type_pattern(pred: &str, ltype: &LvalType) -> Option<bool> {
+    let meta = LvalTypeMeta::from(ltype.clone());
+    match pred {
+        "num?" => Some(meta == LvalTypeMeta::LvalNum),
+        "sym?" => Some(meta == LvalTypeMeta::LvalSym),
+        "str?" => Some(meta == LvalTypeMeta::LvalString),
+        "char?" => Some(meta == LvalTypeMeta::LvalChar),
+        "bool?" => Some(meta == LvalTypeMeta::LvalBool),
+        "vec?" => Some(meta == LvalTypeMeta::LvalIntVec),
+        "map?" => Some(meta == LvalTypeMeta::LvalMap),
+        "set?" => Some(meta == LvalTypeMeta::LvalSet),
+        "list?" => Some(meta == LvalTypeMeta::LvalQexpr),
+        "err?" => Some(meta == LvalTypeMeta::LvalErr),
+        "fun?" => Some(meta == LvalTypeMeta::LvalFun || meta == LvalTypeMeta::LvalLambda),
+        _ => None,
+    }
+}
+
 fn take(_env: Option<&Rc<Lenv>>, lval: &mut Lval) -> Lval {
     let n = lval.lval_pop();
     let mut b = lval.lval_pop();
@@ -1038,9 +1454,40 @@ fn elemen(_env: Option<&Rc<Lenv>>, lval: &mut Lval) -> Lval {
     }
 }
 
+/// The error of `map` and `filter` given a map: its entries aren't cells, so they
+/// would quietly give back an empty list
+fn not_a_map(body: Lval) -> Lval {
+    Lval::lval_err(Lerror::WrongType {
+        got: Box::new(body.ltype.clone()),
+        lval: Box::new(body),
+        expect: LvalTypeMeta::LvalQexprOrSet,
+    })
+}
+
 fn map(env: Option<&Rc<Lenv>>, lval: &mut Lval) -> Lval {
     let f = lval.lval_pop();
     let body = lval.lval_pop();
+    if let LvalType::LVAL_MAP(_) = body.ltype {
+        return not_a_map(body);
+    }
+    if let LvalType::LVAL_SET(set) = body.ltype {
+        let mut mapped = BTreeSet::new();
+        for i in set.into_iter() {
+            let mut s = Lval::lval_sexpr();
+            s.add_cell(f.clone());
+            s.add_cell(i.to_lval());
+            let res = lval_eval::lval_eval(env.unwrap(), &mut s);
+            // an error of the function is its own, not one of a bad element
+            if let LvalType::LVAL_ERR(_) = res.ltype {
+                return res;
+            }
+            match Lkey::from_lval(&res) {
+                Ok(key) => mapped.insert(key),
+                Err(err) => return err,
+            };
+        }
+        return Lval::lval_set(mapped);
+    }
     let mut q = Lval::lval_qexpr();
     for i in body.cell.into_iter() {
         let mut s = Lval::lval_sexpr();
@@ -1054,6 +1501,22 @@ fn map(env: Option<&Rc<Lenv>>, lval: &mut Lval) -> Lval {
 fn filter(env: Option<&Rc<Lenv>>, lval: &mut Lval) -> Lval {
     let f = lval.lval_pop();
     let body = lval.lval_pop();
+    if let LvalType::LVAL_MAP(_) = body.ltype {
+        return not_a_map(body);
+    }
+    if let LvalType::LVAL_SET(set) = body.ltype {
+        let mut filtered = BTreeSet::new();
+        for i in set.into_iter() {
+            let mut s = Lval::lval_sexpr();
+            s.add_cell(f.clone());
+            s.add_cell(i.to_lval());
+            let test = lval_eval::lval_eval(env.unwrap(), &mut s);
+            if let LvalType::LVAL_BOOL(true) = test.ltype {
+                filtered.insert(i);
+            }
+        }
+        return Lval::lval_set(filtered);
+    }
     let mut q = Lval::lval_qexpr();
     for i in body.cell.into_iter() {
         let mut s = Lval::lval_sexpr();
@@ -1067,77 +1530,1829 @@ fn filter(env: Option<&Rc<Lenv>>, lval: &mut Lval) -> Lval {
     q
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    /// tests the expression (+ 1 2 3)
-    fn lbuiltin_op_add() {
-        let mut top = Lval::lval_sexpr();
-        let plus_op = Lval::lval_sym("+".to_string());
-        let one = Lval::lval_num(1.0);
-        let two = Lval::lval_num(2.0);
-        let three = Lval::lval_num(3.0);
-        top.add_cell(plus_op)
-            .add_cell(one)
-            .add_cell(two)
-            .add_cell(three);
-
-        assert_eq!(top.cell.len(), 4);
-        top.lval_pop();
-        top = op(None, &mut top, '+');
-        println!("{:?}", top);
-        assert_eq!(top.cell.len(), 0);
-        assert_eq!(top.ltype, LvalType::LVAL_NUM(6.0));
+/// Pops the first argument, which must be a `LVAL_MAP`
+fn pop_map(lval: &mut Lval) -> Result<BTreeMap<Lkey, Lval>, Lval> {
+    if lval.cell.is_empty() {
+        return Err(Lval::lval_err(Lerror::WrongNumberOfArgs {
+            lval: Box::new(lval.clone()),
+            expect: 1,
+            got: 0,
+        }));
+    }
+    let m = lval.lval_pop();
+    match m.ltype {
+        LvalType::LVAL_MAP(map) => Ok(map),
+        _ => Err(Lval::lval_err(Lerror::WrongType {
+            got: Box::new(m.ltype.clone()),
+            lval: Box::new(m),
+            expect: LvalTypeMeta::LvalMap,
+        })),
     }
+}
 
-    #[test]
-    /// tests the expression (- 4 2 1)
-    fn lbuiltin_op_sub() {
-        let mut top = Lval::lval_sexpr();
-        let sub_op = Lval::lval_sym("-".to_string());
-        let one = Lval::lval_num(4.0);
-        let two = Lval::lval_num(2.0);
-        let three = Lval::lval_num(1.0);
-        top.add_cell(sub_op)
-            .add_cell(one)
-            .add_cell(two)
-            .add_cell(three);
-
-        assert_eq!(top.cell.len(), 4);
-        top.lval_pop();
-        top = op(None, &mut top, '-');
-        println!("{:?}", top);
-        assert_eq!(top.cell.len(), 0);
-        assert_eq!(top.ltype, LvalType::LVAL_NUM(1.0));
+/// Returns a map with the following key value pairs added, replacing existing keys
+/// # Examples
+/// ```
+/// # use rispreter_repl::lval::lval_env::Lenv;
+/// # use rispreter_repl::eval::eval_rispreter;
+/// let env = Lenv::new();
+///
+/// let res = eval_rispreter(&env, "(assoc #{\"a\" 1} \"b\" 2 \"a\" 3)");
+/// assert_eq!("#{\"a\" 3 \"b\" 2}", res.to_string());
+/// ```
+fn assoc(_env: Option<&Rc<Lenv>>, lval: &mut Lval) -> Lval {
+    let mut map = match pop_map(lval) {
+        Ok(map) => map,
+        Err(err) => return err,
+    };
+    if !lval.cell.len().is_multiple_of(2) {
+        return Lval::lval_err(Lerror::WrongNumberOfArgs {
+            lval: Box::new(lval.clone()),
+            expect: lval.cell.len() + 1,
+            got: lval.cell.len(),
+        });
     }
-
-    #[test]
-    /// tests the expression (* 1 2 4)
-    fn lbuiltin_op_mul() {
-        let mut top = Lval::lval_sexpr();
-        let mult_op = Lval::lval_sym("*".to_string());
-        let one = Lval::lval_num(1.0);
-        let two = Lval::lval_num(2.0);
-        let three = Lval::lval_num(4.0);
-        top.add_cell(mult_op)
-            .add_cell(one)
-            .add_cell(two)
-            .add_cell(three);
-
-        assert_eq!(top.cell.len(), 4);
-        top.lval_pop();
-        top = op(None, &mut top, '*');
-        println!("{:?}", top);
-        assert_eq!(top.cell.len(), 0);
-        assert_eq!(top.ltype, LvalType::LVAL_NUM(8.0));
+    while !lval.cell.is_empty() {
+        let k = match Lkey::from_lval(&lval.lval_pop()) {
+            Ok(k) => k,
+            Err(err) => return err,
+        };
+        let v = lval.lval_pop();
+        map.insert(k, v);
     }
+    Lval::lval_map(map)
+}
 
-    #[test]
-    /// tests the expression (/ 9 5 2)
-    fn lbuiltin_op_div() {
-        let mut top = Lval::lval_sexpr();
+/// Returns a map without the following keys
+/// # Examples
+/// ```
+/// # use rispreter_repl::lval::lval_env::Lenv;
+/// # use rispreter_repl::eval::eval_rispreter;
+/// let env = Lenv::new();
+///
+/// let res = eval_rispreter(&env, "(dissoc #{\"a\" 1 \"b\" 2} \"a\")");
+/// assert_eq!("#{\"b\" 2}", res.to_string());
+/// ```
+fn dissoc(_env: Option<&Rc<Lenv>>, lval: &mut Lval) -> Lval {
+    let mut map = match pop_map(lval) {
+        Ok(map) => map,
+        Err(err) => return err,
+    };
+    // what can't be a key isn't in the map
+    for k in lval.cell.iter().filter_map(|k| Lkey::from_lval(k).ok()) {
+        map.remove(&k);
+    }
+    Lval::lval_map(map)
+}
+
+/// Looks up a path of keys given as a Q-expression in nested maps.
+/// Returns the optional third argument, or `nil` when the path isn't found.
+/// # Examples
+/// ```
+/// # use rispreter_repl::lval::lval_env::Lenv;
+/// # use rispreter_repl::eval::eval_rispreter;
+/// let env = Lenv::new();
+///
+/// let res = eval_rispreter(&env, "(get-in #{db #{port 5432}} {db port})");
+/// assert_eq!(5432f64, res);
+///
+/// let res = eval_rispreter(&env, "(get-in #{db #{port 5432}} {db host} \"localhost\")");
+/// assert_eq!("\"localhost\"", res.to_string());
+/// ```
+fn get_in(_env: Option<&Rc<Lenv>>, lval: &mut Lval) -> Lval {
+    if lval.cell.len() != 2 && lval.cell.len() != 3 {
+        return Lval::lval_err(Lerror::WrongNumberOfArgs {
+            lval: Box::new(lval.clone()),
+            expect: 2,
+            got: lval.cell.len(),
+        });
+    }
+    if lval.cell[1].ltype != LvalType::LVAL_QEXPR {
+        return Lval::lval_err(Lerror::WrongType {
+            lval: lval.cell[1].clone(),
+            expect: LvalTypeMeta::LvalQexpr,
+            got: Box::new(lval.cell[1].ltype.clone()),
+        });
+    }
+    let mut current = lval.lval_pop();
+    let path = lval.lval_pop();
+    let default = if lval.cell.is_empty() {
+        Lval::lval_qexpr()
+    } else {
+        lval.lval_pop()
+    };
+    for k in path.cell.iter() {
+        let next = match &current.ltype {
+            LvalType::LVAL_MAP(map) => Lkey::from_lval(k).ok().and_then(|k| map.get(&k).cloned()),
+            _ => None,
+        };
+        match next {
+            Some(v) => current = v,
+            None => return default,
+        }
+    }
+    current
+}
+
+/// Returns a Q-expression with the keys of a map, in order
+/// # Examples
+/// ```
+/// # use rispreter_repl::lval::lval_env::Lenv;
+/// # use rispreter_repl::eval::eval_rispreter;
+/// let env = Lenv::new();
+///
+/// let res = eval_rispreter(&env, "(keys #{b 2 a 1})");
+/// assert_eq!("{a b}", res.to_string());
+/// ```
+fn keys(_env: Option<&Rc<Lenv>>, lval: &mut Lval) -> Lval {
+    match pop_map(lval) {
+        Ok(map) => {
+            let mut q = Lval::lval_qexpr();
+            for k in map.into_keys() {
+                q.add_cell(k.to_lval());
+            }
+            q
+        }
+        Err(err) => err,
+    }
+}
+
+/// Returns a Q-expression with the values of a map, in the order of their keys
+/// # Examples
+/// ```
+/// # use rispreter_repl::lval::lval_env::Lenv;
+/// # use rispreter_repl::eval::eval_rispreter;
+/// let env = Lenv::new();
+///
+/// let res = eval_rispreter(&env, "(vals #{b 2 a 1})");
+/// assert_eq!("{1 2}", res.to_string());
+/// ```
+fn vals(_env: Option<&Rc<Lenv>>, lval: &mut Lval) -> Lval {
+    match pop_map(lval) {
+        Ok(map) => {
+            let mut q = Lval::lval_qexpr();
+            for v in map.into_values() {
+                q.add_cell(v);
+            }
+            q
+        }
+        Err(err) => err,
+    }
+}
+
+/// Checks if a map has the given key, or a set the given element
+/// # Examples
+/// ```
+/// # use rispreter_repl::lval::lval_env::Lenv;
+/// # use rispreter_repl::eval::eval_rispreter;
+/// let env = Lenv::new();
+///
+/// let res = eval_rispreter(&env, "(contains? #{1 'a'} 1)");
+/// assert_eq!(true, res);
+///
+/// let res = eval_rispreter(&env, "(contains? #s{1 2} 3)");
+/// assert_eq!(false, res);
+///
+/// let res = eval_rispreter(&env, "(contains? 5 1)");
+/// assert!(res.to_string().contains("expect: 'LvalMapOrSet'"));
+/// ```
+fn contains(_env: Option<&Rc<Lenv>>, lval: &mut Lval) -> Lval {
+    if lval.cell.len() != 2 {
+        return Lval::lval_err(Lerror::WrongNumberOfArgs {
+            lval: Box::new(lval.clone()),
+            expect: 2,
+            got: lval.cell.len(),
+        });
+    }
+    // what can't be a key is in no map or set
+    let key = Lkey::from_lval(&lval.cell[1]).ok();
+    match &lval.cell[0].ltype {
+        LvalType::LVAL_MAP(map) => Lval::lval_bool(key.is_some_and(|k| map.contains_key(&k))),
+        LvalType::LVAL_SET(set) => Lval::lval_bool(key.is_some_and(|k| set.contains(&k))),
+        t => Lval::lval_err(Lerror::WrongType {
+            lval: lval.cell[0].clone(),
+            expect: LvalTypeMeta::LvalMapOrSet,
+            got: Box::new(t.clone()),
+        }),
+    }
+}
+
+/// Merges n maps, keys of the rightmost maps replace the ones at the left
+/// # Examples
+/// ```
+/// # use rispreter_repl::lval::lval_env::Lenv;
+/// # use rispreter_repl::eval::eval_rispreter;
+/// let env = Lenv::new();
+///
+/// let res = eval_rispreter(&env, "(merge #{a 1 b 2} #{b 3} #{c 4})");
+/// assert_eq!("#{a 1 b 3 c 4}", res.to_string());
+///
+/// let res = eval_rispreter(&env, "(merge #{a 1} 5)");
+/// assert!(res.to_string().contains("Wrong type at '5'"));
+/// ```
+fn merge(_env: Option<&Rc<Lenv>>, lval: &mut Lval) -> Lval {
+    let mut map = match pop_map(lval) {
+        Ok(map) => map,
+        Err(err) => return err,
+    };
+    while !lval.cell.is_empty() {
+        match pop_map(lval) {
+            Ok(other) => map.extend(other),
+            Err(err) => return err,
+        }
+    }
+    Lval::lval_map(map)
+}
+
+/// Pops every argument, which must all be `LVAL_SET`s
+fn pop_sets(lval: &mut Lval) -> Result<Vec<BTreeSet<Lkey>>, Lval> {
+    let mut sets = Vec::new();
+    while !lval.cell.is_empty() {
+        let s = lval.lval_pop();
+        match s.ltype {
+            LvalType::LVAL_SET(set) => sets.push(set),
+            _ => {
+                return Err(Lval::lval_err(Lerror::WrongType {
+                    got: Box::new(s.ltype.clone()),
+                    lval: Box::new(s),
+                    expect: LvalTypeMeta::LvalSet,
+                }))
+            }
+        }
+    }
+    Ok(sets)
+}
+
+/// Union of n sets
+/// # Examples
+/// ```
+/// # use rispreter_repl::lval::lval_env::Lenv;
+/// # use rispreter_repl::eval::eval_rispreter;
+/// # use rispreter_repl::lval::lval_def::{Lval, LvalType};
+/// # use rispreter_repl::lval::lval_error::Lerror;
+/// let env = Lenv::new();
+///
+/// let res = eval_rispreter(&env, "(union #s{a b} #s{b c})");
+/// assert_eq!("#s{a b c}", res.to_string());
+///
+/// let res = eval_rispreter(&env, "(union #s{1} 5)");
+/// if let LvalType::LVAL_ERR(Lerror::WrongType { lval, .. }) = res.ltype {
+///     assert_eq!(Lval::lval_num(5.0), *lval);
+/// } else {
+///     panic!("expected a wrong type error, got {}", res);
+/// }
+/// ```
+fn union(_env: Option<&Rc<Lenv>>, lval: &mut Lval) -> Lval {
+    match pop_sets(lval) {
+        Ok(sets) => Lval::lval_set(sets.into_iter().flatten().collect()),
+        Err(err) => err,
+    }
+}
+
+/// Intersection of n sets
+/// # Examples
+/// ```
+/// # use rispreter_repl::lval::lval_env::Lenv;
+/// # use rispreter_repl::eval::eval_rispreter;
+/// let env = Lenv::new();
+///
+/// let res = eval_rispreter(&env, "(intersection #s{a b c} #s{b c d} #s{c b})");
+/// assert_eq!("#s{b c}", res.to_string());
+/// ```
+fn intersection(_env: Option<&Rc<Lenv>>, lval: &mut Lval) -> Lval {
+    let mut sets = match pop_sets(lval) {
+        Ok(sets) => sets.into_iter(),
+        Err(err) => return err,
+    };
+    let mut result = sets.next().unwrap_or_default();
+    for set in sets {
+        result.retain(|v| set.contains(v));
+    }
+    Lval::lval_set(result)
+}
+
+/// Elements of the first set that aren't in any of the following sets
+/// # Examples
+/// ```
+/// # use rispreter_repl::lval::lval_env::Lenv;
+/// # use rispreter_repl::eval::eval_rispreter;
+/// let env = Lenv::new();
+///
+/// let res = eval_rispreter(&env, "(difference #s{1 2 3 4} #s{1} #s{3})");
+/// assert_eq!("#s{2 4}", res.to_string());
+/// ```
+fn difference(_env: Option<&Rc<Lenv>>, lval: &mut Lval) -> Lval {
+    let mut sets = match pop_sets(lval) {
+        Ok(sets) => sets.into_iter(),
+        Err(err) => return err,
+    };
+    let mut result = sets.next().unwrap_or_default();
+    for set in sets {
+        result.retain(|v| !set.contains(v));
+    }
+    Lval::lval_set(result)
+}
+
+/// Checks if every element of the first set is in the second one
+/// # Examples
+/// ```
+/// # use rispreter_repl::lval::lval_env::Lenv;
+/// # use rispreter_repl::eval::eval_rispreter;
+/// let env = Lenv::new();
+///
+/// let res = eval_rispreter(&env, "(subset? #s{1 2} #s{1 2 3})");
+/// assert_eq!(true, res);
+/// ```
+fn subset(_env: Option<&Rc<Lenv>>, lval: &mut Lval) -> Lval {
+    if lval.cell.len() != 2 {
+        return Lval::lval_err(Lerror::WrongNumberOfArgs {
+            lval: Box::new(lval.clone()),
+            expect: 2,
+            got: lval.cell.len(),
+        });
+    }
+    match pop_sets(lval) {
+        Ok(sets) => Lval::lval_bool(sets[0].is_subset(&sets[1])),
+        Err(err) => err,
+    }
+}
+
+/// Transforms a set in a Q-expression with its elements in order
+/// # Examples
+/// ```
+/// # use rispreter_repl::lval::lval_env::Lenv;
+/// # use rispreter_repl::eval::eval_rispreter;
+/// let env = Lenv::new();
+///
+/// let res = eval_rispreter(&env, "(set->list #s{3 1 2})");
+/// assert_eq!("{1 2 3}", res.to_string());
+/// ```
+fn set_to_list(_env: Option<&Rc<Lenv>>, lval: &mut Lval) -> Lval {
+    if lval.cell.len() != 1 {
+        return Lval::lval_err(Lerror::WrongNumberOfArgs {
+            lval: Box::new(lval.clone()),
+            expect: 1,
+            got: lval.cell.len(),
+        });
+    }
+    match pop_sets(lval) {
+        Ok(mut sets) => {
+            let mut q = Lval::lval_qexpr();
+            for v in sets.remove(0) {
+                q.add_cell(v.to_lval());
+            }
+            q
+        }
+        Err(err) => err,
+    }
+}
+
+/// Transforms a Q-expression in a set, dropping the repeated elements
+/// # Examples
+/// ```
+/// # use rispreter_repl::lval::lval_env::Lenv;
+/// # use rispreter_repl::eval::eval_rispreter;
+/// let env = Lenv::new();
+///
+/// let res = eval_rispreter(&env, "(list->set {b a b})");
+/// assert_eq!("#s{a b}", res.to_string());
+/// ```
+fn list_to_set(_env: Option<&Rc<Lenv>>, lval: &mut Lval) -> Lval {
+    if lval.cell.len() != 1 {
+        return Lval::lval_err(Lerror::WrongNumberOfArgs {
+            lval: Box::new(lval.clone()),
+            expect: 1,
+            got: lval.cell.len(),
+        });
+    }
+    let qexpr = lval.lval_pop();
+    if qexpr.ltype != LvalType::LVAL_QEXPR {
+        return Lval::lval_err(Lerror::WrongType {
+            lval: Box::new(qexpr.clone()),
+            expect: LvalTypeMeta::LvalQexpr,
+            got: Box::new(qexpr.ltype),
+        });
+    }
+    match qexpr.cell.iter().map(|v| Lkey::from_lval(v)).collect() {
+        Ok(set) => Lval::lval_set(set),
+        Err(err) => err,
+    }
+}
+
+/// Checks the number of arguments and gets them as strings, chars are taken as
+/// single character strings
+fn string_args(lval: &Lval, expect: usize) -> Result<Vec<String>, Lval> {
+    if lval.cell.len() != expect {
+        return Err(Lval::lval_err(Lerror::WrongNumberOfArgs {
+            lval: Box::new(lval.clone()),
+            expect,
+            got: lval.cell.len(),
+        }));
+    }
+    lval.cell.iter().map(|v| string_arg(v)).collect()
+}
+
+fn string_arg(v: &Lval) -> Result<String, Lval> {
+    match &v.ltype {
+        LvalType::LVAL_STRING(s) => Ok(s.to_string()),
+        LvalType::LVAL_CHAR(c) => Ok(c.to_string()),
+        t => Err(Lval::lval_err(Lerror::WrongType {
+            lval: Box::new(v.clone()),
+            expect: LvalTypeMeta::LvalString,
+            got: Box::new(t.clone()),
+        })),
+    }
+}
+
+/// Concatenates n strings
+/// # Examples
+/// ```
+/// # use rispreter_repl::lval::lval_env::Lenv;
+/// # use rispreter_repl::eval::eval_rispreter;
+/// let env = Lenv::new();
+///
+/// let res = eval_rispreter(&env, "(str-concat \"risp\" 'r' \"eter\")");
+/// assert_eq!("\"rispreter\"", res.to_string());
+/// ```
+fn str_concat(_env: Option<&Rc<Lenv>>, lval: &mut Lval) -> Lval {
+    match string_args(lval, lval.cell.len()) {
+        Ok(strs) => Lval::lval_string(strs.concat()),
+        Err(err) => err,
+    }
+}
+
+/// Length of a string, in characters
+/// # Examples
+/// ```
+/// # use rispreter_repl::lval::lval_env::Lenv;
+/// # use rispreter_repl::eval::eval_rispreter;
+/// let env = Lenv::new();
+///
+/// let res = eval_rispreter(&env, "(str-len \"açaí\")");
+/// assert_eq!(4f64, res);
+/// ```
+fn str_len(_env: Option<&Rc<Lenv>>, lval: &mut Lval) -> Lval {
+    match string_args(lval, 1) {
+        Ok(strs) => Lval::lval_num(strs[0].chars().count() as f64),
+        Err(err) => err,
+    }
+}
+
+/// Substring from a start character index up to an optional end index (exclusive).
+/// Indexes out of the string are clamped to it.
+/// # Examples
+/// ```
+/// # use rispreter_repl::lval::lval_env::Lenv;
+/// # use rispreter_repl::eval::eval_rispreter;
+/// let env = Lenv::new();
+///
+/// let res = eval_rispreter(&env, "(substr \"rispreter\" 4)");
+/// assert_eq!("\"reter\"", res.to_string());
+///
+/// let res = eval_rispreter(&env, "(substr \"rispreter\" 1 4)");
+/// assert_eq!("\"isp\"", res.to_string());
+/// ```
+fn substr(_env: Option<&Rc<Lenv>>, lval: &mut Lval) -> Lval {
+    if lval.cell.len() != 2 && lval.cell.len() != 3 {
+        return Lval::lval_err(Lerror::WrongNumberOfArgs {
+            lval: Box::new(lval.clone()),
+            expect: 3,
+            got: lval.cell.len(),
+        });
+    }
+    let s = match string_arg(&lval.cell[0]) {
+        Ok(s) => s,
+        Err(err) => return err,
+    };
+    let mut bounds = Vec::new();
+    for i in 1..lval.cell.len() {
+        match lval.cell[i].ltype {
+            LvalType::LVAL_NUM(n) => bounds.push(n.max(0.0) as usize),
+            ref t => {
+                return Lval::lval_err(Lerror::WrongType {
+                    lval: lval.cell[i].clone(),
+                    expect: LvalTypeMeta::LvalNum,
+                    got: Box::new(t.clone()),
+                })
+            }
+        }
+    }
+    let start = bounds[0];
+    let end = bounds.get(1).cloned().unwrap_or(usize::MAX);
+    Lval::lval_string(
+        s.chars()
+            .skip(start)
+            .take(end.saturating_sub(start))
+            .collect(),
+    )
+}
+
+/// Splits a string by a separator, or by whitespace if no separator is given.
+/// An empty separator splits it in its characters
+/// # Examples
+/// ```
+/// # use rispreter_repl::lval::lval_env::Lenv;
+/// # use rispreter_repl::eval::eval_rispreter;
+/// let env = Lenv::new();
+///
+/// let res = eval_rispreter(&env, "(str-split \"a,b,,c\" \",\")");
+/// assert_eq!("{\"a\" \"b\" \"\" \"c\"}", res.to_string());
+///
+/// let res = eval_rispreter(&env, "(str-split \" a  b \")");
+/// assert_eq!("{\"a\" \"b\"}", res.to_string());
+///
+/// let res = eval_rispreter(&env, "(str-split \"a b\" \"\")");
+/// assert_eq!("{\"a\" \" \" \"b\"}", res.to_string());
+/// ```
+fn str_split(_env: Option<&Rc<Lenv>>, lval: &mut Lval) -> Lval {
+    let strs = match string_args(lval, lval.cell.len().clamp(1, 2)) {
+        Ok(strs) => strs,
+        Err(err) => return err,
+    };
+    let mut q = Lval::lval_qexpr();
+    match strs.get(1) {
+        Some(sep) if sep.is_empty() => {
+            for c in strs[0].chars() {
+                q.add_cell(Lval::lval_string(c.to_string()));
+            }
+        }
+        Some(sep) => {
+            for part in strs[0].split(sep.as_str()) {
+                q.add_cell(Lval::lval_string(part.to_string()));
+            }
+        }
+        None => {
+            for part in strs[0].split_whitespace() {
+                q.add_cell(Lval::lval_string(part.to_string()));
+            }
+        }
+    }
+    q
+}
+
+/// Joins a Q-expression of strings with a separator between them
+/// # Examples
+/// ```
+/// # use rispreter_repl::lval::lval_env::Lenv;
+/// # use rispreter_repl::eval::eval_rispreter;
+/// let env = Lenv::new();
+///
+/// let res = eval_rispreter(&env, "(str-join \", \" {\"a\" \"b\" 'c'})");
+/// assert_eq!("\"a, b, c\"", res.to_string());
+/// ```
+fn str_join(_env: Option<&Rc<Lenv>>, lval: &mut Lval) -> Lval {
+    if lval.cell.len() != 2 {
+        return Lval::lval_err(Lerror::WrongNumberOfArgs {
+            lval: Box::new(lval.clone()),
+            expect: 2,
+            got: lval.cell.len(),
+        });
+    }
+    let sep = match string_arg(&lval.cell[0]) {
+        Ok(s) => s,
+        Err(err) => return err,
+    };
+    if lval.cell[1].ltype != LvalType::LVAL_QEXPR {
+        return Lval::lval_err(Lerror::WrongType {
+            lval: lval.cell[1].clone(),
+            expect: LvalTypeMeta::LvalQexpr,
+            got: Box::new(lval.cell[1].ltype.clone()),
+        });
+    }
+    match string_args(&lval.cell[1], lval.cell[1].cell.len()) {
+        Ok(strs) => Lval::lval_string(strs.join(&sep)),
+        Err(err) => err,
+    }
+}
+
+/// Removes the leading and trailing whitespace of a string
+/// # Examples
+/// ```
+/// # use rispreter_repl::lval::lval_env::Lenv;
+/// # use rispreter_repl::eval::eval_rispreter;
+/// let env = Lenv::new();
+///
+/// let res = eval_rispreter(&env, "(str-trim \"  risp \")");
+/// assert_eq!("\"risp\"", res.to_string());
+/// ```
+fn str_trim(_env: Option<&Rc<Lenv>>, lval: &mut Lval) -> Lval {
+    match string_args(lval, 1) {
+        Ok(strs) => Lval::lval_string(strs[0].trim().to_string()),
+        Err(err) => err,
+    }
+}
+
+/// Uppercase version of a string
+/// # Examples
+/// ```
+/// # use rispreter_repl::lval::lval_env::Lenv;
+/// # use rispreter_repl::eval::eval_rispreter;
+/// let env = Lenv::new();
+///
+/// let res = eval_rispreter(&env, "(str-upper \"straße\")");
+/// assert_eq!("\"STRASSE\"", res.to_string());
+/// ```
+fn str_upper(_env: Option<&Rc<Lenv>>, lval: &mut Lval) -> Lval {
+    match string_args(lval, 1) {
+        Ok(strs) => Lval::lval_string(strs[0].to_uppercase()),
+        Err(err) => err,
+    }
+}
+
+/// Lowercase version of a string
+/// # Examples
+/// ```
+/// # use rispreter_repl::lval::lval_env::Lenv;
+/// # use rispreter_repl::eval::eval_rispreter;
+/// let env = Lenv::new();
+///
+/// let res = eval_rispreter(&env, "(str-lower \"ÁRVORE\")");
+/// assert_eq!("\"árvore\"", res.to_string());
+/// ```
+fn str_lower(_env: Option<&Rc<Lenv>>, lval: &mut Lval) -> Lval {
+    match string_args(lval, 1) {
+        Ok(strs) => Lval::lval_string(strs[0].to_lowercase()),
+        Err(err) => err,
+    }
+}
+
+/// Character index of the first occurrence of a substring, `-1` if not found
+/// # Examples
+/// ```
+/// # use rispreter_repl::lval::lval_env::Lenv;
+/// # use rispreter_repl::eval::eval_rispreter;
+/// let env = Lenv::new();
+///
+/// let res = eval_rispreter(&env, "(str-index-of \"ñandú\" 'd')");
+/// assert_eq!(3f64, res);
+///
+/// let res = eval_rispreter(&env, "(str-index-of \"risp\" \"x\")");
+/// assert_eq!(-1f64, res);
+/// ```
+fn str_index_of(_env: Option<&Rc<Lenv>>, lval: &mut Lval) -> Lval {
+    match string_args(lval, 2) {
+        Ok(strs) => match strs[0].find(strs[1].as_str()) {
+            Some(byte_index) => Lval::lval_num(strs[0][..byte_index].chars().count() as f64),
+            None => Lval::lval_num(-1.0),
+        },
+        Err(err) => err,
+    }
+}
+
+/// Replaces every occurrence of a substring
+/// # Examples
+/// ```
+/// # use rispreter_repl::lval::lval_env::Lenv;
+/// # use rispreter_repl::eval::eval_rispreter;
+/// let env = Lenv::new();
+///
+/// let res = eval_rispreter(&env, "(str-replace \"a-b-c\" \"-\" \" + \")");
+/// assert_eq!("\"a + b + c\"", res.to_string());
+/// ```
+fn str_replace(_env: Option<&Rc<Lenv>>, lval: &mut Lval) -> Lval {
+    match string_args(lval, 3) {
+        Ok(strs) => Lval::lval_string(strs[0].replace(strs[1].as_str(), &strs[2])),
+        Err(err) => err,
+    }
+}
+
+/// Checks if a string starts with a prefix
+/// # Examples
+/// ```
+/// # use rispreter_repl::lval::lval_env::Lenv;
+/// # use rispreter_repl::eval::eval_rispreter;
+/// let env = Lenv::new();
+///
+/// let res = eval_rispreter(&env, "(starts-with? \"rispreter\" \"risp\")");
+/// assert_eq!(true, res);
+/// ```
+fn starts_with(_env: Option<&Rc<Lenv>>, lval: &mut Lval) -> Lval {
+    match string_args(lval, 2) {
+        Ok(strs) => Lval::lval_bool(strs[0].starts_with(strs[1].as_str())),
+        Err(err) => err,
+    }
+}
+
+/// Checks if a string ends with a suffix
+/// # Examples
+/// ```
+/// # use rispreter_repl::lval::lval_env::Lenv;
+/// # use rispreter_repl::eval::eval_rispreter;
+/// let env = Lenv::new();
+///
+/// let res = eval_rispreter(&env, "(ends-with? \"rispreter\" \"risp\")");
+/// assert_eq!(false, res);
+/// ```
+fn ends_with(_env: Option<&Rc<Lenv>>, lval: &mut Lval) -> Lval {
+    match string_args(lval, 2) {
+        Ok(strs) => Lval::lval_bool(strs[0].ends_with(strs[1].as_str())),
+        Err(err) => err,
+    }
+}
+
+/// Transforms a string in a Q-expression of its characters
+/// # Examples
+/// ```
+/// # use rispreter_repl::lval::lval_env::Lenv;
+/// # use rispreter_repl::eval::eval_rispreter;
+/// let env = Lenv::new();
+///
+/// let res = eval_rispreter(&env, "(str->chars \"pé\")");
+/// assert_eq!("{'p' 'é'}", res.to_string());
+/// ```
+fn str_to_chars(_env: Option<&Rc<Lenv>>, lval: &mut Lval) -> Lval {
+    match string_args(lval, 1) {
+        Ok(strs) => {
+            let mut q = Lval::lval_qexpr();
+            for c in strs[0].chars() {
+                q.add_cell(Lval::lval_char(c));
+            }
+            q
+        }
+        Err(err) => err,
+    }
+}
+
+/// Transforms a Q-expression of characters in a string
+/// # Examples
+/// ```
+/// # use rispreter_repl::lval::lval_env::Lenv;
+/// # use rispreter_repl::eval::eval_rispreter;
+/// let env = Lenv::new();
+///
+/// let res = eval_rispreter(&env, "(chars->str {'p' 'é'})");
+/// assert_eq!("\"pé\"", res.to_string());
+/// ```
+fn chars_to_str(_env: Option<&Rc<Lenv>>, lval: &mut Lval) -> Lval {
+    if lval.cell.len() != 1 {
+        return Lval::lval_err(Lerror::WrongNumberOfArgs {
+            lval: Box::new(lval.clone()),
+            expect: 1,
+            got: lval.cell.len(),
+        });
+    }
+    let qexpr = lval.lval_pop();
+    if qexpr.ltype != LvalType::LVAL_QEXPR {
+        return Lval::lval_err(Lerror::WrongType {
+            lval: Box::new(qexpr.clone()),
+            expect: LvalTypeMeta::LvalQexpr,
+            got: Box::new(qexpr.ltype),
+        });
+    }
+    let mut s = String::new();
+    for c in qexpr.cell.iter() {
+        match c.ltype {
+            LvalType::LVAL_CHAR(c) => s.push(c),
+            ref t => {
+                return Lval::lval_err(Lerror::WrongType {
+                    lval: c.clone(),
+                    expect: LvalTypeMeta::LvalChar,
+                    got: Box::new(t.clone()),
+                })
+            }
+        }
+    }
+    Lval::lval_string(s)
+}
+
+/// Writes the arguments separated by spaces to the interpreter output
+/// # Examples
+/// ```
+/// # use rispreter_repl::lval::lval_env::Lenv;
+/// # use rispreter_repl::lval::lval_context::CapturedOutput;
+/// # use rispreter_repl::eval::eval_rispreter;
+/// let env = Lenv::new();
+/// let output = CapturedOutput::new();
+/// env.context().set_output(Box::new(output.clone()));
+///
+/// eval_rispreter(&env, "(print \"x =\" 1)");
+/// assert_eq!("x = 1", output.contents());
+/// ```
+fn print(env: Option<&Rc<Lenv>>, lval: &mut Lval) -> Lval {
+    write_output(env, lval, "")
+}
+
+/// Writes the arguments separated by spaces and a new line to the interpreter output
+/// # Examples
+/// ```
+/// # use rispreter_repl::lval::lval_env::Lenv;
+/// # use rispreter_repl::lval::lval_context::CapturedOutput;
+/// # use rispreter_repl::eval::eval_rispreter;
+/// let env = Lenv::new();
+/// let output = CapturedOutput::new();
+/// env.context().set_output(Box::new(output.clone()));
+///
+/// eval_rispreter(&env, "(println 'a' \"b\" {\"c\"})");
+/// assert_eq!("a b {\"c\"}\n", output.contents());
+/// ```
+fn println(env: Option<&Rc<Lenv>>, lval: &mut Lval) -> Lval {
+    write_output(env, lval, "\n")
+}
+
+fn write_output(env: Option<&Rc<Lenv>>, lval: &Lval, end: &str) -> Lval {
+    let text: Vec<String> = lval.cell.iter().map(|v| v.to_print_string()).collect();
+    match env.unwrap().context().write(&(text.join(" ") + end)) {
+        Ok(()) => Lval::lval_sexpr(),
+        Err(err) => Lval::lval_err(Lerror::GenericError {
+            msg: format!("Can't write output: {}", err),
+        }),
+    }
+}
+
+/// The re-readable form of a value, as a string
+/// # Examples
+/// ```
+/// # use rispreter_repl::lval::lval_env::Lenv;
+/// # use rispreter_repl::eval::eval_rispreter;
+/// # use rispreter_repl::lval::lval_def::Lval;
+/// let env = Lenv::new();
+///
+/// let res = eval_rispreter(&env, "(show {1 \"a\"})");
+/// assert_eq!(Lval::lval_string("{1 \"a\"}".to_string()), res);
+/// ```
+fn show(_env: Option<&Rc<Lenv>>, lval: &mut Lval) -> Lval {
+    if lval.cell.len() != 1 {
+        return Lval::lval_err(Lerror::WrongNumberOfArgs {
+            lval: Box::new(lval.clone()),
+            expect: 1,
+            got: lval.cell.len(),
+        });
+    }
+    Lval::lval_string(lval.cell[0].to_string())
+}
+
+/// Formats the arguments into a string, replacing each `{}` placeholder with the next argument.
+/// Placeholders take an optional `:[[fill]align][0][width][.precision]` spec, where align is
+/// one of `<`, `>` or `^`, as in Rust. `{{` and `}}` are literal braces.
+/// # Examples
+/// ```
+/// # use rispreter_repl::lval::lval_env::Lenv;
+/// # use rispreter_repl::eval::eval_rispreter;
+/// let env = Lenv::new();
+///
+/// let res = eval_rispreter(&env, "(format \"{} = {:.2} {{ok}}\" \"pi\" 3.14159)");
+/// assert_eq!("\"pi = 3.14 {ok}\"", res.to_string());
+///
+/// let res = eval_rispreter(&env, "(format \"[{:>5}|{:-<4}|{:^5}|{:04}]\" 42 'a' \"mid\" -7)");
+/// assert_eq!("\"[   42|a---| mid |-007]\"", res.to_string());
+/// ```
+fn format(_env: Option<&Rc<Lenv>>, lval: &mut Lval) -> Lval {
+    if lval.cell.is_empty() {
+        return Lval::lval_err(Lerror::WrongNumberOfArgs {
+            lval: Box::new(lval.clone()),
+            expect: 1,
+            got: 0,
+        });
+    }
+    let fmt = match &lval.cell[0].ltype {
+        LvalType::LVAL_STRING(fmt) => fmt.to_string(),
+        t => {
+            return Lval::lval_err(Lerror::WrongType {
+                lval: lval.cell[0].clone(),
+                expect: LvalTypeMeta::LvalString,
+                got: Box::new(t.clone()),
+            })
+        }
+    };
+    let invalid = |msg: String| {
+        Lval::lval_err(Lerror::InvalidFormat {
+            fmt: fmt.clone(),
+            msg,
+        })
+    };
+
+    let mut args = lval.cell.iter().skip(1);
+    let mut out = String::new();
+    let mut chars = fmt.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                out.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                out.push('}');
+            }
+            '{' => {
+                let mut spec = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => spec.push(c),
+                        None => return invalid("unclosed '{'".to_string()),
+                    }
+                }
+                let arg = match args.next() {
+                    Some(arg) => arg,
+                    None => return invalid("more placeholders than arguments".to_string()),
+                };
+                match format_arg(arg, &spec) {
+                    Ok(s) => out.push_str(&s),
+                    Err(msg) => return invalid(msg),
+                }
+            }
+            '}' => return invalid("unmatched '}'".to_string()),
+            c => out.push(c),
+        }
+    }
+    if args.next().is_some() {
+        return invalid("more arguments than placeholders".to_string());
+    }
+    Lval::lval_string(out)
+}
+
+/// Formats a single `format` argument following a placeholder spec
+fn format_arg(arg: &Lval, spec: &str) -> Result<String, String> {
+    let spec: Vec<char> = match spec.strip_prefix(':') {
+        Some(spec) => spec.chars().collect(),
+        None if spec.is_empty() => vec![],
+        None => return Err(format!("invalid placeholder '{{{}}}'", spec)),
+    };
+    let is_align = |c: Option<&char>| c.is_some_and(|c| "<>^".contains(*c));
+    let (fill, align, mut i) = if is_align(spec.get(1)) {
+        (spec[0], Some(spec[1]), 2)
+    } else if is_align(spec.first()) {
+        (' ', Some(spec[0]), 1)
+    } else {
+        (' ', None, 0)
+    };
+    let zero = align.is_none() && spec.get(i) == Some(&'0');
+    if zero {
+        i += 1;
+    }
+    let mut width = String::new();
+    while i < spec.len() && spec[i].is_ascii_digit() {
+        width.push(spec[i]);
+        i += 1;
+    }
+    let mut precision = None;
+    if spec.get(i) == Some(&'.') {
+        let mut digits = String::new();
+        i += 1;
+        while i < spec.len() && spec[i].is_ascii_digit() {
+            digits.push(spec[i]);
+            i += 1;
+        }
+        precision = Some(
+            digits
+                .parse::<usize>()
+                .map_err(|_| "missing precision".to_string())?,
+        );
+    }
+    if i != spec.len() {
+        return Err(format!(
+            "invalid spec ':{}'",
+            spec.iter().collect::<String>()
+        ));
+    }
+    let width = width.parse::<usize>().unwrap_or(0);
+
+    let (is_num, body) = match (&arg.ltype, precision) {
+        (LvalType::LVAL_NUM(n), Some(p)) => (true, format!("{:.*}", p, n)),
+        (LvalType::LVAL_NUM(_), None) => (true, arg.to_print_string()),
+        (_, Some(p)) => (false, arg.to_print_string().chars().take(p).collect()),
+        (_, None) => (false, arg.to_print_string()),
+    };
+    let len = body.chars().count();
+    if len >= width {
+        return Ok(body);
+    }
+    let pad = width - len;
+    if zero && is_num {
+        return Ok(match body.strip_prefix('-') {
+            Some(digits) => format!("-{}{}", "0".repeat(pad), digits),
+            None => format!("{}{}", "0".repeat(pad), body),
+        });
+    }
+    let fill = |n: usize| fill.to_string().repeat(n);
+    Ok(match align.unwrap_or(if is_num { '>' } else { '<' }) {
+        '>' => format!("{}{}", fill(pad), body),
+        '^' => format!("{}{}{}", fill(pad / 2), body, fill(pad - pad / 2)),
+        _ => format!("{}{}", body, fill(pad)),
+    })
+}
+
+/// Checks the session may use the file system, then gets the arguments as strings
+fn fs_args(
+    env: Option<&Rc<Lenv>>,
+    lval: &Lval,
+    op: &str,
+    expect: usize,
+) -> Result<Vec<String>, Lval> {
+    if !env.unwrap().context().capabilities().file_system {
+        return Err(Lval::lval_err(Lerror::NotPermitted { op: op.to_string() }));
+    }
+    string_args(lval, expect)
+}
+
+fn io_err(path: &str, err: std::io::Error) -> Lval {
+    Lval::lval_err(Lerror::IoError {
+        path: path.to_string(),
+        msg: err.to_string(),
+    })
+}
+
+/// Reads a whole file into a string
+/// # Examples
+/// ```
+/// # use rispreter_repl::lval::lval_env::Lenv;
+/// # use rispreter_repl::eval::eval_rispreter;
+/// # use rispreter_repl::lval::lval_def::Lval;
+/// let env = Lenv::new();
+///
+/// let res = eval_rispreter(&env, "(read-file \"/no/such/file.rspr\")");
+/// assert!(res.to_string().contains("I/O error at '/no/such/file.rspr'"));
+/// ```
+fn read_file(env: Option<&Rc<Lenv>>, lval: &mut Lval) -> Lval {
+    let args = match fs_args(env, lval, "read-file", 1) {
+        Ok(args) => args,
+        Err(err) => return err,
+    };
+    match std::fs::read_to_string(&args[0]) {
+        Ok(content) => Lval::lval_string(content),
+        Err(err) => io_err(&args[0], err),
+    }
+}
+
+/// Reads a file as a Q-expression of its lines, without the line endings
+fn read_lines(env: Option<&Rc<Lenv>>, lval: &mut Lval) -> Lval {
+    let args = match fs_args(env, lval, "read-lines", 1) {
+        Ok(args) => args,
+        Err(err) => return err,
+    };
+    match std::fs::read_to_string(&args[0]) {
+        Ok(content) => {
+            let mut q = Lval::lval_qexpr();
+            for line in content.lines() {
+                q.add_cell(Lval::lval_string(line.to_string()));
+            }
+            q
+        }
+        Err(err) => io_err(&args[0], err),
+    }
+}
+
+/// Writes a string to a file, creating it or replacing what was there
+fn write_file(env: Option<&Rc<Lenv>>, lval: &mut Lval) -> Lval {
+    let args = match fs_args(env, lval, "write-file", 2) {
+        Ok(args) => args,
+        Err(err) => return err,
+    };
+    match std::fs::write(&args[0], &args[1]) {
+        Ok(()) => Lval::lval_sexpr(),
+        Err(err) => io_err(&args[0], err),
+    }
+}
+
+/// Writes a string at the end of a file, creating it if needed
+fn append_file(env: Option<&Rc<Lenv>>, lval: &mut Lval) -> Lval {
+    use std::io::Write;
+
+    let args = match fs_args(env, lval, "append-file", 2) {
+        Ok(args) => args,
+        Err(err) => return err,
+    };
+    let res = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&args[0])
+        .and_then(|mut file| file.write_all(args[1].as_bytes()));
+    match res {
+        Ok(()) => Lval::lval_sexpr(),
+        Err(err) => io_err(&args[0], err),
+    }
+}
+
+/// Whether a file or directory exists at a path
+/// # Examples
+/// ```
+/// # use rispreter_repl::lval::lval_env::Lenv;
+/// # use rispreter_repl::eval::eval_rispreter;
+/// # use rispreter_repl::lval::lval_def::Lval;
+/// let env = Lenv::new();
+///
+/// let res = eval_rispreter(&env, "(file-exists? \"/no/such/file.rspr\")");
+/// assert_eq!(Lval::lval_bool(false), res);
+/// ```
+fn file_exists(env: Option<&Rc<Lenv>>, lval: &mut Lval) -> Lval {
+    match fs_args(env, lval, "file-exists?", 1) {
+        Ok(args) => Lval::lval_bool(std::path::Path::new(&args[0]).exists()),
+        Err(err) => err,
+    }
+}
+
+/// The names of the entries of a directory, sorted, as a Q-expression of strings
+fn list_dir(env: Option<&Rc<Lenv>>, lval: &mut Lval) -> Lval {
+    let args = match fs_args(env, lval, "list-dir", 1) {
+        Ok(args) => args,
+        Err(err) => return err,
+    };
+    let entries = std::fs::read_dir(&args[0]).and_then(|dir| {
+        dir.map(|entry| entry.map(|e| e.file_name().to_string_lossy().to_string()))
+            .collect::<std::io::Result<Vec<String>>>()
+    });
+    match entries {
+        Ok(mut names) => {
+            names.sort();
+            let mut q = Lval::lval_qexpr();
+            for name in names {
+                q.add_cell(Lval::lval_string(name));
+            }
+            q
+        }
+        Err(err) => io_err(&args[0], err),
+    }
+}
+
+/// Creates a directory, along with any missing parent
+fn make_dir(env: Option<&Rc<Lenv>>, lval: &mut Lval) -> Lval {
+    let args = match fs_args(env, lval, "make-dir", 1) {
+        Ok(args) => args,
+        Err(err) => return err,
+    };
+    match std::fs::create_dir_all(&args[0]) {
+        Ok(()) => Lval::lval_sexpr(),
+        Err(err) => io_err(&args[0], err),
+    }
+}
+
+/// Removes a file
+fn remove_file(env: Option<&Rc<Lenv>>, lval: &mut Lval) -> Lval {
+    let args = match fs_args(env, lval, "remove-file", 1) {
+        Ok(args) => args,
+        Err(err) => return err,
+    };
+    match std::fs::remove_file(&args[0]) {
+        Ok(()) => Lval::lval_sexpr(),
+        Err(err) => io_err(&args[0], err),
+    }
+}
+
+/// Checks the session may touch the process environment, then gets the arguments
+/// as strings
+fn proc_args(
+    env: Option<&Rc<Lenv>>,
+    lval: &Lval,
+    op: &str,
+    expect: usize,
+) -> Result<Vec<String>, Lval> {
+    if !env.unwrap().context().capabilities().process {
+        return Err(Lval::lval_err(Lerror::NotPermitted { op: op.to_string() }));
+    }
+    string_args(lval, expect)
+}
+
+/// The value of an environment variable, or nil if it isn't set
+/// # Examples
+/// ```
+/// # use rispreter_repl::lval::lval_env::Lenv;
+/// # use rispreter_repl::eval::eval_rispreter;
+/// # use rispreter_repl::lval::lval_def::Lval;
+/// let env = Lenv::new();
+///
+/// eval_rispreter(&env, "(setenv \"RISP_GREETING\" \"hello\")");
+/// let res = eval_rispreter(&env, "(getenv \"RISP_GREETING\")");
+/// assert_eq!(Lval::lval_string("hello".to_string()), res);
+/// ```
+fn getenv(env: Option<&Rc<Lenv>>, lval: &mut Lval) -> Lval {
+    match proc_args(env, lval, "getenv", 1) {
+        Ok(args) => match std::env::var(&args[0]) {
+            Ok(value) => Lval::lval_string(value),
+            Err(_) => Lval::lval_qexpr(),
+        },
+        Err(err) => err,
+    }
+}
+
+/// Sets an environment variable, seen by the session and the processes it runs
+fn setenv(env: Option<&Rc<Lenv>>, lval: &mut Lval) -> Lval {
+    match proc_args(env, lval, "setenv", 2) {
+        Ok(args) => {
+            std::env::set_var(&args[0], &args[1]);
+            Lval::lval_sexpr()
+        }
+        Err(err) => err,
+    }
+}
+
+/// Stops the program with a status code. The exit unwinds like an error, so the one
+/// running the session decides what to do with it
+/// # Examples
+/// ```
+/// # use rispreter_repl::lval::lval_env::Lenv;
+/// # use rispreter_repl::eval::eval_rispreter;
+/// # use rispreter_repl::lval::lval_def::Lval;
+/// # use rispreter_repl::lval::lval_error::Lerror;
+/// let env = Lenv::new();
+///
+/// let res = eval_rispreter(&env, "(+ 1 (exit 3))");
+/// assert_eq!(Lval::lval_err(Lerror::Exit { code: 3 }), res);
+/// ```
+fn exit(_env: Option<&Rc<Lenv>>, lval: &mut Lval) -> Lval {
+    if lval.cell.len() != 1 {
+        return Lval::lval_err(Lerror::WrongNumberOfArgs {
+            lval: Box::new(lval.clone()),
+            expect: 1,
+            got: lval.cell.len(),
+        });
+    }
+    let code = match &lval.cell[0].ltype {
+        LvalType::LVAL_NUM(n) => *n as i32,
+        t => {
+            return Lval::lval_err(Lerror::WrongType {
+                lval: Box::new(lval.clone()),
+                expect: LvalTypeMeta::LvalNum,
+                got: Box::new(t.clone()),
+            })
+        }
+    };
+    Lval::lval_err(Lerror::Exit { code })
+}
+
+/// Runs a program with arguments and waits for it, giving back a map with its
+/// "status", "stdout" and "stderr". The status is -1 if it was killed by a signal
+fn run(env: Option<&Rc<Lenv>>, lval: &mut Lval) -> Lval {
+    let args = match proc_args(env, lval, "run", lval.cell.len().max(1)) {
+        Ok(args) => args,
+        Err(err) => return err,
+    };
+    let output = match std::process::Command::new(&args[0])
+        .args(&args[1..])
+        .output()
+    {
+        Ok(output) => output,
+        Err(err) => return io_err(&args[0], err),
+    };
+    let text = |bytes: &[u8]| Lval::lval_string(String::from_utf8_lossy(bytes).to_string());
+    let mut map = BTreeMap::new();
+    map.insert(
+        Lkey::Str("status".to_string()),
+        Lval::lval_num(f64::from(output.status.code().unwrap_or(-1))),
+    );
+    map.insert(Lkey::Str("stdout".to_string()), text(&output.stdout));
+    map.insert(Lkey::Str("stderr".to_string()), text(&output.stderr));
+    Lval::lval_map(map)
+}
+
+/// The directory `load` and `import` start from: the one of the file being
+/// evaluated, or the working directory outside of any file
+fn module_dir(env: &Rc<Lenv>) -> PathBuf {
+    env.context()
+        .current_file()
+        .and_then(|file| file.parent().map(Path::to_path_buf))
+        .unwrap_or_default()
+}
+
+fn module_file(name: &str) -> PathBuf {
+    let file = PathBuf::from(name);
+    if file.extension().is_none() {
+        file.with_extension("rspr")
+    } else {
+        file
+    }
+}
+
+/// Evaluates another .rspr file, relative to the current one, right in this env.
+/// Gives back the value of its last form
+fn load(env: Option<&Rc<Lenv>>, lval: &mut Lval) -> Lval {
+    let args = match fs_args(env, lval, "load", 1) {
+        Ok(args) => args,
+        Err(err) => return err,
+    };
+    let env = env.unwrap();
+    eval_rispreter_file(env, &module_dir(env).join(module_file(&args[0])))
+}
+
+/// Evaluates a module once per session in its own env, and binds what it defines
+/// under the name of the module, as in `(import "math" {sqrt})` binding `math/sqrt`.
+/// Without a Q-expression of names everything it defines is bound. Gives back the
+/// bound symbols.
+///
+/// Modules are looked up from the directory of the current file, then in the
+/// search path, unless the name starts with `./`, `../` or `/`. They see the
+/// prelude of the session, but nothing else the importer defined
+fn import(env: Option<&Rc<Lenv>>, lval: &mut Lval) -> Lval {
+    if lval.cell.len() != 1 && lval.cell.len() != 2 {
+        return Lval::lval_err(Lerror::WrongNumberOfArgs {
+            lval: Box::new(lval.clone()),
+            expect: 2,
+            got: lval.cell.len(),
+        });
+    }
+    let names = if lval.cell.len() == 2 {
+        let names = lval.lval_pop_last();
+        let mut syms = Vec::new();
+        for name in &names.cell {
+            match &name.ltype {
+                LvalType::LVAL_SYM(sym) => syms.push(sym.to_string()),
+                t => {
+                    return Lval::lval_err(Lerror::WrongType {
+                        lval: Box::new(names.clone()),
+                        expect: LvalTypeMeta::LvalSym,
+                        got: Box::new(t.clone()),
+                    })
+                }
+            }
+        }
+        Some(syms)
+    } else {
+        None
+    };
+    let args = match fs_args(env, lval, "import", 1) {
+        Ok(args) => args,
+        Err(err) => return err,
+    };
+    let env = env.unwrap();
+    let context = env.context();
+
+    let file = module_file(&args[0]);
+    let prefix = file
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let mut dirs = vec![module_dir(env)];
+    if !(file.is_absolute() || file.starts_with(".") || file.starts_with("..")) {
+        dirs.extend(context.search_path());
+    }
+    let path = match dirs.iter().map(|dir| dir.join(&file)).find(|p| p.is_file()) {
+        Some(path) => path.canonicalize().unwrap_or(path),
+        None => {
+            return Lval::lval_err(Lerror::ModuleNotFound {
+                name: args[0].to_string(),
+                searched: dirs
+                    .iter()
+                    .map(|dir| dir.join(&file).display().to_string())
+                    .collect(),
+            })
+        }
+    };
+
+    let bindings = match context.module(&path) {
+        Some(bindings) => bindings,
+        None => {
+            let prelude = context.prelude();
+            let module_env = Lenv::module(&context);
+            for (name, val) in &prelude {
+                module_env.put(name.to_string(), val.clone()).unwrap();
+            }
+            let res = eval_rispreter_file(&module_env, &path);
+            if let LvalType::LVAL_ERR(_) = res.ltype {
+                return res;
+            }
+            // what the module got from the prelude isn't part of what it defines
+            let bindings: Vec<(String, Lval)> = module_env
+                .bindings()
+                .into_iter()
+                .filter(|binding| !prelude.contains(binding))
+                .map(|(name, mut val)| {
+                    if let LvalType::LVAL_LAMBDA(lambda) = &mut val.ltype {
+                        lambda.home = Some(Rc::clone(&module_env));
+                    }
+                    (name, val)
+                })
+                .collect();
+            context.add_module(path, bindings.clone());
+            bindings
+        }
+    };
+
+    let names =
+        names.unwrap_or_else(|| bindings.iter().map(|(name, _)| name.to_string()).collect());
+    let mut imported = Lval::lval_qexpr();
+    for name in names {
+        let sym = format!("{}/{}", prefix, name);
+        match bindings.iter().find(|(n, _)| *n == name) {
+            Some((_, val)) => {
+                env.def(sym.to_string(), val.clone()).unwrap();
+                imported.add_cell(Lval::lval_sym(sym));
+            }
+            None => return Lval::lval_err(Lerror::SymbolNotBinded { sym }),
+        }
+    }
+    imported
+}
+
+/// Gives back the result of a numbered input of the REPL, the first one is 1.
+/// It isn't a keyword, the REPL binds it as `out`
+/// # Examples
+/// ```
+/// # use rispreter_repl::lval::lval_env::Lenv;
+/// # use rispreter_repl::eval::eval_rispreter;
+/// # use rispreter_repl::lval::lval_def::Lval;
+/// # use rispreter_repl::lval::lval_builtin::Lbuiltin;
+/// let env = Lenv::new();
+/// env.def("out".to_string(), Lval::lval_fun(Lbuiltin::lbuiltin_out())).unwrap();
+/// env.context().add_result(Lval::lval_num(42f64));
+///
+/// let res = eval_rispreter(&env, "(+ 1 (out 1))");
+/// assert_eq!(43f64, res);
+/// ```
+fn out(env: Option<&Rc<Lenv>>, lval: &mut Lval) -> Lval {
+    if lval.cell.len() != 1 {
+        return Lval::lval_err(Lerror::WrongNumberOfArgs {
+            lval: Box::new(lval.clone()),
+            expect: 1,
+            got: lval.cell.len(),
+        });
+    }
+    let n = match &lval.cell[0].ltype {
+        LvalType::LVAL_NUM(n) => *n,
+        t => {
+            return Lval::lval_err(Lerror::WrongType {
+                lval: Box::new(lval.clone()),
+                expect: LvalTypeMeta::LvalNum,
+                got: Box::new(t.clone()),
+            })
+        }
+    };
+    match env.unwrap().context().result(n as usize) {
+        Some(res) => res,
+        _ => Lval::lval_err(Lerror::GenericError {
+            msg: format!("There's no result number {}", n),
+        }),
+    }
+}
+
+/// Stops in the debugger, when there's one to stop in, and goes on with `()`.
+/// The stop shows the values it's called with
+/// # Examples
+/// ```
+/// # use rispreter_repl::lval::lval_env::Lenv;
+/// # use rispreter_repl::eval::eval_rispreter;
+/// # use rispreter_repl::lval::lval_debug::Lresume;
+/// let env = Lenv::new();
+/// env.context().debugger().set_handler(Box::new(|stop| {
+///     assert_eq!("(break 3)", stop.expr.to_string());
+///     Lresume::Continue
+/// }));
+///
+/// let res = eval_rispreter(&env, "(do (break (+ 1 2)) 4)");
+/// assert_eq!(4f64, res);
+/// ```
+fn lbreak(env: Option<&Rc<Lenv>>, lval: &mut Lval) -> Lval {
+    let env = env.unwrap();
+    let context = env.context();
+    let mut expr = Lval::lval_sexpr();
+    expr.add_cell(Lval::lval_sym("break".to_string()));
+    while !lval.cell.is_empty() {
+        expr.add_cell(lval.lval_pop());
+    }
+    let stop = Lstop {
+        reason: Lreason::Break,
+        expr,
+        env: Rc::clone(env),
+        calls: context.calls(),
+    };
+    if context.debugger().stop(&stop) {
+        Lval::lval_sexpr()
+    } else {
+        Lval::lval_err(Lerror::Aborted)
+    }
+}
+
+/// Traces the calls of functions: every call is printed with its arguments, and
+/// then with what it gave back, whatever the function is called by. A lambda
+/// goes by the first name it was bound to, the names are given back
+/// # Examples
+/// ```
+/// # use rispreter_repl::lval::lval_env::Lenv;
+/// # use rispreter_repl::eval::eval_rispreter;
+/// # use rispreter_repl::lval::lval_context::CapturedOutput;
+/// let env = Lenv::new();
+/// let output = CapturedOutput::new();
+/// env.context().tracer().set_output(Box::new(output.clone()));
+/// eval_rispreter(&env, "(fun {double x} {* 2 x})");
+///
+/// eval_rispreter(&env, "(trace double)");
+/// eval_rispreter(&env, "(+ 1 (double 2))");
+/// assert_eq!("0: (double 2)\n0: double returned 4\n", output.contents());
+/// ```
+fn trace(env: Option<&Rc<Lenv>>, lval: &mut Lval) -> Lval {
+    let env = env.unwrap();
+    let names = match traced_names(lval) {
+        Ok(names) => names,
+        Err(err) => return err,
+    };
+    let mut traced = Lval::lval_qexpr();
+    for (f, name) in lval.cell.iter().zip(names) {
+        env.context().tracer().trace(f);
+        traced.add_cell(Lval::lval_sym(name));
+    }
+    traced
+}
+
+/// Stops tracing the calls of functions, giving back the names that were traced
+/// # Examples
+/// ```
+/// # use rispreter_repl::lval::lval_env::Lenv;
+/// # use rispreter_repl::eval::eval_rispreter;
+/// let env = Lenv::new();
+/// eval_rispreter(&env, "(trace head tail)");
+///
+/// let res = eval_rispreter(&env, "(untrace head)");
+/// assert_eq!("{head}", res.to_string());
+/// ```
+fn untrace(env: Option<&Rc<Lenv>>, lval: &mut Lval) -> Lval {
+    let env = env.unwrap();
+    let names = match traced_names(lval) {
+        Ok(names) => names,
+        Err(err) => return err,
+    };
+    let mut untraced = Lval::lval_qexpr();
+    for (f, name) in lval.cell.iter().zip(names) {
+        if env.context().tracer().untrace(f) {
+            untraced.add_cell(Lval::lval_sym(name));
+        }
+    }
+    untraced
+}
+
+/// The names of the functions `trace` and `untrace` are given: the one of a
+/// builtin, or the first one a lambda was bound to
+fn traced_names(lval: &Lval) -> Result<Vec<String>, Lval> {
+    let mut names: Vec<String> = Vec::new();
+    for f in lval.cell.iter() {
+        match &f.ltype {
+            LvalType::LVAL_FUN(builtin) => names.push(builtin.name().to_string()),
+            LvalType::LVAL_LAMBDA(lambda) => match lambda.name() {
+                Some(name) => names.push(name),
+                None => {
+                    return Err(Lval::lval_err(Lerror::GenericError {
+                        msg: format!("Can't trace '{}', it isn't bound to a name", f),
+                    }))
+                }
+            },
+            t => {
+                return Err(Lval::lval_err(Lerror::WrongType {
+                    lval: f.clone(),
+                    expect: LvalTypeMeta::LvalLambda,
+                    got: Box::new(t.clone()),
+                }))
+            }
+        }
+    }
+    Ok(names)
+}
+
+/// Evaluates a Q-expression as `eval` does, and then prints a table with the
+/// calls of every function it called, their time and their allocations
+/// # Examples
+/// ```
+/// # use rispreter_repl::lval::lval_env::Lenv;
+/// # use rispreter_repl::eval::eval_rispreter;
+/// # use rispreter_repl::lval::lval_context::CapturedOutput;
+/// let env = Lenv::new();
+/// let output = CapturedOutput::new();
+/// env.context().set_output(Box::new(output.clone()));
+///
+/// let res = eval_rispreter(&env, "(profile {+ 1 (* 2 3)})");
+/// assert_eq!(7f64, res);
+/// assert!(output.contents().starts_with("function"));
+/// ```
+fn profile(env: Option<&Rc<Lenv>>, lval: &mut Lval) -> Lval {
+    if lval.cell.len() != 1 {
+        return Lval::lval_err(Lerror::WrongNumberOfArgs {
+            lval: Box::new(lval.clone()),
+            expect: 1,
+            got: lval.cell.len(),
+        });
+    }
+    let context = env.unwrap().context();
+    // the profile it's evaluated in already sees its calls
+    if !context.profiler().start() {
+        return eval(env, lval);
+    }
+    let res = eval(env, lval);
+    let profile = context.profiler().stop();
+    match context.write(&format!("{}\n", profile)) {
+        Ok(()) => res,
+        Err(err) => Lval::lval_err(Lerror::GenericError {
+            msg: format!("Can't write output: {}", err),
+        }),
+    }
+}
+
+/// Defines a test, with a name and a Q-expression for its body. `rispreter test`
+/// runs every test in a session of its own, and the test fails when its body
+/// gives back an error, like the one of a failing `assert`
+/// # Examples
+/// ```
+/// # use rispreter_repl::lval::lval_env::Lenv;
+/// # use rispreter_repl::eval::eval_rispreter;
+/// let env = Lenv::new();
+/// eval_rispreter(&env, "(deftest \"adds\" {assert-eq 3 (+ 1 2)})");
+///
+/// let tests = env.context().take_tests();
+/// assert_eq!("adds", tests[0].0);
+/// assert_eq!("{assert-eq 3 (+ 1 2)}", tests[0].1.to_string());
+/// ```
+fn deftest(env: Option<&Rc<Lenv>>, lval: &mut Lval) -> Lval {
+    if lval.cell.len() != 2 {
+        return Lval::lval_err(Lerror::WrongNumberOfArgs {
+            lval: Box::new(lval.clone()),
+            expect: 2,
+            got: lval.cell.len(),
+        });
+    }
+    let name = match string_arg(&lval.cell[0]) {
+        Ok(name) => name,
+        Err(err) => return err,
+    };
+    if lval.cell[1].ltype != LvalType::LVAL_QEXPR {
+        return Lval::lval_err(Lerror::WrongType {
+            lval: lval.cell[1].clone(),
+            expect: LvalTypeMeta::LvalQexpr,
+            got: Box::new(lval.cell[1].ltype.clone()),
+        });
+    }
+    env.unwrap().context().add_test(name, lval.lval_pop_last());
+    Lval::lval_sexpr()
+}
+
+/// Fails unless its argument is true, with an optional message
+/// # Examples
+/// ```
+/// # use rispreter_repl::lval::lval_env::Lenv;
+/// # use rispreter_repl::eval::eval_rispreter;
+/// # use rispreter_repl::lval::lval_def::{Lval, LvalType};
+/// # use rispreter_repl::lval::lval_error::Lerror;
+/// let env = Lenv::new();
+///
+/// assert_eq!("()", eval_rispreter(&env, "(assert (> 2 1))").to_string());
+/// let res = eval_rispreter(&env, "(assert (> 1 2) \"1 is bigger\")");
+/// let msg = "1 is bigger".to_string();
+/// assert_eq!(Lval::lval_err(Lerror::AssertionFailed { msg }), res);
+/// let res = eval_rispreter(&env, "(assert true \"a\" \"b\")");
+/// assert!(matches!(res.ltype, LvalType::LVAL_ERR(Lerror::WrongNumberOfArgs { expect: 2, got: 3, .. })));
+/// ```
+fn lassert(_env: Option<&Rc<Lenv>>, lval: &mut Lval) -> Lval {
+    if lval.cell.len() != 1 && lval.cell.len() != 2 {
+        return Lval::lval_err(Lerror::WrongNumberOfArgs {
+            lval: Box::new(lval.clone()),
+            // the message is optional
+            expect: if lval.cell.is_empty() { 1 } else { 2 },
+            got: lval.cell.len(),
+        });
+    }
+    let msg = match lval.cell.get(1) {
+        Some(msg) => match string_arg(msg) {
+            Ok(msg) => msg,
+            Err(err) => return err,
+        },
+        None => "Expected true, got false".to_string(),
+    };
+    match &lval.cell[0].ltype {
+        LvalType::LVAL_BOOL(true) => Lval::lval_sexpr(),
+        LvalType::LVAL_BOOL(false) => Lval::lval_err(Lerror::AssertionFailed { msg }),
+        t => Lval::lval_err(Lerror::FirstArgumentDoesNotEvalTo {
+            expect: LvalTypeMeta::LvalBool,
+            got: Box::new(t.clone()),
+        }),
+    }
+}
+
+/// Fails unless the value expected and the actual one are equal, showing where
+/// they differ when printed
+/// # Examples
+/// ```
+/// # use rispreter_repl::lval::lval_env::Lenv;
+/// # use rispreter_repl::eval::eval_rispreter;
+/// # use rispreter_repl::lval::lval_def::LvalType;
+/// let env = Lenv::new();
+///
+/// assert_eq!("()", eval_rispreter(&env, "(assert-eq {1 2} (list 1 2))").to_string());
+/// let res = eval_rispreter(&env, "(assert-eq {1 2 3} (list 1 2 4))");
+/// if let LvalType::LVAL_ERR(err) = res.ltype {
+///     let diff = "expected: {1 2 3}\n    actual: {1 2 4}\n                 ^";
+///     assert!(err.to_string().ends_with(diff));
+/// }
+/// ```
+fn lassert_eq(_env: Option<&Rc<Lenv>>, lval: &mut Lval) -> Lval {
+    if lval.cell.len() != 2 {
+        return Lval::lval_err(Lerror::WrongNumberOfArgs {
+            lval: Box::new(lval.clone()),
+            expect: 2,
+            got: lval.cell.len(),
+        });
+    }
+    let actual = lval.lval_pop_last();
+    let expected = lval.lval_pop_last();
+    if expected == actual {
+        Lval::lval_sexpr()
+    } else {
+        Lval::lval_err(Lerror::NotEqual {
+            expected: Box::new(expected),
+            actual: Box::new(actual),
+        })
+    }
+}
+
+/// Evaluates a Q-expression as `eval` does, and fails unless it gives back an
+/// error. Gives back the message of the error, so it can be checked too
+/// # Examples
+/// ```
+/// # use rispreter_repl::lval::lval_env::Lenv;
+/// # use rispreter_repl::eval::eval_rispreter;
+/// let env = Lenv::new();
+///
+/// let res = eval_rispreter(&env, "(assert-error {/ 1 0})");
+/// assert_eq!("\"DivisionByZero .. Divizion by zero\"", res.to_string());
+/// let res = eval_rispreter(&env, "(assert-error {/ 4 2})");
+/// assert_eq!("error", res.type_name());
+/// ```
+fn lassert_error(env: Option<&Rc<Lenv>>, lval: &mut Lval) -> Lval {
+    if lval.cell.len() != 1 {
+        return Lval::lval_err(Lerror::WrongNumberOfArgs {
+            lval: Box::new(lval.clone()),
+            expect: 1,
+            got: lval.cell.len(),
+        });
+    }
+    let res = eval(env, lval);
+    match &res.ltype {
+        // exiting and aborting in the debugger aren't errors of what's tested
+        LvalType::LVAL_ERR(Lerror::Exit { .. }) | LvalType::LVAL_ERR(Lerror::Aborted) => res,
+        LvalType::LVAL_ERR(err) => Lval::lval_string(err.to_string()),
+        _ => Lval::lval_err(Lerror::AssertionFailed {
+            msg: format!("Expected an error, got {}", res),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eval::eval_rispreter;
+    use crate::test_util::TempDir;
+
+    #[test]
+    /// tests the expression (+ 1 2 3)
+    fn lbuiltin_op_add() {
+        let mut top = Lval::lval_sexpr();
+        let plus_op = Lval::lval_sym("+".to_string());
+        let one = Lval::lval_num(1.0);
+        let two = Lval::lval_num(2.0);
+        let three = Lval::lval_num(3.0);
+        top.add_cell(plus_op)
+            .add_cell(one)
+            .add_cell(two)
+            .add_cell(three);
+
+        assert_eq!(top.cell.len(), 4);
+        top.lval_pop();
+        top = op(None, &mut top, '+');
+        println!("{:?}", top);
+        assert_eq!(top.cell.len(), 0);
+        assert_eq!(top.ltype, LvalType::LVAL_NUM(6.0));
+    }
+
+    #[test]
+    /// tests the expression (- 4 2 1)
+    fn lbuiltin_op_sub() {
+        let mut top = Lval::lval_sexpr();
+        let sub_op = Lval::lval_sym("-".to_string());
+        let one = Lval::lval_num(4.0);
+        let two = Lval::lval_num(2.0);
+        let three = Lval::lval_num(1.0);
+        top.add_cell(sub_op)
+            .add_cell(one)
+            .add_cell(two)
+            .add_cell(three);
+
+        assert_eq!(top.cell.len(), 4);
+        top.lval_pop();
+        top = op(None, &mut top, '-');
+        println!("{:?}", top);
+        assert_eq!(top.cell.len(), 0);
+        assert_eq!(top.ltype, LvalType::LVAL_NUM(1.0));
+    }
+
+    #[test]
+    /// tests the expression (* 1 2 4)
+    fn lbuiltin_op_mul() {
+        let mut top = Lval::lval_sexpr();
+        let mult_op = Lval::lval_sym("*".to_string());
+        let one = Lval::lval_num(1.0);
+        let two = Lval::lval_num(2.0);
+        let three = Lval::lval_num(4.0);
+        top.add_cell(mult_op)
+            .add_cell(one)
+            .add_cell(two)
+            .add_cell(three);
+
+        assert_eq!(top.cell.len(), 4);
+        top.lval_pop();
+        top = op(None, &mut top, '*');
+        println!("{:?}", top);
+        assert_eq!(top.cell.len(), 0);
+        assert_eq!(top.ltype, LvalType::LVAL_NUM(8.0));
+    }
+
+    #[test]
+    /// tests the expression (/ 9 5 2)
+    fn lbuiltin_op_div() {
+        let mut top = Lval::lval_sexpr();
         let div_op = Lval::lval_sym("/".to_string());
         let one = Lval::lval_num(9.0);
         let two = Lval::lval_num(5.0);
@@ -1264,4 +3479,320 @@ mod tests {
         top = eval(Some(&mut lenv), &mut top);
         assert_eq!(top.ltype, LvalType::LVAL_NUM(3.0));
     }
+
+    #[test]
+    fn lbuiltin_match() {
+        let env = Lenv::new();
+        let res = eval_rispreter(&env, "(match {1 {2 3}} {{a {b c}} (+ a b c)})");
+        assert_eq!(6f64, res);
+
+        let res = eval_rispreter(&env, "(match \"risp\" {(num? _) 0} {(str? s) s})");
+        assert_eq!(Lval::lval_string("risp".to_string()), res);
+
+        let res = eval_rispreter(&env, "(match {} {{x & xs} x} {{} true})");
+        assert_eq!(true, res);
+
+        let res = eval_rispreter(&env, "(match 3 {x (> x 5) x})");
+        if let LvalType::LVAL_ERR(Lerror::NonExhaustiveMatch { .. }) = res.ltype {
+        } else {
+            panic!("expected a non exhaustive match error, got {}", res);
+        }
+
+        let res = eval_rispreter(&env, "(match {1 {1}} {{x {x}} x} {_ 0})");
+        assert_eq!(1f64, res);
+        let res = eval_rispreter(&env, "(match {1 2} {{x x} \"same\"} {_ \"diff\"})");
+        assert_eq!(Lval::lval_string("diff".to_string()), res);
+
+        let res = eval_rispreter(&env, "(match 3 {x 1 \"one\"} {_ \"other\"})");
+        if let LvalType::LVAL_ERR(Lerror::WrongType { .. }) = res.ltype {
+        } else {
+            panic!("expected a wrong type error for the guard, got {}", res);
+        }
+    }
+
+    #[test]
+    fn lbuiltin_match_binds_in_child_env() {
+        let env = Lenv::new();
+        eval_rispreter(&env, "(match 1 {x x})");
+        let res = eval_rispreter(&env, "x");
+        if let LvalType::LVAL_ERR(Lerror::SymbolNotBinded { .. }) = res.ltype {
+        } else {
+            panic!("pattern binding leaked to the caller env: {}", res);
+        }
+    }
+
+    #[test]
+    fn lbuiltin_map_reads_back() {
+        let env = Lenv::new();
+        let map = eval_rispreter(&env, "(assoc #{} \"b\" {1 2} 'c' #{x 1.5})");
+        let printed = map.to_string();
+        assert_eq!("#{\"b\" {1 2} 'c' #{x 1.5}}", printed);
+        assert_eq!(map, eval_rispreter(&env, &printed));
+        assert_eq!(
+            true,
+            eval_rispreter(&env, "(== #{a 1 b 2} (merge #{b 2} #{a 1}))")
+        );
+    }
+
+    #[test]
+    fn lbuiltin_set_map_filter() {
+        let env = Lenv::new();
+        let res = eval_rispreter(&env, "(map (\\ {x} {% x 2}) #s{1 2 3 4})");
+        assert_eq!("#s{0 1}", res.to_string());
+
+        let res = eval_rispreter(&env, "(filter (\\ {x} {> x 2}) #s{1 2 3 4})");
+        assert_eq!(eval_rispreter(&env, "#s{3 4}"), res);
+
+        let res = eval_rispreter(&env, "(map (\\ {x} {/ x 0}) #s{1 2})");
+        assert_eq!(Lval::lval_err(Lerror::DivisionByZero), res);
+        let res = eval_rispreter(&env, "(map (\\ {x} {\\ {y} {x}}) #s{1})");
+        match res.ltype {
+            LvalType::LVAL_ERR(Lerror::NotAKey { .. }) => {}
+            _ => panic!("a lambda made a set element: {}", res),
+        }
+
+        for builtin in &["map", "filter"] {
+            let res = eval_rispreter(&env, &format!("({} (\\ {{x}} {{true}}) #{{a 1}})", builtin));
+            match res.ltype {
+                LvalType::LVAL_ERR(Lerror::WrongType { .. }) => {}
+                _ => panic!("{} over a map gave back {}", builtin, res),
+            }
+        }
+    }
+
+    #[test]
+    fn lbuiltin_str_unicode() {
+        let env = Lenv::new();
+        let res = eval_rispreter(
+            &env,
+            "(chars->str (str->chars (substr \"日本語テキスト\" 2 5)))",
+        );
+        assert_eq!(Lval::lval_string("語テキ".to_string()), res);
+
+        let res = eval_rispreter(
+            &env,
+            "(str-len (str-join \"\" (str-split \"ü-ö-ä\" \"-\")))",
+        );
+        assert_eq!(3f64, res);
+    }
+
+    #[test]
+    fn lbuiltin_println_to_captured_output() {
+        use crate::lval::lval_context::CapturedOutput;
+
+        let env = Lenv::new();
+        let output = CapturedOutput::new();
+        env.context().set_output(Box::new(output.clone()));
+        eval_rispreter(
+            &env,
+            "(def {greet} (\\ {name} {println (format \"hello, {}!\" name)}))",
+        );
+        let res = eval_rispreter(&env, "(greet \"risp\")");
+        assert_eq!(Lval::lval_sexpr(), res);
+        assert_eq!("hello, risp!\n", output.contents());
+    }
+
+    #[test]
+    fn lbuiltin_format_errors() {
+        let env = Lenv::new();
+        for fmt in &[
+            "(format \"{} {}\" 1)",
+            "(format \"{}\" 1 2)",
+            "(format \"{:x}\" 1)",
+            "(format \"{\")",
+        ] {
+            let res = eval_rispreter(&env, fmt);
+            if let LvalType::LVAL_ERR(Lerror::InvalidFormat { .. }) = res.ltype {
+            } else {
+                panic!("expected a format error for {}, got {}", fmt, res);
+            }
+        }
+    }
+
+    #[test]
+    fn lbuiltin_file_system() {
+        let tmp = TempDir::new("fs");
+        let dir = tmp.join("notes").to_string_lossy().to_string();
+        let env = Lenv::new();
+        eval_rispreter(&env, &format!("(def {{dir}} \"{}\")", dir));
+        eval_rispreter(&env, "(def {path} (str-concat dir \"/notes.txt\"))");
+
+        assert_eq!(Lval::lval_sexpr(), eval_rispreter(&env, "(make-dir dir)"));
+        assert_eq!(
+            Lval::lval_sexpr(),
+            eval_rispreter(&env, "(write-file path \"one\n\")")
+        );
+        assert_eq!(
+            Lval::lval_sexpr(),
+            eval_rispreter(&env, "(append-file path \"two\n\")")
+        );
+        let res = eval_rispreter(&env, "(read-file path)");
+        assert_eq!(Lval::lval_string("one\ntwo\n".to_string()), res);
+        let res = eval_rispreter(&env, "(read-lines path)");
+        assert_eq!("{\"one\" \"two\"}", res.to_string());
+        let res = eval_rispreter(&env, "(list-dir dir)");
+        assert_eq!("{\"notes.txt\"}", res.to_string());
+
+        assert_eq!(
+            Lval::lval_sexpr(),
+            eval_rispreter(&env, "(remove-file path)")
+        );
+        assert_eq!(
+            Lval::lval_bool(false),
+            eval_rispreter(&env, "(file-exists? path)")
+        );
+        if let LvalType::LVAL_ERR(Lerror::IoError { .. }) =
+            eval_rispreter(&env, "(remove-file path)").ltype
+        {
+        } else {
+            panic!("expected an I/O error removing a missing file");
+        }
+    }
+
+    #[test]
+    fn lbuiltin_file_system_not_permitted() {
+        use crate::lval::lval_context::Lcapabilities;
+
+        let env = Lenv::new();
+        env.context().set_capabilities(Lcapabilities {
+            file_system: false,
+            ..Lcapabilities::default()
+        });
+        let res = eval_rispreter(&env, "(file-exists? \".\")");
+        if let LvalType::LVAL_ERR(Lerror::NotPermitted { op }) = res.ltype {
+            assert_eq!("file-exists?", op);
+        } else {
+            panic!("expected file-exists? to be refused, got {}", res);
+        }
+    }
+
+    #[test]
+    fn lbuiltin_run() {
+        let env = Lenv::new();
+        let res = eval_rispreter(
+            &env,
+            "(run \"sh\" \"-c\" \"echo out; echo err >&2; exit 2\")",
+        );
+        assert_eq!(
+            "#{\"status\" 2 \"stderr\" \"err\\n\" \"stdout\" \"out\\n\"}",
+            res.to_string()
+        );
+
+        if let LvalType::LVAL_ERR(Lerror::IoError { path, .. }) =
+            eval_rispreter(&env, "(run \"no-such-program-risp\")").ltype
+        {
+            assert_eq!("no-such-program-risp", path);
+        } else {
+            panic!("expected an I/O error running a missing program");
+        }
+    }
+
+    #[test]
+    fn lbuiltin_process_not_permitted() {
+        use crate::lval::lval_context::Lcapabilities;
+
+        let env = Lenv::new();
+        env.context().set_capabilities(Lcapabilities {
+            process: false,
+            ..Lcapabilities::default()
+        });
+        for op in &[
+            "(getenv \"HOME\")",
+            "(setenv \"A\" \"b\")",
+            "(run \"true\")",
+        ] {
+            let res = eval_rispreter(&env, op);
+            if let LvalType::LVAL_ERR(Lerror::NotPermitted { .. }) = res.ltype {
+            } else {
+                panic!("expected {} to be refused, got {}", op, res);
+            }
+        }
+    }
+
+    #[test]
+    fn lbuiltin_load_and_import() {
+        let dir = TempDir::new("mod");
+        std::fs::create_dir_all(dir.join("lib")).unwrap();
+        std::fs::write(
+            dir.join("lib/math.rspr"),
+            "(fun {square x} {* x x})\n(fun {area r} {* 3 (square r)})\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("main.rspr"),
+            "(load \"defs\")\n(import \"math\" {area})\n(math/area radius)",
+        )
+        .unwrap();
+        std::fs::write(dir.join("defs.rspr"), "; defs\n(def {radius} 2)").unwrap();
+        std::fs::write(dir.join("a.rspr"), "(import \"b\")").unwrap();
+        std::fs::write(dir.join("b.rspr"), "(import \"a\")").unwrap();
+
+        let env = Lenv::new();
+        env.context().set_search_path(vec![dir.join("lib")]);
+        let res = eval_rispreter_file(&env, &dir.join("main.rspr"));
+        assert_eq!(12f64, res);
+        // only the selected export is bound, and helpers of the module stay hidden
+        assert!(env
+            .get("math/square".to_string())
+            .unwrap()
+            .to_string()
+            .contains("SymbolNotBinded"));
+        assert!(env
+            .get("square".to_string())
+            .unwrap()
+            .to_string()
+            .contains("SymbolNotBinded"));
+
+        match eval_rispreter_file(&env, &dir.join("a.rspr")).ltype {
+            LvalType::LVAL_ERR(Lerror::CircularImport { chain }) => assert_eq!(3, chain.len()),
+            e => panic!("expected a circular import, got {:?}", e),
+        }
+        match eval_rispreter(&env, "(import \"nowhere\")").ltype {
+            LvalType::LVAL_ERR(Lerror::ModuleNotFound { searched, .. }) => {
+                assert_eq!(2, searched.len())
+            }
+            e => panic!("expected a missing module, got {:?}", e),
+        }
+    }
+
+    #[test]
+    fn lbuiltin_import_sees_prelude() {
+        let dir = TempDir::new("prelude");
+        std::fs::write(
+            dir.join("twice.rspr"),
+            "(fun {twice x} {double (double x)})",
+        )
+        .unwrap();
+
+        let env = Lenv::new();
+        eval_rispreter(&env, "(fun {double x} {* 2 x})");
+        env.context().set_prelude(env.bindings());
+        let res = eval_rispreter(
+            &env,
+            &format!("(import \"{}/twice\")", dir.path().display()),
+        );
+        assert_eq!("{twice/twice}", res.to_string());
+        assert_eq!(12f64, eval_rispreter(&env, "(twice/twice 3)"));
+    }
+
+    #[test]
+    fn lbuiltin_import_frees_the_session() {
+        let dir = TempDir::new("free");
+        std::fs::write(dir.join("sq.rspr"), "(fun {sq x} {* x x})").unwrap();
+
+        let env = Lenv::new();
+        let context = Rc::downgrade(&env.context());
+        eval_rispreter(&env, &format!("(import \"{}/sq\")", dir.path().display()));
+        assert_eq!(9f64, eval_rispreter(&env, "(sq/sq 3)"));
+        let sq = eval_rispreter(&env, "sq/sq");
+        // the modules of the session go with it
+        std::mem::drop(env);
+        assert!(context.upgrade().is_none());
+
+        // what it exported is still called, in the session calling it
+        let env = Lenv::new();
+        env.def("sq".to_string(), sq).unwrap();
+        assert_eq!(16f64, eval_rispreter(&env, "(sq 4)"));
+        assert_eq!(25f64, eval_rispreter(&env, "((\\ {f} {f 5}) sq)"));
+    }
 }
@@ -0,0 +1,261 @@
+use crate::lval::lval_coverage::Lcoverage;
+use crate::lval::lval_debug::Ldebugger;
+use crate::lval::lval_def::Lval;
+use crate::lval::lval_profile::Lprofiler;
+use crate::lval::lval_trace::Ltracer;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+/// What a session is allowed to touch outside the interpreter, everything by default
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Lcapabilities {
+    /// file system builtins, like `read-file` and `write-file`
+    pub file_system: bool,
+    /// environment variables and subprocesses, like `getenv` and `run`
+    pub process: bool,
+}
+
+impl Default for Lcapabilities {
+    fn default() -> Self {
+        Lcapabilities {
+            file_system: true,
+            process: true,
+        }
+    }
+}
+
+/// Interpreter wide state, shared by every `Lenv` of a session
+pub struct Lcontext {
+    output: RefCell<Box<dyn Write>>,
+    capabilities: Cell<Lcapabilities>,
+    files: RefCell<Vec<PathBuf>>,
+    search_path: RefCell<Vec<PathBuf>>,
+    modules: RefCell<HashMap<PathBuf, Vec<(String, Lval)>>>,
+    prelude: RefCell<Vec<(String, Lval)>>,
+    steps: Cell<u64>,
+    results: RefCell<Vec<Lval>>,
+    calls: RefCell<Vec<String>>,
+    debugger: Ldebugger,
+    tracer: Ltracer,
+    profiler: Lprofiler,
+    coverage: Lcoverage,
+    tests: RefCell<Vec<(String, Lval)>>,
+}
+
+impl Lcontext {
+    pub fn new() -> Rc<Lcontext> {
+        Rc::new(Lcontext::default())
+    }
+
+    /// Sets where `print` and `println` write to, stdout by default
+    pub fn set_output(&self, output: Box<dyn Write>) {
+        *self.output.borrow_mut() = output;
+    }
+
+    pub fn capabilities(&self) -> Lcapabilities {
+        self.capabilities.get()
+    }
+
+    pub fn set_capabilities(&self, capabilities: Lcapabilities) {
+        self.capabilities.set(capabilities);
+    }
+
+    /// The file being evaluated right now, if any
+    pub fn current_file(&self) -> Option<PathBuf> {
+        self.files.borrow().last().cloned()
+    }
+
+    /// Marks a file as being evaluated. If it already is, gives back the chain of
+    /// files that leads to it again
+    pub fn enter_file(&self, path: PathBuf) -> Result<(), Vec<PathBuf>> {
+        let mut files = self.files.borrow_mut();
+        if let Some(at) = files.iter().position(|f| *f == path) {
+            let mut chain = files[at..].to_vec();
+            chain.push(path);
+            return Err(chain);
+        }
+        files.push(path);
+        Ok(())
+    }
+
+    pub fn leave_file(&self) {
+        self.files.borrow_mut().pop();
+    }
+
+    /// Directories `import` looks into, after the one of the current file
+    pub fn search_path(&self) -> Vec<PathBuf> {
+        self.search_path.borrow().clone()
+    }
+
+    pub fn set_search_path(&self, search_path: Vec<PathBuf>) {
+        *self.search_path.borrow_mut() = search_path;
+    }
+
+    /// The definitions of a module already imported in this session
+    pub fn module(&self, path: &Path) -> Option<Vec<(String, Lval)>> {
+        self.modules.borrow().get(path).cloned()
+    }
+
+    pub fn add_module(&self, path: PathBuf, bindings: Vec<(String, Lval)>) {
+        self.modules.borrow_mut().insert(path, bindings);
+    }
+
+    /// What the standard library of the session defines, modules start with it
+    pub fn prelude(&self) -> Vec<(String, Lval)> {
+        self.prelude.borrow().clone()
+    }
+
+    pub fn set_prelude(&self, prelude: Vec<(String, Lval)>) {
+        *self.prelude.borrow_mut() = prelude;
+    }
+
+    /// Counts an evaluation step
+    pub fn step(&self) {
+        self.steps.set(self.steps.get() + 1);
+    }
+
+    /// How many evaluation steps the session took so far
+    pub fn steps(&self) -> u64 {
+        self.steps.get()
+    }
+
+    /// Keeps the result of an input of the REPL, giving back its number
+    pub fn add_result(&self, result: Lval) -> usize {
+        let mut results = self.results.borrow_mut();
+        results.push(result);
+        results.len()
+    }
+
+    /// The result of the REPL input with a number, counting from 1
+    pub fn result(&self, n: usize) -> Option<Lval> {
+        n.checked_sub(1)
+            .and_then(|i| self.results.borrow().get(i).cloned())
+    }
+
+    /// How many inputs of the REPL have a result
+    pub fn results(&self) -> usize {
+        self.results.borrow().len()
+    }
+
+    /// Marks a function as being called, by the name it was called with
+    pub fn enter_call(&self, name: String) {
+        self.calls.borrow_mut().push(name);
+    }
+
+    pub fn leave_call(&self) {
+        self.calls.borrow_mut().pop();
+    }
+
+    /// The functions being called right now, the outermost first
+    pub fn calls(&self) -> Vec<String> {
+        self.calls.borrow().clone()
+    }
+
+    /// How deep in function calls the evaluation is
+    pub fn depth(&self) -> usize {
+        self.calls.borrow().len()
+    }
+
+    pub fn debugger(&self) -> &Ldebugger {
+        &self.debugger
+    }
+
+    pub fn tracer(&self) -> &Ltracer {
+        &self.tracer
+    }
+
+    pub fn profiler(&self) -> &Lprofiler {
+        &self.profiler
+    }
+
+    pub fn coverage(&self) -> &Lcoverage {
+        &self.coverage
+    }
+
+    /// Keeps a test defined with `deftest`, by its name and its body
+    pub fn add_test(&self, name: String, body: Lval) {
+        self.tests.borrow_mut().push((name, body));
+    }
+
+    /// The tests defined so far, in order, forgetting them
+    pub fn take_tests(&self) -> Vec<(String, Lval)> {
+        self.tests.borrow_mut().drain(..).collect()
+    }
+
+    /// Forgets what the session evaluated: imported modules, the prelude, the
+    /// step count, the results and the tests. The output, capabilities, search
+    /// path, the breakpoints and the traced functions are kept
+    pub fn reset(&self) {
+        self.files.borrow_mut().clear();
+        self.modules.borrow_mut().clear();
+        self.prelude.borrow_mut().clear();
+        self.steps.set(0);
+        self.results.borrow_mut().clear();
+        self.tests.borrow_mut().clear();
+        self.calls.borrow_mut().clear();
+        self.debugger.stop_stepping();
+        self.tracer.reset();
+    }
+
+    pub fn write(&self, text: &str) -> io::Result<()> {
+        let mut output = self.output.borrow_mut();
+        output.write_all(text.as_bytes())?;
+        output.flush()
+    }
+}
+
+impl Default for Lcontext {
+    fn default() -> Self {
+        Lcontext {
+            output: RefCell::new(Box::new(io::stdout())),
+            capabilities: Cell::new(Lcapabilities::default()),
+            files: RefCell::new(Vec::new()),
+            search_path: RefCell::new(Vec::new()),
+            modules: RefCell::new(HashMap::new()),
+            prelude: RefCell::new(Vec::new()),
+            steps: Cell::new(0),
+            results: RefCell::new(Vec::new()),
+            calls: RefCell::new(Vec::new()),
+            debugger: Ldebugger::default(),
+            tracer: Ltracer::default(),
+            profiler: Lprofiler::default(),
+            coverage: Lcoverage::default(),
+            tests: RefCell::new(Vec::new()),
+        }
+    }
+}
+
+impl std::fmt::Debug for Lcontext {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Lcontext")
+    }
+}
+
+/// A `Write` that keeps what is written to it, so the output of a session can be
+/// captured and read back
+#[derive(Clone, Default)]
+pub struct CapturedOutput(Rc<RefCell<Vec<u8>>>);
+
+impl CapturedOutput {
+    pub fn new() -> Self {
+        CapturedOutput::default()
+    }
+
+    pub fn contents(&self) -> String {
+        String::from_utf8_lossy(&self.0.borrow()).to_string()
+    }
+}
+
+impl Write for CapturedOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,309 @@
+use crate::lval::lval_def::Lspan;
+use rispreter_parser::syntax::{read_syntax, Node, Span, Syntax};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::fmt;
+
+/// What a part of a source that can be evaluated or not is
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Lregion {
+    /// a form of the program
+    Form,
+    /// the body of a `fun` or a `\`
+    Body,
+    /// a branch of an `if`, or a clause of a `select`, numbered in the source by
+    /// the `if` or `select` it's part of, its block
+    Branch { block: usize, branch: usize },
+}
+
+/// How many times a part of a source was evaluated
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lcovered {
+    pub region: Lregion,
+    pub span: Span,
+    /// counting from 1
+    pub line: usize,
+    pub count: u64,
+}
+
+/// The coverage of a source
+#[derive(Debug)]
+pub struct LsourceCoverage {
+    /// the path of the file, or `<input>` for a program that isn't one
+    pub name: String,
+    pub text: String,
+    /// what can be evaluated in the source, in order
+    pub covered: Vec<Lcovered>,
+}
+
+impl LsourceCoverage {
+    /// Every line a region starts on, with how many times the most evaluated one
+    /// was, and if there's one on it that wasn't
+    fn lines(&self) -> Vec<(usize, u64, bool)> {
+        let mut lines: Vec<(usize, u64, bool)> = Vec::new();
+        for covered in &self.covered {
+            match lines.iter_mut().find(|(line, _, _)| *line == covered.line) {
+                Some((_, count, missed)) => {
+                    *count = (*count).max(covered.count);
+                    *missed = *missed || covered.count == 0;
+                }
+                None => lines.push((covered.line, covered.count, covered.count == 0)),
+            }
+        }
+        lines.sort();
+        lines
+    }
+}
+
+/// The source listed with how many times what starts on every line was
+/// evaluated: `#####` for never, and a `*` after the count when some of it was
+/// never evaluated
+impl fmt::Display for LsourceCoverage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let lines = self.lines();
+        write!(f, "{}", self.name)?;
+        for (i, text) in self.text.lines().enumerate() {
+            let marker = match lines.iter().find(|(line, _, _)| *line == i + 1) {
+                None => String::new(),
+                Some((_, 0, _)) => "#####".to_string(),
+                Some((_, count, true)) => format!("{}*", count),
+                Some((_, count, false)) => count.to_string(),
+            };
+            write!(f, "\n{:>7} | {}", marker, text)?;
+        }
+        Ok(())
+    }
+}
+
+/// What a coverage saw, for every source evaluated while it was recording
+#[derive(Debug, Default)]
+pub struct LcoverageReport {
+    pub sources: Vec<LsourceCoverage>,
+}
+
+impl LcoverageReport {
+    /// The report in the lcov tracefile format: the lines regions start on, and
+    /// the branches of the `if`s and `select`s
+    pub fn lcov(&self) -> String {
+        let mut lcov = String::new();
+        for source in &self.sources {
+            lcov.push_str(&format!("TN:\nSF:{}\n", source.name));
+            let mut branches = 0;
+            let mut branches_hit = 0;
+            for covered in &source.covered {
+                if let Lregion::Branch { block, branch } = covered.region {
+                    lcov.push_str(&format!(
+                        "BRDA:{},{},{},{}\n",
+                        covered.line, block, branch, covered.count
+                    ));
+                    branches += 1;
+                    if covered.count > 0 {
+                        branches_hit += 1;
+                    }
+                }
+            }
+            lcov.push_str(&format!("BRF:{}\nBRH:{}\n", branches, branches_hit));
+            let lines = source.lines();
+            for (line, count, _) in &lines {
+                lcov.push_str(&format!("DA:{},{}\n", line, count));
+            }
+            let hit = lines.iter().filter(|(_, count, _)| *count > 0).count();
+            lcov.push_str(&format!("LF:{}\nLH:{}\nend_of_record\n", lines.len(), hit));
+        }
+        lcov
+    }
+}
+
+impl fmt::Display for LcoverageReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, source) in self.sources.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", source)?;
+        }
+        Ok(())
+    }
+}
+
+/// Records which parts of the sources are evaluated, and how many times. Only the
+/// programs read while it's recording have their values spanned, and so counted
+#[derive(Debug, Default)]
+pub struct Lcoverage {
+    recording: Cell<bool>,
+    sources: RefCell<Vec<(String, String)>>,
+    hits: RefCell<HashMap<(usize, usize, usize), u64>>,
+}
+
+impl Lcoverage {
+    /// Starts recording, forgetting what was recorded before
+    pub fn start(&self) {
+        self.recording.set(true);
+        self.sources.borrow_mut().clear();
+        self.hits.borrow_mut().clear();
+    }
+
+    /// Stops recording, giving back what was
+    pub fn stop(&self) -> LcoverageReport {
+        self.recording.set(false);
+        let hits = self.hits.borrow();
+        let sources = self
+            .sources
+            .borrow_mut()
+            .drain(..)
+            .enumerate()
+            .map(|(i, (name, text))| {
+                let covered = regions(&text)
+                    .into_iter()
+                    .map(|(span, region)| Lcovered {
+                        region,
+                        span,
+                        line: text[..span.start].matches('\n').count() + 1,
+                        count: hits.get(&(i, span.start, span.end)).cloned().unwrap_or(0),
+                    })
+                    .collect();
+                LsourceCoverage {
+                    name,
+                    text,
+                    covered,
+                }
+            })
+            .collect();
+        LcoverageReport { sources }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.get()
+    }
+
+    /// Number of a source being read, the same for a file read again
+    pub fn add_source(&self, name: String, text: &str) -> usize {
+        let mut sources = self.sources.borrow_mut();
+        if let Some(i) = sources.iter().position(|(n, t)| *n == name && t == text) {
+            return i;
+        }
+        sources.push((name, text.to_string()));
+        sources.len() - 1
+    }
+
+    /// Counts the evaluation of a value read from a source
+    pub fn hit(&self, span: Lspan) {
+        if self.recording.get() {
+            *self
+                .hits
+                .borrow_mut()
+                .entry((span.source, span.start, span.end))
+                .or_insert(0) += 1;
+        }
+    }
+}
+
+/// The parts of a source that are evaluated or not, in order
+fn regions(text: &str) -> Vec<(Span, Lregion)> {
+    let mut regions = Vec::new();
+    let mut blocks = 0;
+    if let Ok(nodes) = read_syntax(text) {
+        for node in nodes.iter().filter(|node| node.is_form()) {
+            regions.push((node.span, Lregion::Form));
+            nested_regions(node, &mut blocks, &mut regions);
+        }
+    }
+    regions.sort_by_key(|(span, _)| span.start);
+    regions
+}
+
+fn nested_regions(node: &Node, blocks: &mut usize, regions: &mut Vec<(Span, Lregion)>) {
+    let forms: Vec<&Node> = node.forms().collect();
+    // Q-expressions are the bodies and branches evaluated as S-expressions
+    let head = match node.syntax {
+        Syntax::List { open: "(", .. } | Syntax::List { open: "{", .. } => {
+            forms.first().and_then(|head| head.atom())
+        }
+        _ => None,
+    };
+    match head {
+        Some("fun") | Some("\\") => {
+            if let Some(body) = forms.get(2) {
+                regions.push((body.span, Lregion::Body));
+            }
+        }
+        Some("if") => {
+            for (branch, node) in forms.iter().skip(2).take(2).enumerate() {
+                regions.push((
+                    node.span,
+                    Lregion::Branch {
+                        block: *blocks,
+                        branch,
+                    },
+                ));
+            }
+            *blocks += 1;
+        }
+        Some("select") => {
+            for (branch, clause) in forms.iter().skip(1).enumerate() {
+                if let Some(node) = clause.forms().nth(1) {
+                    regions.push((
+                        node.span,
+                        Lregion::Branch {
+                            block: *blocks,
+                            branch,
+                        },
+                    ));
+                }
+            }
+            *blocks += 1;
+        }
+        _ => {}
+    }
+    for form in forms {
+        nested_regions(form, blocks, regions);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::eval::eval_rispreter_program;
+    use crate::lval::lval_env::Lenv;
+
+    #[test]
+    fn coverage_counts_forms_bodies_and_branches() {
+        let env = Lenv::new();
+        let context = env.context();
+        context.coverage().start();
+        eval_rispreter_program(
+            &env,
+            "(fun {sign n}
+  {select
+    {(> n 0) 1}
+    {(< n 0) -1}
+    {true 0}})
+(fun {unused x} {x})
+(if (== (sign 5) 1)
+  {sign -2}
+  {0})",
+        );
+        let report = context.coverage().stop();
+        assert!(!context.coverage().is_recording());
+        assert_eq!(
+            "<input>
+      1 | (fun {sign n}
+      2 |   {select
+      1 |     {(> n 0) 1}
+      1 |     {(< n 0) -1}
+  ##### |     {true 0}})
+     1* | (fun {unused x} {x})
+      1 | (if (== (sign 5) 1)
+      1 |   {sign -2}
+  ##### |   {0})",
+            report.to_string()
+        );
+        let lcov = report.lcov();
+        assert!(lcov.starts_with("TN:\nSF:<input>\nBRDA:3,0,0,1\nBRDA:4,0,1,1\nBRDA:5,0,2,0\n"));
+        assert!(lcov.contains("BRF:5\nBRH:3\n"), "{}", lcov);
+        assert!(
+            lcov.ends_with("DA:9,0\nLF:9\nLH:7\nend_of_record\n"),
+            "{}",
+            lcov
+        );
+    }
+}
@@ -0,0 +1,228 @@
+use crate::lval::lval_def::Lval;
+use crate::lval::lval_env::Lenv;
+use std::cell::{Cell, RefCell};
+use std::fmt;
+use std::rc::Rc;
+
+/// How the evaluation goes on after a stop in the debugger
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Lresume {
+    /// stops again at the next call, inside the one being made too
+    Step,
+    /// stops again after the call being made, at the next one of the same function
+    /// or of one it returns to
+    Next,
+    /// runs up to the next breakpoint or `break`
+    Continue,
+    /// gives up on the evaluation, with an error
+    Abort,
+}
+
+/// Why the evaluation stopped
+#[derive(Debug, Clone, PartialEq)]
+pub enum Lreason {
+    /// a function with a breakpoint was called
+    Breakpoint(String),
+    /// `break` was called
+    Break,
+    /// stepping reached another call
+    Step,
+}
+
+/// Where the evaluation stopped: the call about to be made, with its arguments
+/// evaluated, the env it's made in and the names of the functions being called,
+/// the outermost first
+pub struct Lstop {
+    pub reason: Lreason,
+    pub expr: Lval,
+    pub env: Rc<Lenv>,
+    pub calls: Vec<String>,
+}
+
+impl Lstop {
+    /// The bindings of the envs the stop is evaluated in, innermost first, up to
+    /// the global one. A name shadowed by an inner env is only there once
+    pub fn locals(&self) -> Vec<(String, Lval)> {
+        let mut locals: Vec<(String, Lval)> = Vec::new();
+        let mut env = Rc::clone(&self.env);
+        while let Some(parent) = env.parent() {
+            for (name, val) in env.bindings() {
+                if !locals.iter().any(|(local, _)| *local == name) {
+                    locals.push((name, val));
+                }
+            }
+            env = parent;
+        }
+        locals
+    }
+}
+
+impl fmt::Display for Lstop {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.reason {
+            Lreason::Breakpoint(name) => write!(f, "breakpoint on {}: {}", name, self.expr)?,
+            Lreason::Break => write!(f, "break: {}", self.expr)?,
+            Lreason::Step => write!(f, "step: {}", self.expr)?,
+        }
+        if self.calls.is_empty() {
+            write!(f, "\n  calls: (top level)")?;
+        } else {
+            write!(f, "\n  calls: {}", self.calls.join(" > "))?;
+        }
+        for (name, val) in self.locals() {
+            write!(f, "\n  {} = {}", name, val)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+enum Mode {
+    #[default]
+    Run,
+    Step,
+    /// stepping over the calls deeper than this
+    Next(usize),
+}
+
+/// What the debugger is given every stop, to say how the evaluation goes on
+pub type Lhandler = Box<dyn FnMut(&Lstop) -> Lresume>;
+
+/// Breakpoints on function names and stepping through the evaluation. It only
+/// stops with a handler, which is given the stops and says how to go on, like the
+/// REPL asking for commands
+#[derive(Default)]
+pub struct Ldebugger {
+    breakpoints: RefCell<Vec<String>>,
+    mode: Cell<Mode>,
+    handler: RefCell<Option<Lhandler>>,
+}
+
+impl Ldebugger {
+    pub fn set_handler(&self, handler: Lhandler) {
+        *self.handler.borrow_mut() = Some(handler);
+    }
+
+    /// Stops every time the function bound to a name is called
+    pub fn add_breakpoint(&self, name: &str) {
+        let mut breakpoints = self.breakpoints.borrow_mut();
+        if !breakpoints.iter().any(|breakpoint| breakpoint == name) {
+            breakpoints.push(name.to_string());
+        }
+    }
+
+    /// Removes the breakpoint on a name, false if there was none
+    pub fn remove_breakpoint(&self, name: &str) -> bool {
+        let mut breakpoints = self.breakpoints.borrow_mut();
+        let before = breakpoints.len();
+        breakpoints.retain(|breakpoint| breakpoint != name);
+        breakpoints.len() != before
+    }
+
+    pub fn clear_breakpoints(&self) {
+        self.breakpoints.borrow_mut().clear();
+    }
+
+    pub fn breakpoints(&self) -> Vec<String> {
+        self.breakpoints.borrow().clone()
+    }
+
+    /// Stops at the next call
+    pub fn step(&self) {
+        self.mode.set(Mode::Step);
+    }
+
+    /// Stops stepping, only the breakpoints and `break` stop from now on
+    pub fn stop_stepping(&self) {
+        self.mode.set(Mode::Run);
+    }
+
+    /// Why a call of the function bound to `name`, `depth` calls deep, is a stop
+    pub fn stops_at(&self, name: Option<&str>, depth: usize) -> Option<Lreason> {
+        match self.mode.get() {
+            Mode::Step => return Some(Lreason::Step),
+            Mode::Next(next) if depth <= next => return Some(Lreason::Step),
+            _ => {}
+        }
+        let name = name?;
+        if self.breakpoints.borrow().iter().any(|b| b == name) {
+            Some(Lreason::Breakpoint(name.to_string()))
+        } else {
+            None
+        }
+    }
+
+    /// Hands a stop to the handler, and goes on as it says. Gives back false when
+    /// the evaluation has to be given up on. Without a handler, or while it's
+    /// handling another stop, the evaluation just goes on
+    pub fn stop(&self, stop: &Lstop) -> bool {
+        // taken out, so what the handler evaluates doesn't stop again
+        let mut handler = match self.handler.borrow_mut().take() {
+            Some(handler) => handler,
+            None => return true,
+        };
+        let resume = handler(stop);
+        *self.handler.borrow_mut() = Some(handler);
+        self.mode.set(match resume {
+            Lresume::Step => Mode::Step,
+            Lresume::Next => Mode::Next(stop.calls.len()),
+            Lresume::Continue | Lresume::Abort => Mode::Run,
+        });
+        resume != Lresume::Abort
+    }
+}
+
+impl fmt::Debug for Ldebugger {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Ldebugger {:?}", self.breakpoints.borrow())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eval::eval_rispreter;
+
+    /// Evaluates an input stopping with a handler that answers every stop in turn,
+    /// giving back the stops it saw
+    fn debug(env: &Rc<Lenv>, input: &str, mut resumes: Vec<Lresume>) -> (Lval, Vec<String>) {
+        let stops = Rc::new(RefCell::new(Vec::new()));
+        let seen = Rc::clone(&stops);
+        env.context().debugger().set_handler(Box::new(move |stop| {
+            seen.borrow_mut().push(stop.to_string());
+            if resumes.is_empty() {
+                Lresume::Continue
+            } else {
+                resumes.remove(0)
+            }
+        }));
+        let res = eval_rispreter(env, input);
+        let stops = stops.borrow().clone();
+        (res, stops)
+    }
+
+    #[test]
+    fn debugger_breakpoints_and_stepping() {
+        let env = Lenv::new();
+        eval_rispreter(&env, "(fun {dec n} {- n 1})");
+        eval_rispreter(&env, "(fun {twice n} {* 2 (dec n)})");
+        env.context().debugger().add_breakpoint("dec");
+
+        let (res, stops) = debug(&env, "(twice 5)", vec![Lresume::Step, Lresume::Next]);
+        assert_eq!(8f64, res);
+        assert_eq!(
+            vec![
+                "breakpoint on dec: (dec 5)\n  calls: twice\n  n = 5",
+                "step: (- 5 1)\n  calls: twice > dec\n  n = 5",
+                "step: (* 2 4)\n  calls: twice\n  n = 5",
+            ],
+            stops
+        );
+
+        env.context().debugger().clear_breakpoints();
+        let (res, stops) = debug(&env, "(+ 1 (do (break 10) 2))", vec![Lresume::Abort]);
+        assert_eq!("error", res.type_name());
+        assert_eq!(vec!["break: (break 10)\n  calls: (top level)"], stops);
+        assert_eq!(3f64, debug(&env, "(+ 1 (do (break) 2))", vec![]).0);
+    }
+}
@@ -1,8 +1,9 @@
 use crate::lval::lval_builtin::*;
 use crate::lval::lval_env::Lenv;
 use crate::lval::lval_error::Lerror;
+use crate::lval::lval_key::Lkey;
 use crate::lval::lval_lambda::LLambda;
-use std::collections::VecDeque;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
 use std::fmt;
 use std::rc::Rc;
 #[allow(non_camel_case_types)] // please
@@ -17,24 +18,30 @@ pub enum LvalType {
     LVAL_CHAR(char),
     LVAL_BOOL(bool),
     LVAL_NUM_VEC(Vec<f64>),
+    LVAL_MAP(BTreeMap<Lkey, Lval>),
+    LVAL_SET(BTreeSet<Lkey>),
     LVAL_SEXPR,
     LVAL_QEXPR,
 }
 
+/// Values print as the source that reads back to them. Errors are the exception,
+/// they can't be written in source
 impl fmt::Display for LvalType {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             LvalType::LVAL_ERR(err) => write!(f, "error: \"{}\"", err),
             LvalType::LVAL_NUM(num) => write!(f, "{}", num),
             LvalType::LVAL_SYM(sym) => write!(f, "{}", sym),
-            LvalType::LVAL_STRING(str) => write!(f, "\"{}\"", str),
-            LvalType::LVAL_CHAR(ch) => write!(f, "'{}'", ch),
-            LvalType::LVAL_FUN(fun) => write!(f, "{:?}", fun),
-            LvalType::LVAL_LAMBDA(lambda) => {
-                writeln!(f, "body: {:?}", lambda.body.cell)?;
-                writeln!(f, "formals: {:?}", lambda.formals.cell)
+            LvalType::LVAL_STRING(str) => write!(f, "\"{}\"", escape(str, '"')),
+            LvalType::LVAL_CHAR(ch) => write!(f, "'{}'", escape(&ch.to_string(), '\'')),
+            LvalType::LVAL_FUN(fun) => write!(f, "{}", fun.name()),
+            LvalType::LVAL_LAMBDA(lambda) => fmt_lambda(f, lambda),
+            LvalType::LVAL_NUM_VEC(v) => {
+                let nums: Vec<String> = v.iter().map(|n| n.to_string()).collect();
+                write!(f, "#[{}]", nums.join(" "))
             }
-            LvalType::LVAL_NUM_VEC(v) => write!(f, "#{:?}", v),
+            LvalType::LVAL_MAP(m) => fmt_map(f, m),
+            LvalType::LVAL_SET(s) => fmt_set(f, s),
             LvalType::LVAL_SEXPR => write!(f, "()"),
             LvalType::LVAL_QEXPR => write!(f, "{{}}"),
             LvalType::LVAL_BOOL(b) => write!(f, "{}", b),
@@ -44,29 +51,99 @@ impl fmt::Display for LvalType {
 
 impl fmt::Debug for LvalType {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self {
-            LvalType::LVAL_ERR(err) => write!(f, "error: \"{}\"", err),
-            LvalType::LVAL_NUM(num) => write!(f, "{}", num),
-            LvalType::LVAL_SYM(sym) => write!(f, "{}", sym),
-            LvalType::LVAL_STRING(str) => write!(f, "\"{}\"", str),
-            LvalType::LVAL_CHAR(ch) => write!(f, "'{}'", ch),
-            LvalType::LVAL_FUN(fun) => write!(f, "{:?}", fun),
-            LvalType::LVAL_LAMBDA(lambda) => {
-                writeln!(f, "body: {:?}", lambda.body.cell)?;
-                writeln!(f, "formals: {:?}", lambda.formals.cell)
+        write!(f, "{}", self)
+    }
+}
+
+/// Escapes the content of a string or char literal, so it reads back the same
+pub(crate) fn escape(s: &str, quote: char) -> String {
+    let mut escaped = String::new();
+    for c in s.chars() {
+        match c {
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            '\r' => escaped.push_str("\\r"),
+            '\0' => escaped.push_str("\\0"),
+            '\\' => escaped.push_str("\\\\"),
+            c if c == quote => {
+                escaped.push('\\');
+                escaped.push(c);
             }
-            LvalType::LVAL_NUM_VEC(v) => write!(f, "{:?}", v),
-            LvalType::LVAL_SEXPR => write!(f, "()"),
-            LvalType::LVAL_QEXPR => write!(f, "{{}}"),
-            LvalType::LVAL_BOOL(b) => write!(f, "{}", b),
+            c if c.is_control() => escaped.extend(c.escape_unicode()),
+            c => escaped.push(c),
         }
     }
+    escaped
 }
 
-#[derive(PartialEq, Clone)]
+/// A partially applied lambda prints as the lambda applied to the arguments it
+/// was given, so they are read back with it
+fn fmt_lambda(f: &mut fmt::Formatter, lambda: &LLambda) -> fmt::Result {
+    if lambda.bound.is_empty() {
+        return write!(f, "(\\ {} {})", lambda.formals, lambda.body);
+    }
+    let mut formals = Lval::lval_qexpr();
+    for name in &lambda.bound {
+        formals.add_cell(Lval::lval_sym(name.to_string()));
+    }
+    for formal in &lambda.formals.cell {
+        formals.add_cell((**formal).clone());
+    }
+    write!(f, "((\\ {} {})", formals, lambda.body)?;
+    for name in &lambda.bound {
+        if let Some(val) = lambda.local_lenv.local(name) {
+            write!(f, " {}", val)?;
+        }
+    }
+    write!(f, ")")
+}
+
+fn fmt_map(f: &mut fmt::Formatter, m: &BTreeMap<Lkey, Lval>) -> fmt::Result {
+    write!(f, "#{{")?;
+    for (i, (k, v)) in m.iter().enumerate() {
+        if i == 0 {
+            write!(f, "{} {}", k, v)?;
+        } else {
+            write!(f, " {} {}", k, v)?;
+        }
+    }
+    write!(f, "}}")
+}
+
+fn fmt_set(f: &mut fmt::Formatter, s: &BTreeSet<Lkey>) -> fmt::Result {
+    write!(f, "#s{{")?;
+    for (i, v) in s.iter().enumerate() {
+        if i == 0 {
+            write!(f, "{}", v)?;
+        } else {
+            write!(f, " {}", v)?;
+        }
+    }
+    write!(f, "}}")
+}
+
+/// Where a value was read from: a source the coverage knows, by its number, and
+/// the byte offsets of the value in it
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Lspan {
+    pub source: usize,
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Clone)]
 pub struct Lval {
     pub ltype: LvalType,
     pub cell: VecDeque<Box<Lval>>,
+    /// only read while recording the coverage
+    pub span: Option<Lspan>,
+}
+
+/// Values are the same wherever they were read from
+impl PartialEq for Lval {
+    fn eq(&self, other: &Lval) -> bool {
+        self.ltype == other.ltype && self.cell == other.cell
+    }
 }
 
 impl Lval {
@@ -74,6 +151,7 @@ impl Lval {
         Lval {
             ltype: LvalType::LVAL_NUM(num),
             cell: VecDeque::new(),
+            span: None,
         }
     }
 
@@ -81,6 +159,7 @@ impl Lval {
         Lval {
             ltype: LvalType::LVAL_ERR(err),
             cell: VecDeque::new(),
+            span: None,
         }
     }
 
@@ -88,6 +167,7 @@ impl Lval {
         Lval {
             ltype: LvalType::LVAL_SYM(sym),
             cell: VecDeque::new(),
+            span: None,
         }
     }
 
@@ -95,6 +175,7 @@ impl Lval {
         Lval {
             ltype: LvalType::LVAL_STRING(str),
             cell: VecDeque::new(),
+            span: None,
         }
     }
 
@@ -102,6 +183,7 @@ impl Lval {
         Lval {
             ltype: LvalType::LVAL_CHAR(ch),
             cell: VecDeque::new(),
+            span: None,
         }
     }
 
@@ -109,6 +191,23 @@ impl Lval {
         Lval {
             ltype: LvalType::LVAL_NUM_VEC(v),
             cell: VecDeque::new(),
+            span: None,
+        }
+    }
+
+    pub fn lval_map(m: BTreeMap<Lkey, Lval>) -> Lval {
+        Lval {
+            ltype: LvalType::LVAL_MAP(m),
+            cell: VecDeque::new(),
+            span: None,
+        }
+    }
+
+    pub fn lval_set(s: BTreeSet<Lkey>) -> Lval {
+        Lval {
+            ltype: LvalType::LVAL_SET(s),
+            cell: VecDeque::new(),
+            span: None,
         }
     }
 
@@ -116,6 +215,7 @@ impl Lval {
         Lval {
             ltype: LvalType::LVAL_SEXPR,
             cell: VecDeque::new(),
+            span: None,
         }
     }
 
@@ -123,6 +223,7 @@ impl Lval {
         Lval {
             ltype: LvalType::LVAL_QEXPR,
             cell: VecDeque::new(),
+            span: None,
         }
     }
 
@@ -130,6 +231,7 @@ impl Lval {
         Lval {
             ltype: LvalType::LVAL_BOOL(b),
             cell: VecDeque::new(),
+            span: None,
         }
     }
 
@@ -137,6 +239,7 @@ impl Lval {
         Lval {
             ltype: LvalType::LVAL_FUN(func),
             cell: VecDeque::new(),
+            span: None,
         }
     }
 
@@ -144,6 +247,7 @@ impl Lval {
         Lval {
             ltype: LvalType::LVAL_LAMBDA(LLambda::new(formals, body)),
             cell: VecDeque::new(),
+            span: None,
         }
     }
 
@@ -151,6 +255,36 @@ impl Lval {
         Lval {
             ltype: LvalType::LVAL_LAMBDA(LLambda::llambda_copy(env, formals, body)),
             cell: VecDeque::new(),
+            span: None,
+        }
+    }
+
+    /// Name of the type of a value, as the REPL shows it
+    pub fn type_name(&self) -> &'static str {
+        match &self.ltype {
+            LvalType::LVAL_ERR(_) => "error",
+            LvalType::LVAL_NUM(_) => "number",
+            LvalType::LVAL_SYM(_) => "symbol",
+            LvalType::LVAL_FUN(_) => "builtin",
+            LvalType::LVAL_LAMBDA(_) => "lambda",
+            LvalType::LVAL_STRING(_) => "string",
+            LvalType::LVAL_CHAR(_) => "char",
+            LvalType::LVAL_BOOL(_) => "bool",
+            LvalType::LVAL_NUM_VEC(_) => "vector",
+            LvalType::LVAL_MAP(_) => "map",
+            LvalType::LVAL_SET(_) => "set",
+            LvalType::LVAL_SEXPR => "s-expression",
+            LvalType::LVAL_QEXPR => "q-expression",
+        }
+    }
+
+    /// The form `print` outputs: strings and chars as their plain content,
+    /// anything else as it reads
+    pub fn to_print_string(&self) -> String {
+        match &self.ltype {
+            LvalType::LVAL_STRING(str) => str.to_string(),
+            LvalType::LVAL_CHAR(ch) => ch.to_string(),
+            _ => self.to_string(),
         }
     }
 
@@ -194,6 +328,7 @@ impl Lval {
         let v = Lval {
             ltype: LvalType::LVAL_QEXPR,
             cell: buf,
+            span: None,
         };
         (self.clone(), v)
     }
@@ -243,31 +378,7 @@ impl fmt::Display for Lval {
 
 impl fmt::Debug for Lval {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self.ltype {
-            LvalType::LVAL_SEXPR => {
-                if self.cell.is_empty() {
-                    write!(f, "{}", self.ltype)
-                } else {
-                    write!(f, "(")?;
-                    for elem in self.cell.iter() {
-                        write!(f, " {} ", elem)?;
-                    }
-                    write!(f, ")")
-                }
-            }
-            LvalType::LVAL_QEXPR => {
-                if self.cell.is_empty() {
-                    write!(f, "{}", self.ltype)
-                } else {
-                    write!(f, "{{")?;
-                    for elem in self.cell.iter() {
-                        write!(f, " {} ", elem)?;
-                    }
-                    write!(f, "}}")
-                }
-            }
-            _ => write!(f, "{}", self.ltype),
-        }
+        write!(f, "{}", self)
     }
 }
 
@@ -360,4 +471,145 @@ pub mod tests {
     //     assert_eq!(Some(&Box::new(Lval::lval_string("local_sym".to_string()))), lenv.get(&String::from("x")));
     // }
 
+    #[test]
+    fn string_and_char_display_reads_back() {
+        use crate::eval::eval_rispreter;
+
+        let env = Lenv::new();
+        let lval = eval_rispreter(&env, r#"{"say \"hi\"\n\t\\" '\'' '\n' "\u{7}"}"#);
+        let printed = lval.to_string();
+        assert_eq!(r#"{"say \"hi\"\n\t\\" '\'' '\n' "\u{7}"}"#, printed);
+        assert_eq!(lval, eval_rispreter(&env, &printed));
+    }
+
+    /// Random rispreter source of a value, from a xorshift generator so a failure
+    /// can be found again by its seed
+    struct SourceGen(u64);
+
+    impl SourceGen {
+        fn below(&mut self, n: u64) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0 % n
+        }
+
+        fn pick<'a>(&mut self, choices: &[&'a str]) -> &'a str {
+            choices[self.below(choices.len() as u64) as usize]
+        }
+
+        fn number(&mut self) -> String {
+            let sign = self.pick(&["", "-"]);
+            match self.below(2) {
+                0 => format!("{}{}", sign, self.below(100_000)),
+                _ => format!("{}{}.{}", sign, self.below(1000), self.below(1000)),
+            }
+        }
+
+        fn many(&mut self, depth: u32, open: &str, close: &str, each: u32) -> String {
+            let items: Vec<String> = (0..self.below(4) * u64::from(each))
+                .map(|_| self.source(depth - 1))
+                .collect();
+            format!("{}{}{}", open, items.join(" "), close)
+        }
+
+        fn source(&mut self, depth: u32) -> String {
+            match self.below(if depth == 0 { 6 } else { 11 }) {
+                0 => self.number(),
+                1 => {
+                    let parts = (0..self.below(5))
+                        .map(|_| {
+                            self.pick(&[
+                                "a", " ", "\\\"", "\\\\", "\\n", "\\t", "é", "🦀", "{", ")", "'",
+                                "\\u{7}",
+                            ])
+                        })
+                        .collect::<String>();
+                    format!("\"{}\"", parts)
+                }
+                2 => self
+                    .pick(&["'a'", "'\\''", "'\\n'", "'\"'", "'🦀'", "'\\\\'", "'{'"])
+                    .to_string(),
+                3 => self
+                    .pick(&["true", "false", "r\"raw \\ text\"", "r#\"say \"hi\"\"#"])
+                    .to_string(),
+                4 => self
+                    .pick(&[
+                        "x",
+                        "foo-bar",
+                        "list?",
+                        "a/b",
+                        "head",
+                        "+",
+                        "==",
+                        "%",
+                        "\\",
+                        "str->chars",
+                    ])
+                    .to_string(),
+                5 => {
+                    let nums: Vec<String> = (0..self.below(4)).map(|_| self.number()).collect();
+                    format!("#[{}]", nums.join(" "))
+                }
+                6 | 7 => self.many(depth, "{", "}", 1),
+                8 => self.many(depth, "(", ")", 1),
+                9 => self.many(depth, "#{", "}", 2),
+                _ => self.many(depth, "#s{", "}", 1),
+            }
+        }
+    }
+
+    #[test]
+    fn display_round_trips_through_the_reader() {
+        use crate::read::read;
+        use rispreter_parser::complete_parser::parse_risp;
+
+        for seed in 1..500u64 {
+            let source = SourceGen(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15)).source(4);
+            let lval = read(parse_risp(&source));
+            assert!(
+                lval.type_name() != "error",
+                "seed {}: {} doesn't read",
+                seed,
+                source
+            );
+            let printed = lval.to_string();
+            let reread = read(parse_risp(&printed));
+            assert_eq!(
+                lval, reread,
+                "seed {}: {} printed as {}",
+                seed, source, printed
+            );
+            assert_eq!(printed, reread.to_string(), "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn lambdas_and_builtins_print_as_source() {
+        use crate::eval::eval_rispreter;
+
+        let env = Lenv::new();
+        for source in &[
+            "(\\ {x & xs} {+ x (head xs)})",
+            "(\\ {} {1})",
+            "%",
+            "#[1 -2.5 0.125]",
+        ] {
+            let lval = eval_rispreter(&env, source);
+            assert_eq!(lval, eval_rispreter(&env, &lval.to_string()));
+        }
+
+        // what a partially applied lambda was given is read back with it
+        let partial = eval_rispreter(&env, "((\\ {x y} {== x y}) 1)").to_string();
+        assert_eq!("((\\ {x y} {== x y}) 1)", partial);
+        let call = |arg: &str| eval_rispreter(&env, &format!("({} {})", partial, arg));
+        assert_eq!(Lval::lval_bool(true), call("1"));
+        assert_eq!(Lval::lval_bool(false), call("2"));
+        let partial = eval_rispreter(&env, "(((\\ {x y z} {- x (* y z)}) 1) \"a\")");
+        assert_eq!("((\\ {x y z} {- x (* y z)}) 1 \"a\")", partial.to_string());
+        assert_eq!(
+            "#[1 -2.5 0.125]",
+            format!("{:?}", eval_rispreter(&env, "#[1 -2.5 0.125]"))
+        );
+    }
 }
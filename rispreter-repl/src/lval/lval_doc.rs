@@ -0,0 +1,111 @@
+/// Name, usage and a one line summary of every builtin function
+pub const BUILTIN_DOCS: &[(&str, &str, &str)] = &[
+    ("+", "(+ n ...)", "Adds n numbers"),
+    ("-", "(- n ...)", "Subtracts the following numbers from the first, negates a single one"),
+    ("*", "(* n ...)", "Multiplies n numbers"),
+    ("/", "(/ n ...)", "Divides the first number by the following ones"),
+    ("%", "(% n ...)", "Remainder of dividing the first number by the following ones"),
+    ("==", "(== a b)", "Checks if two values are equal"),
+    ("!=", "(!= a b)", "Checks if two values are different"),
+    (">", "(> a b)", "Checks if a number is greater than another"),
+    ("<", "(< a b)", "Checks if a number is less than another"),
+    (">=", "(>= a b)", "Checks if a number is greater than or equal to another"),
+    ("<=", "(<= a b)", "Checks if a number is less than or equal to another"),
+    ("not", "(not b)", "Negates a boolean"),
+    ("and", "(and a b)", "Logical and of two booleans"),
+    ("or", "(or a b)", "Logical or of two booleans"),
+    ("xor", "(xor a b)", "Logical exclusive or of two booleans"),
+    ("if", "(if cond {then} {else})", "Evaluates one of two Q-expressions depending on a condition"),
+    ("select", "(select {cond value} ...)", "Gives back the value of the first clause whose condition is true"),
+    ("match", "(match value {pattern body} ...)", "Evaluates the body of the first pattern that matches a value, guards go between pattern and body"),
+    ("def", "(def {sym ...} value ...)", "Binds symbols in the global env"),
+    ("=", "(= {sym ...} value ...)", "Binds symbols in the local env"),
+    ("\\", "(\\ {formals} {body})", "Creates a lambda, `& rest` in the formals takes the remaining arguments"),
+    ("fun", "(fun {name formals ...} {body})", "Defines a named function in the global env"),
+    ("do", "(do expr ...)", "Evaluates expressions in order, giving back the last value"),
+    ("let", "(let {body})", "Evaluates a body in its own local env"),
+    ("eval", "(eval {expr})", "Evaluates a Q-expression as an S-expression"),
+    ("list", "(list value ...)", "Puts values in a Q-expression"),
+    ("head", "(head {list})", "Q-expression with the first element of a Q-expression"),
+    ("tail", "(tail {list})", "Q-expression without the first element"),
+    ("join", "(join {list} ...)", "Joins Q-expressions one after the other"),
+    ("cons", "(cons value {list})", "Puts a value in front of a Q-expression"),
+    ("fst", "(fst {list})", "First element of a Q-expression"),
+    ("snd", "(snd {list})", "Second element of a Q-expression"),
+    ("trd", "(trd {list})", "Third element of a Q-expression"),
+    ("nth", "(nth n {list})", "Element at index n of a Q-expression"),
+    ("last", "(last {list})", "Last element of a Q-expression"),
+    ("take", "(take n {list})", "The first n elements of a Q-expression"),
+    ("drop", "(drop n {list})", "A Q-expression without its first n elements"),
+    ("split", "(split n {list})", "Splits a Q-expression in two at index n"),
+    ("elemen", "(elemen value {list})", "Checks if a value is in a Q-expression"),
+    ("map", "(map f {list})", "Applies a function to every element of a Q-expression or set"),
+    ("filter", "(filter f {list})", "Keeps the elements of a Q-expression or set a predicate holds for"),
+    ("unpack", "(unpack f {list})", "Calls a function with the elements of a Q-expression as arguments"),
+    ("pack", "(pack f value ...)", "Calls a function with its arguments in a single Q-expression"),
+    ("curry", "(curry f {list})", "Calls a function with the elements of a Q-expression as arguments"),
+    ("uncurry", "(uncurry f value ...)", "Calls a function with its arguments in a single Q-expression"),
+    ("get", "(get n #[vec])", "Element at index n of a number vector"),
+    ("assoc", "(assoc map key value ...)", "A map with key value pairs added"),
+    ("dissoc", "(dissoc map key ...)", "A map without some keys"),
+    ("get-in", "(get-in map {key ...} default)", "Looks a path of keys up in nested maps"),
+    ("keys", "(keys map)", "Q-expression with the keys of a map"),
+    ("vals", "(vals map)", "Q-expression with the values of a map"),
+    ("contains?", "(contains? map-or-set key)", "Checks if a map has a key, or a set an element"),
+    ("merge", "(merge map ...)", "Merges maps, the rightmost keys win"),
+    ("union", "(union set ...)", "Union of sets"),
+    ("intersection", "(intersection set ...)", "Intersection of sets"),
+    ("difference", "(difference set ...)", "Elements of the first set not in the following ones"),
+    ("subset?", "(subset? a b)", "Checks if every element of a set is in another"),
+    ("set->list", "(set->list set)", "Q-expression with the elements of a set"),
+    ("list->set", "(list->set {list})", "Set with the elements of a Q-expression"),
+    ("str-concat", "(str-concat s ...)", "Concatenates strings"),
+    ("str-len", "(str-len s)", "Length of a string, in characters"),
+    ("substr", "(substr s start end)", "Substring between two character indexes, the end is optional"),
+    ("str-split", "(str-split s sep)", "Splits a string by a separator, in characters by \"\", or by whitespace without one"),
+    ("str-join", "(str-join sep {list})", "Joins a Q-expression of strings with a separator"),
+    ("str-trim", "(str-trim s)", "Removes the leading and trailing whitespace"),
+    ("str-upper", "(str-upper s)", "Uppercase version of a string"),
+    ("str-lower", "(str-lower s)", "Lowercase version of a string"),
+    ("str-index-of", "(str-index-of s sub)", "Character index of a substring, -1 if not found"),
+    ("str-replace", "(str-replace s from to)", "Replaces every occurrence of a substring"),
+    ("starts-with?", "(starts-with? s prefix)", "Checks if a string starts with a prefix"),
+    ("ends-with?", "(ends-with? s suffix)", "Checks if a string ends with a suffix"),
+    ("str->chars", "(str->chars s)", "Q-expression with the characters of a string"),
+    ("chars->str", "(chars->str {chars})", "String from a Q-expression of characters"),
+    ("print", "(print value ...)", "Writes values separated by spaces"),
+    ("println", "(println value ...)", "Writes values separated by spaces and a new line"),
+    ("show", "(show value)", "The re-readable form of a value, as a string"),
+    ("format", "(format fmt value ...)", "Formats values into the `{}` placeholders of a string"),
+    ("read-file", "(read-file path)", "Reads a whole file into a string"),
+    ("read-lines", "(read-lines path)", "Reads a file as a Q-expression of its lines"),
+    ("write-file", "(write-file path s)", "Writes a string to a file, replacing it"),
+    ("append-file", "(append-file path s)", "Writes a string at the end of a file"),
+    ("file-exists?", "(file-exists? path)", "Checks if a file or directory exists"),
+    ("list-dir", "(list-dir path)", "Names of the entries of a directory"),
+    ("make-dir", "(make-dir path)", "Creates a directory and its missing parents"),
+    ("remove-file", "(remove-file path)", "Removes a file"),
+    ("getenv", "(getenv name)", "Value of an environment variable, nil if unset"),
+    ("setenv", "(setenv name value)", "Sets an environment variable"),
+    ("exit", "(exit code)", "Stops the program with a status code"),
+    ("run", "(run program arg ...)", "Runs a program, giving back a map with its status, stdout and stderr"),
+    ("load", "(load path)", "Evaluates another file, relative to the current one"),
+    ("import", "(import path {name ...})", "Binds what a module defines under its name, like `math/sqrt`"),
+    ("out", "(out n)", "Result of the input number n of the REPL"),
+    ("break", "(break expr ...)", "Stops in the debugger, showing the values of its arguments"),
+    ("trace", "(trace f ...)", "Prints the calls of functions, with their arguments and what they give back"),
+    ("untrace", "(untrace f ...)", "Stops printing the calls of functions"),
+    ("profile", "(profile {expr})", "Evaluates a Q-expression, printing the calls, time and allocations of every function"),
+    ("deftest", "(deftest name {body})", "Defines a test, run by `rispreter test` in a session of its own"),
+    ("assert", "(assert cond msg)", "Fails unless a condition is true, with an optional message"),
+    ("assert-eq", "(assert-eq expected actual)", "Fails unless two values are equal, showing where they differ"),
+    ("assert-error", "(assert-error {expr})", "Evaluates a Q-expression, failing unless it gives back an error, whose message it gives back"),
+];
+
+/// Usage and summary of a builtin function
+pub fn builtin_doc(name: &str) -> Option<(&'static str, &'static str)> {
+    BUILTIN_DOCS
+        .iter()
+        .find(|(builtin, _, _)| *builtin == name)
+        .map(|(_, usage, summary)| (*usage, *summary))
+}
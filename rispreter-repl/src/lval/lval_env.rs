@@ -1,3 +1,4 @@
+use crate::lval::lval_context::Lcontext;
 use crate::lval::lval_def::Lval;
 use crate::lval::lval_error::Lerror;
 use fnv::FnvHashMap;
@@ -9,22 +10,67 @@ pub struct Lenv {
     parent: Option<Parent>,
     vals: RefCell<FnvHashMap<String, Lval>>,
     deepness: RefCell<usize>,
+    context: Context,
+    /// the name of the lambda this is the env of
+    name: RefCell<Option<String>>,
 }
 
 impl Lenv {
     pub fn new() -> Rc<Lenv> {
-        Lenv::init(None)
+        Lenv::init(None, Context::Strong(Lcontext::new()))
+    }
+
+    /// A root env sharing an existing interpreter context
+    pub fn with_context(context: Rc<Lcontext>) -> Rc<Lenv> {
+        Lenv::init(None, Context::Strong(context))
+    }
+
+    /// The root env of a module. The context keeps the module through the lambdas
+    /// it exports, so the module and the envs made from it only refer back to the
+    /// context weakly
+    pub fn module(context: &Rc<Lcontext>) -> Rc<Lenv> {
+        Lenv::init(None, Context::Weak(Rc::downgrade(context)))
+    }
+
+    /// The env of a call of a lambda from a module: a child of the module, in the
+    /// session of the caller, which the module doesn't keep and may outlive
+    pub fn from_module(module: &Rc<Lenv>, caller: &Rc<Lenv>) -> Rc<Lenv> {
+        Lenv::init(
+            Some(Parent::Strong(Rc::clone(module))),
+            caller.context.clone(),
+        )
     }
 
     pub fn from(parent: &Rc<Lenv>) -> Rc<Lenv> {
-        Lenv::init(Some(Parent::Strong(Rc::clone(parent))))
+        Lenv::init(
+            Some(Parent::Strong(Rc::clone(parent))),
+            parent.context.clone(),
+        )
     }
 
     pub fn from_weak(parent: &Rc<Lenv>) -> Rc<Lenv> {
         if parent.has_weak() {
             return Lenv::from(parent);
         }
-        Lenv::init(Some(Parent::Weak(Rc::downgrade(parent))))
+        Lenv::init(
+            Some(Parent::Weak(Rc::downgrade(parent))),
+            parent.context.clone(),
+        )
+    }
+
+    pub fn context(&self) -> Rc<Lcontext> {
+        match self.context {
+            Context::Strong(ref context) => Rc::clone(context),
+            Context::Weak(ref context) => match context.upgrade() {
+                Some(context) => context,
+                None => panic!("session of the module went out of scope"),
+            },
+        }
+    }
+
+    /// Counts an evaluation step in the context of the session
+    pub fn step(&self) {
+        self.context().step();
     }
 
     pub fn put(&self, id: String, val: Lval) -> Result<(), String> {
@@ -41,6 +87,36 @@ impl Lenv {
         }
     }
 
+    /// The name of the lambda this is the env of, if it was bound to one
+    pub fn name(&self) -> Option<String> {
+        self.name.borrow().clone()
+    }
+
+    /// Names the lambda this is the env of, unless it has a name already
+    pub fn set_name(&self, name: &str) {
+        let mut current = self.name.borrow_mut();
+        if current.is_none() {
+            *current = Some(name.to_string());
+        }
+    }
+
+    /// The values bound right in this env, not in its parents, sorted by name
+    pub fn bindings(&self) -> Vec<(String, Lval)> {
+        let mut bindings: Vec<(String, Lval)> = self
+            .vals
+            .borrow()
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.clone()))
+            .collect();
+        bindings.sort_by(|a, b| a.0.cmp(&b.0));
+        bindings
+    }
+
+    /// The value bound to a name right in this env, not in its parents
+    pub fn local(&self, id: &str) -> Option<Lval> {
+        self.vals.borrow().get(id).cloned()
+    }
+
     pub fn get(&self, id: String) -> Result<Lval, String> {
         //println!("trying to get {}", id);
         let vals = self.vals.borrow();
@@ -91,6 +167,15 @@ impl Lenv {
         Err(format!("ancestor is undefined at depth {}", d))
     }
 
+    /// The env this one was made from, `None` for the global one
+    pub fn parent(&self) -> Option<Rc<Lenv>> {
+        match self.parent {
+            Some(Parent::Strong(ref e)) => Some(Rc::clone(e)),
+            Some(Parent::Weak(ref w)) => w.upgrade(),
+            None => None,
+        }
+    }
+
     pub fn has_weak(&self) -> bool {
         match self.parent {
             Some(ref p) => p.has_weak(),
@@ -100,11 +185,13 @@ impl Lenv {
 }
 
 impl Lenv {
-    fn init(parent: Option<Parent>) -> Rc<Lenv> {
+    fn init(parent: Option<Parent>, context: Context) -> Rc<Lenv> {
         Rc::new(Lenv {
             parent,
             vals: RefCell::new(FnvHashMap::default()),
             deepness: RefCell::new(0),
+            context,
+            name: RefCell::new(None),
         })
     }
 
@@ -163,6 +250,18 @@ impl Lenv {
 //     }
 // }
 
+#[derive(Debug, Clone)]
+enum Context {
+    Strong(Rc<Lcontext>),
+    Weak(Weak<Lcontext>),
+}
+
+impl Default for Context {
+    fn default() -> Context {
+        Context::Strong(Rc::default())
+    }
+}
+
 #[derive(Debug, Clone)]
 enum Parent {
     Strong(Rc<Lenv>),
@@ -51,6 +51,45 @@ pub enum Lerror {
         expect: LvalTypeMeta,
         got: Box<LvalType>,
     },
+    NonExhaustiveMatch {
+        lval: Box<Lval>,
+        patterns: Box<Lval>,
+    },
+    InvalidFormat {
+        fmt: String,
+        msg: String,
+    },
+    IoError {
+        path: String,
+        msg: String,
+    },
+    NotPermitted {
+        op: String,
+    },
+    Exit {
+        code: i32,
+    },
+    ModuleNotFound {
+        name: String,
+        searched: Vec<String>,
+    },
+    CircularImport {
+        chain: Vec<String>,
+    },
+    InvalidImage {
+        msg: String,
+    },
+    Aborted,
+    AssertionFailed {
+        msg: String,
+    },
+    NotEqual {
+        expected: Box<Lval>,
+        actual: Box<Lval>,
+    },
+    NotAKey {
+        lval: Box<Lval>,
+    },
 }
 
 impl std::fmt::Debug for Lerror {
@@ -68,6 +107,18 @@ impl std::fmt::Debug for Lerror {
             Lerror::LambdaWrongGenericError { .. } => write!(f, "LambdaWrongGenericError"),
             Lerror::IncompatibleNumberOfArgs { .. } => write!(f, "IncompatibleNumberOfArgs"),
             Lerror::WrongType { .. } => write!(f, "WrongType"),
+            Lerror::NonExhaustiveMatch { .. } => write!(f, "NonExhaustiveMatch"),
+            Lerror::InvalidFormat { .. } => write!(f, "InvalidFormat"),
+            Lerror::IoError { .. } => write!(f, "IoError"),
+            Lerror::NotPermitted { .. } => write!(f, "NotPermitted"),
+            Lerror::Exit { .. } => write!(f, "Exit"),
+            Lerror::ModuleNotFound { .. } => write!(f, "ModuleNotFound"),
+            Lerror::CircularImport { .. } => write!(f, "CircularImport"),
+            Lerror::InvalidImage { .. } => write!(f, "InvalidImage"),
+            Lerror::Aborted => write!(f, "Aborted"),
+            Lerror::AssertionFailed { .. } => write!(f, "AssertionFailed"),
+            Lerror::NotEqual { .. } => write!(f, "NotEqual"),
+            Lerror::NotAKey { .. } => write!(f, "NotAKey"),
         }
     }
 }
@@ -89,7 +140,24 @@ impl std::fmt::Display for Lerror {
             Lerror::IncompatibleNumberOfArgs{lval_left, expect_left, expect_right, lval_right, got_left, got_right} =>  {
                 write!(f, "Left and Right side operands doesn't match.\n got left: '{}', got right: '{}'.\n expect left: '{}', expect right: '{}'.\n within '{}' at left, and '{}' at right",
                 got_left, got_right, expect_left, expect_right, lval_left, lval_right )},
-            Lerror::WrongType{lval, expect, got} => write!(f, "Wrong type at '{}', got: '{}', expect: '{:?}'", lval, got, expect)
+            Lerror::WrongType{lval, expect, got} => write!(f, "Wrong type at '{}', got: '{}', expect: '{:?}'", lval, got, expect),
+            Lerror::NonExhaustiveMatch{lval, patterns} => write!(f, "No pattern in '{}' matches the value '{}'", patterns, lval),
+            Lerror::InvalidFormat{fmt, msg} => write!(f, "Invalid format string '{}': {}", fmt, msg),
+            Lerror::IoError{path, msg} => write!(f, "I/O error at '{}': {}", path, msg),
+            Lerror::NotPermitted{op} => write!(f, "'{}' is not permitted in this session", op),
+            Lerror::Exit{code} => write!(f, "Exit with status {}", code),
+            Lerror::ModuleNotFound{name, searched} => write!(f, "Module '{}' not found, looked in: {}", name, searched.join(", ")),
+            Lerror::CircularImport{chain} => write!(f, "Circular import: {}", chain.join(" -> ")),
+            Lerror::InvalidImage{msg} => write!(f, "Invalid image: {}", msg),
+            Lerror::Aborted => write!(f, "Evaluation aborted from the debugger"),
+            Lerror::AssertionFailed{msg} => write!(f, "{}", msg),
+            Lerror::NotEqual{expected, actual} => {
+                let (expected, actual) = (expected.to_string(), actual.to_string());
+                // the first character the printed values differ at
+                let at = expected.chars().zip(actual.chars()).take_while(|(e, a)| e == a).count();
+                write!(f, "Values aren't equal\n  expected: {}\n    actual: {}\n            {}^", expected, actual, " ".repeat(at))
+            }
+            Lerror::NotAKey{lval} => write!(f, "'{}' can't be a map key or a set element", lval),
         }
     }
 }
@@ -105,8 +173,14 @@ pub enum LvalTypeMeta {
     LvalChar,
     LvalBool,
     LvalIntVec,
+    LvalMap,
+    LvalSet,
     LvalSexpr,
     LvalQexpr,
+    /// what the builtins taking either a map or a set expect
+    LvalMapOrSet,
+    /// what `map` and `filter` expect
+    LvalQexprOrSet,
 }
 
 impl From<LvalType> for LvalTypeMeta {
@@ -121,6 +195,8 @@ impl From<LvalType> for LvalTypeMeta {
             LvalType::LVAL_CHAR(_) => LvalTypeMeta::LvalChar,
             LvalType::LVAL_BOOL(_) => LvalTypeMeta::LvalBool,
             LvalType::LVAL_NUM_VEC(_) => LvalTypeMeta::LvalIntVec,
+            LvalType::LVAL_MAP(_) => LvalTypeMeta::LvalMap,
+            LvalType::LVAL_SET(_) => LvalTypeMeta::LvalSet,
             LvalType::LVAL_SEXPR => LvalTypeMeta::LvalSexpr,
             LvalType::LVAL_QEXPR => LvalTypeMeta::LvalQexpr,
         }
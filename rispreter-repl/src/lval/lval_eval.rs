@@ -1,10 +1,19 @@
 use crate::lval::lval_builtin;
+use crate::lval::lval_debug::Lstop;
 use crate::lval::lval_def::*;
 use crate::lval::lval_env::Lenv;
 use crate::lval::lval_error::Lerror;
+use crate::lval::lval_lambda::LLambda;
 use std::rc::Rc;
 
 pub fn lval_eval(lenv: &Rc<Lenv>, lval: &mut Lval) -> Lval {
+    lenv.step();
+    // a Q-expression is only evaluated once it's turned into an S-expression
+    if let Some(span) = lval.span {
+        if !matches!(lval.ltype, LvalType::LVAL_QEXPR) {
+            lenv.context().coverage().hit(span);
+        }
+    }
     match &lval.ltype {
         LvalType::LVAL_SYM(sym) => lenv.get(sym.to_string()).unwrap(),
         LvalType::LVAL_SEXPR => lval_eval_sexpr(lenv, lval),
@@ -13,6 +22,12 @@ pub fn lval_eval(lenv: &Rc<Lenv>, lval: &mut Lval) -> Lval {
 }
 
 pub fn lval_eval_sexpr(lenv: &Rc<Lenv>, lval: &mut Lval) -> Lval {
+    // the name the function is called with, gone once the head is evaluated
+    let name = match lval.cell.front().map(|head| &head.ltype) {
+        Some(LvalType::LVAL_SYM(sym)) => Some(sym.to_string()),
+        Some(LvalType::LVAL_FUN(builtin)) => Some(builtin.name().to_string()),
+        _ => None,
+    };
     for i in 0..lval.cell.len() {
         lval.cell[i] = Box::new(lval_eval(lenv, &mut lval.cell[i]));
     }
@@ -27,14 +42,102 @@ pub fn lval_eval_sexpr(lenv: &Rc<Lenv>, lval: &mut Lval) -> Lval {
         return lval.clone();
     }
     if lval.cell.len() == 1 {
+        // `(break)` stops without arguments, other functions are given back
+        if is_break(&lval.cell[0]) {
+            let mut f = lval.lval_pop();
+            return lval_call(lenv, &mut f, lval);
+        }
         return lval_eval(lenv, &mut lval.lval_take(0));
     }
+    if let Some(err) = debug_stop(lenv, lval, name.as_deref()) {
+        return err;
+    }
 
     let mut f = lval.lval_pop();
-    lval_call(lenv, &mut f, lval)
+    let context = lenv.context();
+    let name = name.unwrap_or_else(|| "lambda".to_string());
+    if let LvalType::LVAL_LAMBDA(_) = f.ltype {
+        context.enter_call(name);
+        let res = lval_call(lenv, &mut f, lval);
+        context.leave_call();
+        res
+    } else {
+        lval_call(lenv, &mut f, lval)
+    }
+}
+
+fn is_break(lval: &Lval) -> bool {
+    match &lval.ltype {
+        LvalType::LVAL_FUN(f) => f.name() == "break",
+        _ => false,
+    }
+}
+
+/// Stops in the debugger before a call, with its arguments evaluated, when it's
+/// stepping or there's a breakpoint on the function. Gives back the error to
+/// return when the evaluation is aborted
+fn debug_stop(lenv: &Rc<Lenv>, lval: &Lval, name: Option<&str>) -> Option<Lval> {
+    // `break` stops by itself
+    if is_break(&lval.cell[0]) {
+        return None;
+    }
+    let context = lenv.context();
+    let debugger = context.debugger();
+    let reason = debugger.stops_at(name, context.depth())?;
+    let mut expr = lval.clone();
+    if let Some(name) = name {
+        *expr.cell[0] = Lval::lval_sym(name.to_string());
+    }
+    let stop = Lstop {
+        reason,
+        expr,
+        env: Rc::clone(lenv),
+        calls: context.calls(),
+    };
+    if debugger.stop(&stop) {
+        None
+    } else {
+        Some(Lval::lval_err(Lerror::Aborted))
+    }
+}
+
+/// The name a function goes by in traces and profiles: the one of a builtin, or
+/// the first one a lambda was bound to
+fn function_name(f: &Lval) -> String {
+    match &f.ltype {
+        LvalType::LVAL_FUN(builtin) => builtin.name().to_string(),
+        LvalType::LVAL_LAMBDA(lambda) => lambda.name().unwrap_or_else(|| "lambda".to_string()),
+        _ => f.to_string(),
+    }
 }
 
+/// Calls a function with its evaluated arguments, printing the call when the
+/// function is traced and timing it while profiling, whatever it's called by
 pub fn lval_call(lenv: &Rc<Lenv>, f: &mut Lval, lval: &mut Lval) -> Lval {
+    let context = lenv.context();
+    let traced = context.tracer().is_traced(f);
+    let profiled = context.profiler().is_profiling();
+    if !traced && !profiled {
+        return apply(lenv, f, lval);
+    }
+    let name = function_name(f);
+    if traced {
+        context.tracer().enter(&name, lval);
+    }
+    if profiled {
+        context.profiler().enter(&name);
+    }
+    let res = apply(lenv, f, lval);
+    if profiled {
+        context.profiler().leave();
+    }
+    if traced {
+        context.tracer().leave(&name, &res);
+    }
+    res
+}
+
+fn apply(lenv: &Rc<Lenv>, f: &mut Lval, lval: &mut Lval) -> Lval {
     match f.ltype.clone() {
         // if builtin we return
         LvalType::LVAL_FUN(builtin) => builtin.clone().0(Some(lenv), lval),
@@ -45,7 +148,16 @@ pub fn lval_call(lenv: &Rc<Lenv>, f: &mut Lval, lval: &mut Lval) -> Lval {
             let total = lambda.formals.cell.len();
             // println!("given {}", given);
             // println!("total {}", total);
-            lambda.local_lenv = Lenv::from_weak(&lenv);
+            // a partially applied lambda keeps the arguments it was given before
+            let bound = lambda.local_lenv.bindings();
+            lambda.local_lenv = match &lambda.home {
+                Some(home) => Lenv::from_module(home, lenv),
+                None => Lenv::from_weak(&lenv),
+            };
+            for (sym, val) in bound {
+                lambda.local_lenv.put(sym, val).unwrap();
+            }
+            let mut bound = lambda.bound.to_vec();
             // while arguments still to be processed
             while !lval.cell.is_empty() {
                 // if we ran out of formal arguments to bind
@@ -81,6 +193,7 @@ pub fn lval_call(lenv: &Rc<Lenv>, f: &mut Lval, lval: &mut Lval) -> Lval {
                 // println!("{:?}", val.clone());
                 // bind a copy to the lambda local env
                 lambda.local_lenv.put(sym.to_string(), val).unwrap();
+                bound.push(sym.to_string());
             }
 
             if !lambda.formals.cell.is_empty()
@@ -110,7 +223,15 @@ pub fn lval_call(lenv: &Rc<Lenv>, f: &mut Lval, lval: &mut Lval) -> Lval {
                 )
             } else {
                 // returns a partially bound evalueted lambda
-                Lval::lval_lambda_copy(lambda.local_lenv, *lambda.formals, *lambda.body)
+                let mut partial =
+                    LLambda::llambda_copy(lambda.local_lenv, *lambda.formals, *lambda.body);
+                partial.home = lambda.home;
+                partial.bound = bound.into_boxed_slice();
+                Lval {
+                    ltype: LvalType::LVAL_LAMBDA(partial),
+                    cell: Default::default(),
+                    span: None,
+                }
             }
         }
         e => Lval::lval_err(Lerror::GenericError {
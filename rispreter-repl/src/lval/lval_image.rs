@@ -0,0 +1,481 @@
+use crate::lval::lval_builtin::Lbuiltin;
+use crate::lval::lval_def::{escape, Lval, LvalType};
+use crate::lval::lval_env::Lenv;
+use crate::lval::lval_error::Lerror;
+use crate::lval::lval_key::Lkey;
+use rispreter_parser::complete_parser::parse_risp;
+use rispreter_parser::structure::Risp;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::rc::Rc;
+
+/// Version of the image format, images of another version are not read
+pub const IMAGE_VERSION: u32 = 1;
+
+const MAGIC: &str = "rispreter-image";
+
+/// Writes down the bindings of an env, and of the envs its imported lambdas run in,
+/// so they can be read back in another session. After the version come the envs,
+/// the saved one first, each binding on its own line:
+///
+/// ```text
+/// rispreter-image 1
+/// env 0 2
+/// "inc" lambda qexpr 1 sym "x" qexpr 3 builtin "+" sym "x" num 1 0 -
+/// "xs" qexpr 2 num 1 str "two"
+/// ```
+///
+/// Every value is a tag followed by what it holds. Builtins are written by name,
+/// lambdas with their formals, body, the arguments they were partially applied to
+/// and the number of the env they run in, since those can be shared or refer back
+/// to the lambda. Errors only keep their message
+pub fn save_image(env: &Rc<Lenv>) -> String {
+    let mut writer = ImageWriter {
+        envs: vec![Rc::clone(env)],
+        out: format!("{} {}\n", MAGIC, IMAGE_VERSION),
+    };
+    let mut i = 0;
+    // the envs of lambdas are numbered as they are found, and written after
+    while i < writer.envs.len() {
+        let bindings = writer.envs[i].bindings();
+        writer
+            .out
+            .push_str(&format!("env {} {}\n", i, bindings.len()));
+        for (name, val) in bindings {
+            writer.string(&name);
+            writer.value(&val);
+            writer.out.push('\n');
+        }
+        i += 1;
+    }
+    writer.out
+}
+
+/// Binds what an image holds in an env, over what the env already had. Gives back
+/// an empty S-expression, or the error of an image that can't be read, and then
+/// binds nothing
+pub fn load_image(env: &Rc<Lenv>, image: &str) -> Lval {
+    let mut reader = ImageReader {
+        rest: image,
+        envs: HashMap::new(),
+        env: Rc::clone(env),
+    };
+    reader.envs.insert(0, Rc::clone(env));
+    match reader.image() {
+        Ok(()) => Lval::lval_sexpr(),
+        Err(msg) => Lval::lval_err(Lerror::InvalidImage { msg }),
+    }
+}
+
+struct ImageWriter {
+    envs: Vec<Rc<Lenv>>,
+    out: String,
+}
+
+impl ImageWriter {
+    fn value(&mut self, lval: &Lval) {
+        match &lval.ltype {
+            LvalType::LVAL_NUM(n) => self.word("num", &n.to_string()),
+            LvalType::LVAL_SYM(sym) => {
+                self.out.push_str("sym ");
+                self.string(sym);
+            }
+            LvalType::LVAL_STRING(str) => {
+                self.out.push_str("str ");
+                self.string(str);
+            }
+            LvalType::LVAL_CHAR(ch) => {
+                self.out.push_str("char ");
+                self.string(&ch.to_string());
+            }
+            LvalType::LVAL_BOOL(b) => self.word("bool", &b.to_string()),
+            LvalType::LVAL_FUN(builtin) => {
+                self.out.push_str("builtin ");
+                self.string(builtin.name());
+            }
+            LvalType::LVAL_ERR(err) => {
+                self.out.push_str("err ");
+                match err {
+                    Lerror::GenericError { msg } => self.string(msg),
+                    err => self.string(&err.to_string()),
+                }
+            }
+            LvalType::LVAL_NUM_VEC(v) => {
+                self.word("vec", &v.len().to_string());
+                for n in v {
+                    self.word("", &n.to_string());
+                }
+            }
+            LvalType::LVAL_MAP(m) => {
+                self.word("map", &m.len().to_string());
+                for (k, v) in m {
+                    self.value(&k.to_lval());
+                    self.value(v);
+                }
+            }
+            LvalType::LVAL_SET(s) => {
+                self.word("set", &s.len().to_string());
+                for v in s {
+                    self.value(&v.to_lval());
+                }
+            }
+            LvalType::LVAL_SEXPR | LvalType::LVAL_QEXPR => {
+                let tag = match lval.ltype {
+                    LvalType::LVAL_SEXPR => "sexpr",
+                    _ => "qexpr",
+                };
+                self.word(tag, &lval.cell.len().to_string());
+                for v in &lval.cell {
+                    self.value(v);
+                }
+            }
+            LvalType::LVAL_LAMBDA(lambda) => {
+                self.out.push_str("lambda ");
+                self.value(&lambda.formals);
+                self.value(&lambda.body);
+                let bound: Vec<(&String, Lval)> = lambda
+                    .bound
+                    .iter()
+                    .filter_map(|name| Some((name, lambda.local_lenv.local(name)?)))
+                    .collect();
+                self.word("", &bound.len().to_string());
+                for (name, val) in bound {
+                    self.string(name);
+                    self.value(&val);
+                }
+                match &lambda.home {
+                    Some(home) => {
+                        let id = self.env_id(home);
+                        self.word("", &id.to_string());
+                    }
+                    None => self.word("", "-"),
+                }
+            }
+        }
+    }
+
+    /// Number of an env in the image, giving it the next one if it's new
+    fn env_id(&mut self, env: &Rc<Lenv>) -> usize {
+        match self.envs.iter().position(|e| Rc::ptr_eq(e, env)) {
+            Some(id) => id,
+            None => {
+                self.envs.push(Rc::clone(env));
+                self.envs.len() - 1
+            }
+        }
+    }
+
+    fn word(&mut self, tag: &str, word: &str) {
+        if !tag.is_empty() {
+            self.out.push_str(tag);
+            self.out.push(' ');
+        }
+        self.out.push_str(word);
+        self.out.push(' ');
+    }
+
+    fn string(&mut self, str: &str) {
+        self.out.push('"');
+        self.out.push_str(&escape(str, '"'));
+        self.out.push_str("\" ");
+    }
+}
+
+struct ImageReader<'a> {
+    rest: &'a str,
+    envs: HashMap<usize, Rc<Lenv>>,
+    env: Rc<Lenv>,
+}
+
+impl<'a> ImageReader<'a> {
+    fn image(&mut self) -> Result<(), String> {
+        let magic = self.word()?;
+        let version = self.word()?;
+        if magic != MAGIC {
+            return Err("not a rispreter image".to_string());
+        }
+        if version != IMAGE_VERSION.to_string() {
+            return Err(format!(
+                "version {} can't be read, only version {}",
+                version, IMAGE_VERSION
+            ));
+        }
+        // the env the image is loaded in is only bound to once all of it is read,
+        // the other envs are new ones
+        let mut bindings = Vec::new();
+        while !self.rest.trim_start().is_empty() {
+            let tag = self.word()?;
+            if tag != "env" {
+                return Err(format!("expected an env, found '{}'", tag));
+            }
+            let env = self.env()?;
+            for _ in 0..self.count()? {
+                let name = self.string()?;
+                let val = self.value()?;
+                if Rc::ptr_eq(&env, &self.env) {
+                    bindings.push((name, val));
+                } else {
+                    env.put(name, val).unwrap();
+                }
+            }
+        }
+        for (name, val) in bindings {
+            self.env.put(name, val).unwrap();
+        }
+        Ok(())
+    }
+
+    /// A map key or a set element
+    fn key(&mut self) -> Result<Lkey, String> {
+        let lval = self.value()?;
+        Lkey::from_lval(&lval).map_err(|_| format!("'{}' can't be a key", lval))
+    }
+
+    fn value(&mut self) -> Result<Lval, String> {
+        let tag = self.word()?;
+        Ok(match tag {
+            "num" => Lval::lval_num(self.number()?),
+            "sym" => Lval::lval_sym(self.string()?),
+            "str" => Lval::lval_string(self.string()?),
+            "char" => {
+                let str = self.string()?;
+                let mut chars = str.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(ch), None) => Lval::lval_char(ch),
+                    _ => return Err(format!("'{}' isn't a char", str)),
+                }
+            }
+            "bool" => match self.word()? {
+                "true" => Lval::lval_bool(true),
+                "false" => Lval::lval_bool(false),
+                word => return Err(format!("'{}' isn't a bool", word)),
+            },
+            "builtin" => {
+                let name = self.string()?;
+                match Lbuiltin::from_name(&name) {
+                    Some(builtin) => Lval::lval_fun(builtin),
+                    None => return Err(format!("there's no builtin '{}'", name)),
+                }
+            }
+            "err" => Lval::lval_err(Lerror::GenericError {
+                msg: self.string()?,
+            }),
+            "vec" => {
+                let n = self.count()?;
+                let v = (0..n).map(|_| self.number()).collect::<Result<_, _>>()?;
+                Lval::lval_int_vec(v)
+            }
+            "map" => {
+                let mut m = BTreeMap::new();
+                for _ in 0..self.count()? {
+                    let k = self.key()?;
+                    m.insert(k, self.value()?);
+                }
+                Lval::lval_map(m)
+            }
+            "set" => {
+                let n = self.count()?;
+                let s = (0..n)
+                    .map(|_| self.key())
+                    .collect::<Result<BTreeSet<_>, _>>()?;
+                Lval::lval_set(s)
+            }
+            "sexpr" | "qexpr" => {
+                let mut lval = match tag {
+                    "sexpr" => Lval::lval_sexpr(),
+                    _ => Lval::lval_qexpr(),
+                };
+                for _ in 0..self.count()? {
+                    lval.add_cell(self.value()?);
+                }
+                lval
+            }
+            "lambda" => {
+                let formals = self.value()?;
+                let body = self.value()?;
+                let bound = Lenv::with_context(self.env.context());
+                let mut names = Vec::new();
+                for _ in 0..self.count()? {
+                    let name = self.string()?;
+                    bound.put(name.to_string(), self.value()?).unwrap();
+                    names.push(name);
+                }
+                let mut lval = Lval::lval_lambda_copy(bound, formals, body);
+                let home = if self.rest.trim_start().starts_with('-') {
+                    self.word()?;
+                    None
+                } else {
+                    Some(self.env()?)
+                };
+                if let LvalType::LVAL_LAMBDA(lambda) = &mut lval.ltype {
+                    lambda.home = home;
+                    lambda.bound = names.into_boxed_slice();
+                }
+                lval
+            }
+            tag => return Err(format!("unknown value '{}'", tag)),
+        })
+    }
+
+    /// The env with a number, the same one every time it's referred to
+    fn env(&mut self) -> Result<Rc<Lenv>, String> {
+        let id = self.count()?;
+        let context = self.env.context();
+        Ok(Rc::clone(
+            self.envs
+                .entry(id)
+                .or_insert_with(|| Lenv::with_context(context)),
+        ))
+    }
+
+    fn count(&mut self) -> Result<usize, String> {
+        let word = self.word()?;
+        word.parse()
+            .map_err(|_| format!("expected a count, found '{}'", word))
+    }
+
+    fn number(&mut self) -> Result<f64, String> {
+        let word = self.word()?;
+        word.parse()
+            .map_err(|_| format!("expected a number, found '{}'", word))
+    }
+
+    fn word(&mut self) -> Result<&'a str, String> {
+        let rest = self.rest.trim_start();
+        let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        if end == 0 {
+            return Err("the image ends too early".to_string());
+        }
+        self.rest = &rest[end..];
+        Ok(&rest[..end])
+    }
+
+    /// A string literal, as rispreter reads it
+    fn string(&mut self) -> Result<String, String> {
+        let rest = self.rest.trim_start();
+        if rest.is_empty() {
+            return Err("the image ends too early".to_string());
+        }
+        let mut chars = rest.char_indices().skip(1);
+        let mut end = None;
+        if rest.starts_with('"') {
+            while let Some((i, c)) = chars.next() {
+                match c {
+                    '\\' => {
+                        chars.next();
+                    }
+                    '"' => {
+                        end = Some(i + 1);
+                        break;
+                    }
+                    _ => (),
+                }
+            }
+        }
+        let literal = match end {
+            Some(end) => &rest[..end],
+            None => return Err("expected a string".to_string()),
+        };
+        self.rest = &rest[literal.len()..];
+        match parse_risp(literal) {
+            Some(Risp::LString(str)) => Ok(str),
+            _ => Err(format!("{} isn't a valid string", literal)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eval::eval_rispreter;
+
+    #[test]
+    fn image_round_trip() {
+        let env = Lenv::new();
+        for input in &[
+            "(def {nums} #[1 -2 3])",
+            "(def {data} #{\"k\\\"ey\" {a 'b' true} 2 #s{1 2}})",
+            "(def {add} (\\ {x y} {+ x y}))",
+            "(def {add10} (add 10))",
+            "(def {ops} {+ % == head})",
+        ] {
+            eval_rispreter(&env, input);
+        }
+        env.put("failed".to_string(), Lval::lval_err(Lerror::DivisionByZero))
+            .unwrap();
+        let image = save_image(&env);
+        assert!(
+            image.starts_with("rispreter-image 1\nenv 0 6\n"),
+            "{}",
+            image
+        );
+
+        let restored = Lenv::new();
+        assert_eq!(Lval::lval_sexpr(), load_image(&restored, &image));
+        for name in &["nums", "data", "add", "ops"] {
+            assert_eq!(
+                eval_rispreter(&env, name),
+                eval_rispreter(&restored, name),
+                "{}",
+                name
+            );
+        }
+        assert_eq!(15f64, eval_rispreter(&restored, "(add10 5)"));
+        assert_eq!(3f64, eval_rispreter(&restored, "((eval (head ops)) 1 2)"));
+        assert_eq!("error", eval_rispreter(&restored, "failed").type_name());
+        assert_eq!(image, save_image(&restored));
+    }
+
+    #[test]
+    fn image_keeps_shared_envs() {
+        let env = Lenv::new();
+        let module = Lenv::with_context(env.context());
+        eval_rispreter(&module, "(def {base} 100)");
+        eval_rispreter(&module, "(fun {up x} {+ base x})");
+        let mut up = eval_rispreter(&module, "up");
+        if let LvalType::LVAL_LAMBDA(lambda) = &mut up.ltype {
+            lambda.home = Some(Rc::clone(&module));
+        }
+        // the module refers back to its own lambda
+        module.put("up".to_string(), up.clone()).unwrap();
+        env.put("m/up".to_string(), up).unwrap();
+
+        let restored = Lenv::new();
+        load_image(&restored, &save_image(&env));
+        assert_eq!(105f64, eval_rispreter(&restored, "(m/up 5)"));
+        assert_eq!("error", eval_rispreter(&restored, "base").type_name());
+    }
+
+    #[test]
+    fn image_errors() {
+        let env = Lenv::new();
+        for (image, msg) in &[
+            ("", "the image ends too early"),
+            ("hello 1", "not a rispreter image"),
+            (
+                "rispreter-image 9",
+                "version 9 can't be read, only version 1",
+            ),
+            (
+                "rispreter-image 1\nenv 0 1\n\"f\" builtin \"nope\"",
+                "there's no builtin 'nope'",
+            ),
+            (
+                "rispreter-image 1\nenv 0 1\n\"n\" num x",
+                "expected a number, found 'x'",
+            ),
+            (
+                "rispreter-image 1\nenv 0 2\n\"n\" num 1",
+                "the image ends too early",
+            ),
+        ] {
+            assert_eq!(
+                Lval::lval_err(Lerror::InvalidImage {
+                    msg: msg.to_string()
+                }),
+                load_image(&env, image)
+            );
+        }
+        // nothing of an image that can't be read is bound
+        assert!(env.bindings().is_empty());
+    }
+}
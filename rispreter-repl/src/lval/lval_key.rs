@@ -0,0 +1,151 @@
+use crate::lval::lval_builtin::Lbuiltin;
+use crate::lval::lval_def::*;
+use crate::lval::lval_error::Lerror;
+use std::cmp::Ordering;
+use std::collections::BTreeSet;
+use std::fmt;
+
+/// A number as a key: `NaN` is equal to itself and bigger than any other number,
+/// so numbers have a total order
+#[derive(Clone, Copy, Debug)]
+pub struct Lnum(pub f64);
+
+impl PartialEq for Lnum {
+    fn eq(&self, other: &Lnum) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Lnum {}
+
+impl PartialOrd for Lnum {
+    fn partial_cmp(&self, other: &Lnum) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Lnum {
+    fn cmp(&self, other: &Lnum) -> Ordering {
+        self.0
+            .partial_cmp(&other.0)
+            .unwrap_or_else(|| self.0.is_nan().cmp(&other.0.is_nan()))
+    }
+}
+
+/// A key of a `LVAL_MAP` or an element of a `LVAL_SET`: the plain data an `Lval`
+/// holds, with an equality and an order that agree. Lambdas, which capture an
+/// env, and errors can't be one. Keys are ordered by type first and then by
+/// their content
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Lkey {
+    Num(Lnum),
+    Sym(String),
+    /// a builtin, by its name
+    Fun(String),
+    Str(String),
+    Char(char),
+    Bool(bool),
+    NumVec(Vec<Lnum>),
+    /// the entries of a map in order
+    Map(Vec<(Lkey, Lkey)>),
+    Set(BTreeSet<Lkey>),
+    Sexpr(Vec<Lkey>),
+    Qexpr(Vec<Lkey>),
+}
+
+impl Lkey {
+    /// The key for a value, or an error if it can't be one
+    pub fn from_lval(lval: &Lval) -> Result<Lkey, Lval> {
+        let cells = || -> Result<Vec<Lkey>, Lval> {
+            lval.cell.iter().map(|v| Lkey::from_lval(v)).collect()
+        };
+        Ok(match &lval.ltype {
+            LvalType::LVAL_NUM(n) => Lkey::Num(Lnum(*n)),
+            LvalType::LVAL_SYM(s) => Lkey::Sym(s.clone()),
+            LvalType::LVAL_FUN(fun) => Lkey::Fun(fun.name().to_string()),
+            LvalType::LVAL_STRING(s) => Lkey::Str(s.clone()),
+            LvalType::LVAL_CHAR(c) => Lkey::Char(*c),
+            LvalType::LVAL_BOOL(b) => Lkey::Bool(*b),
+            LvalType::LVAL_NUM_VEC(v) => Lkey::NumVec(v.iter().map(|n| Lnum(*n)).collect()),
+            LvalType::LVAL_MAP(m) => Lkey::Map(
+                m.iter()
+                    .map(|(k, v)| Ok((k.clone(), Lkey::from_lval(v)?)))
+                    .collect::<Result<_, Lval>>()?,
+            ),
+            LvalType::LVAL_SET(s) => Lkey::Set(s.clone()),
+            LvalType::LVAL_SEXPR => Lkey::Sexpr(cells()?),
+            LvalType::LVAL_QEXPR => Lkey::Qexpr(cells()?),
+            LvalType::LVAL_LAMBDA(_) | LvalType::LVAL_ERR(_) => {
+                return Err(Lval::lval_err(Lerror::NotAKey {
+                    lval: Box::new(lval.clone()),
+                }))
+            }
+        })
+    }
+
+    /// The value the key is for
+    pub fn to_lval(&self) -> Lval {
+        let expr = |mut expr: Lval, cells: &[Lkey]| {
+            for cell in cells {
+                expr.add_cell(cell.to_lval());
+            }
+            expr
+        };
+        match self {
+            Lkey::Num(n) => Lval::lval_num(n.0),
+            Lkey::Sym(s) => Lval::lval_sym(s.clone()),
+            Lkey::Fun(name) => match Lbuiltin::from_name(name) {
+                Some(fun) => Lval::lval_fun(fun),
+                None => Lval::lval_sym(name.clone()),
+            },
+            Lkey::Str(s) => Lval::lval_string(s.clone()),
+            Lkey::Char(c) => Lval::lval_char(*c),
+            Lkey::Bool(b) => Lval::lval_bool(*b),
+            Lkey::NumVec(v) => Lval::lval_int_vec(v.iter().map(|n| n.0).collect()),
+            Lkey::Map(m) => {
+                Lval::lval_map(m.iter().map(|(k, v)| (k.clone(), v.to_lval())).collect())
+            }
+            Lkey::Set(s) => Lval::lval_set(s.clone()),
+            Lkey::Sexpr(cells) => expr(Lval::lval_sexpr(), cells),
+            Lkey::Qexpr(cells) => expr(Lval::lval_qexpr(), cells),
+        }
+    }
+}
+
+impl fmt::Display for Lkey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_lval())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_are_equal_only_when_they_are_ordered_equal() {
+        let nan = Lkey::Num(Lnum(f64::NAN));
+        assert_eq!(nan, nan.clone());
+        assert_eq!(Ordering::Greater, nan.cmp(&Lkey::Num(Lnum(1.0))));
+        assert!(Lkey::Num(Lnum(1.0)) < Lkey::Sym("a".to_string()));
+
+        let mut list = Lval::lval_qexpr();
+        list.add_cell(Lval::lval_num(1.0));
+        list.add_cell(Lval::lval_string("a".to_string()));
+        let key = Lkey::from_lval(&list).unwrap();
+        assert_eq!(list, key.to_lval());
+    }
+
+    #[test]
+    fn lambdas_and_errors_are_not_keys() {
+        let lambda = Lval::lval_lambda(Lval::lval_qexpr(), Lval::lval_qexpr());
+        let err = Lkey::from_lval(&lambda).unwrap_err();
+        assert_eq!(
+            LvalType::LVAL_ERR(Lerror::NotAKey {
+                lval: Box::new(lambda)
+            }),
+            err.ltype
+        );
+        assert!(Lkey::from_lval(&Lval::lval_err(Lerror::DivisionByZero)).is_err());
+    }
+}
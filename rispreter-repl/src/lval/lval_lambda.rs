@@ -6,6 +6,13 @@ pub struct LLambda {
     pub local_lenv: Rc<Lenv>,
     pub formals: Box<Lval>,
     pub body: Box<Lval>,
+    /// the env of the module an imported lambda comes from, it's called in there
+    /// instead of the caller env so it still sees the rest of its module, but in
+    /// the session of the caller
+    pub home: Option<Rc<Lenv>>,
+    /// the formals a partially applied lambda was already given arguments for, in
+    /// order, their values are in `local_lenv`
+    pub bound: Box<[String]>,
 }
 
 impl LLambda {
@@ -14,6 +21,8 @@ impl LLambda {
             local_lenv: Lenv::new(),
             formals: Box::new(formals),
             body: Box::new(body),
+            home: None,
+            bound: Box::default(),
         }
     }
 
@@ -22,8 +31,16 @@ impl LLambda {
             local_lenv: env,
             formals: Box::new(formals),
             body: Box::new(body),
+            home: None,
+            bound: Box::default(),
         }
     }
+
+    /// The name the lambda goes by in traces and profiles: the first one it was
+    /// bound to with `def`, `fun` or `=`, which all of its copies share
+    pub fn name(&self) -> Option<String> {
+        self.local_lenv.name()
+    }
 }
 
 impl PartialEq for LLambda {
@@ -0,0 +1,285 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+static ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+/// how many profilers are profiling, the allocations are only counted for them
+static PROFILING: AtomicUsize = AtomicUsize::new(0);
+
+/// The system allocator, counting the allocations for the profiler while there's
+/// a profile. Without it as the `#[global_allocator]` the profiles have no
+/// allocations
+pub struct CountingAlloc;
+
+fn count_allocation() {
+    if PROFILING.load(Ordering::Relaxed) > 0 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        count_allocation();
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        count_allocation();
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+/// How many allocations `CountingAlloc` counted so far
+pub fn allocations() -> u64 {
+    ALLOCATIONS.load(Ordering::Relaxed)
+}
+
+/// What a profile knows about a function
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Lstats {
+    pub calls: u64,
+    /// time spent in its calls, counting a recursive call once
+    pub inclusive: Duration,
+    /// time spent in its calls, without the calls they made
+    pub exclusive: Duration,
+    /// allocations of its calls, without the calls they made
+    pub allocations: u64,
+}
+
+/// What was profiled: every function called, by the name of the function,
+/// and the stacks of calls the time was spent in
+#[derive(Debug, Default)]
+pub struct Lprofile {
+    /// the functions, those with the most exclusive time first
+    pub functions: Vec<(String, Lstats)>,
+    /// the exclusive time of every stack of calls, by its names joined with `;`
+    pub stacks: Vec<(String, Duration)>,
+}
+
+impl Lprofile {
+    /// The stacks in the folded format flame graph tools read, a stack per line
+    /// with its time in microseconds
+    pub fn folded(&self) -> String {
+        self.stacks
+            .iter()
+            .map(|(stack, time)| format!("{} {}\n", stack, time.as_micros()))
+            .collect()
+    }
+}
+
+impl fmt::Display for Lprofile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let width = self
+            .functions
+            .iter()
+            .map(|(name, _)| name.chars().count())
+            .chain(std::iter::once("function".len()))
+            .max()
+            .unwrap_or(0);
+        write!(
+            f,
+            "{:<width$} {:>10} {:>14} {:>14} {:>12}",
+            "function",
+            "calls",
+            "inclusive ms",
+            "exclusive ms",
+            "allocations",
+            width = width
+        )?;
+        let ms = |time: Duration| time.as_secs_f64() * 1000.0;
+        for (name, stats) in &self.functions {
+            write!(
+                f,
+                "\n{:<width$} {:>10} {:>14.3} {:>14.3} {:>12}",
+                name,
+                stats.calls,
+                ms(stats.inclusive),
+                ms(stats.exclusive),
+                stats.allocations,
+                width = width
+            )?;
+        }
+        Ok(())
+    }
+}
+
+struct Frame {
+    name: String,
+    start: Instant,
+    allocations: u64,
+    /// time and allocations of the calls made from this one
+    children: Duration,
+    children_allocations: u64,
+}
+
+/// Times the calls of the functions while it's profiling
+#[derive(Default)]
+pub struct Lprofiler {
+    profiling: Cell<bool>,
+    frames: RefCell<Vec<Frame>>,
+    functions: RefCell<HashMap<String, Lstats>>,
+    stacks: RefCell<HashMap<String, Duration>>,
+}
+
+impl Lprofiler {
+    /// Starts a profile, false if there's one already
+    pub fn start(&self) -> bool {
+        if self.profiling.replace(true) {
+            return false;
+        }
+        PROFILING.fetch_add(1, Ordering::Relaxed);
+        self.frames.borrow_mut().clear();
+        self.functions.borrow_mut().clear();
+        self.stacks.borrow_mut().clear();
+        true
+    }
+
+    /// Ends the profile, giving back what it saw
+    pub fn stop(&self) -> Lprofile {
+        if self.profiling.replace(false) {
+            PROFILING.fetch_sub(1, Ordering::Relaxed);
+        }
+        self.frames.borrow_mut().clear();
+        let mut functions: Vec<(String, Lstats)> = self.functions.borrow_mut().drain().collect();
+        functions.sort_by(|(a_name, a), (b_name, b)| {
+            b.exclusive
+                .cmp(&a.exclusive)
+                .then_with(|| a_name.cmp(b_name))
+        });
+        let mut stacks: Vec<(String, Duration)> = self.stacks.borrow_mut().drain().collect();
+        stacks.sort();
+        Lprofile { functions, stacks }
+    }
+
+    pub fn is_profiling(&self) -> bool {
+        self.profiling.get()
+    }
+
+    /// Marks a call being made
+    pub fn enter(&self, name: &str) {
+        self.frames.borrow_mut().push(Frame {
+            name: name.to_string(),
+            start: Instant::now(),
+            allocations: allocations(),
+            children: Duration::default(),
+            children_allocations: 0,
+        });
+    }
+
+    /// Marks the last call made as ended
+    pub fn leave(&self) {
+        let mut frames = self.frames.borrow_mut();
+        let frame = match frames.pop() {
+            Some(frame) => frame,
+            None => return,
+        };
+        let time = frame.start.elapsed();
+        let allocated = allocations() - frame.allocations;
+        let exclusive = time.checked_sub(frame.children).unwrap_or_default();
+        let recursive = frames.iter().any(|f| f.name == frame.name);
+
+        let mut stack: Vec<&str> = frames.iter().map(|f| f.name.as_str()).collect();
+        stack.push(&frame.name);
+        *self.stacks.borrow_mut().entry(stack.join(";")).or_default() += exclusive;
+
+        let mut functions = self.functions.borrow_mut();
+        let stats = functions.entry(frame.name).or_default();
+        stats.calls += 1;
+        if !recursive {
+            stats.inclusive += time;
+        }
+        stats.exclusive += exclusive;
+        stats.allocations += allocated.saturating_sub(frame.children_allocations);
+        // measured again, so what is kept here isn't taken for the parent's own
+        if let Some(parent) = frames.last_mut() {
+            parent.children += frame.start.elapsed();
+            parent.children_allocations += allocations() - frame.allocations;
+        }
+    }
+}
+
+impl Drop for Lprofiler {
+    fn drop(&mut self) {
+        if self.profiling.get() {
+            PROFILING.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+}
+
+impl fmt::Debug for Lprofiler {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Lprofiler {}", self.profiling.get())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eval::eval_rispreter;
+    use crate::lval::lval_env::Lenv;
+
+    #[global_allocator]
+    static ALLOC: CountingAlloc = CountingAlloc;
+
+    #[test]
+    fn profiler_counts_calls_and_stacks() {
+        let env = Lenv::new();
+        eval_rispreter(
+            &env,
+            "(fun {count n} {if (== n 0) {{}} {join {n} (count (- n 1))}})",
+        );
+        let context = env.context();
+        let profiler = context.profiler();
+        assert!(profiler.start());
+        assert!(!profiler.start());
+        eval_rispreter(&env, "(count 3)");
+        let profile = profiler.stop();
+        assert!(!profiler.is_profiling());
+
+        let stats = |name: &str| {
+            profile
+                .functions
+                .iter()
+                .find(|(n, _)| n == name)
+                .map(|(_, stats)| *stats)
+                .unwrap()
+        };
+        assert_eq!(4, stats("count").calls);
+        assert_eq!(3, stats("join").calls);
+        assert!(stats("count").inclusive >= stats("count").exclusive);
+        assert!(stats("join").allocations > 0);
+
+        let before = allocations();
+        eval_rispreter(&env, "(count 3)");
+        assert_eq!(before, allocations());
+
+        // the calls are counted for the function called, whatever it's called by
+        eval_rispreter(&env, "(fun {app f x} {f x})");
+        profiler.start();
+        eval_rispreter(&env, "(map count {1 2})");
+        eval_rispreter(&env, "(app count 0)");
+        let calls = profiler.stop().functions;
+        let calls = |name: &str| {
+            calls
+                .iter()
+                .find(|(n, _)| n == name)
+                .map(|(_, stats)| stats.calls)
+        };
+        assert_eq!(Some(6), calls("count"));
+        assert_eq!(None, calls("f"));
+
+        let folded = profile.folded();
+        assert!(folded.contains("\ncount;if;count;if;join "), "{}", folded);
+        assert!(folded.starts_with("count "), "{}", folded);
+        let table = profile.to_string();
+        assert!(table.starts_with("function"), "{}", table);
+        assert_eq!(profile.functions.len() + 1, table.lines().count());
+    }
+}
@@ -0,0 +1,185 @@
+use crate::lval::lval_def::{Lval, LvalType};
+use crate::lval::lval_env::Lenv;
+use std::cell::{Cell, RefCell};
+use std::fmt;
+use std::io::{self, Write};
+use std::rc::{Rc, Weak};
+
+/// A function being traced, whatever name it's called by: a builtin by its own
+/// name, a lambda by the env it was made with, which all of its copies share
+#[derive(Debug)]
+enum Traced {
+    Builtin(String),
+    Lambda(Weak<Lenv>),
+}
+
+impl Traced {
+    fn of(f: &Lval) -> Option<Traced> {
+        match &f.ltype {
+            LvalType::LVAL_FUN(builtin) => Some(Traced::Builtin(builtin.name().to_string())),
+            LvalType::LVAL_LAMBDA(lambda) => {
+                Some(Traced::Lambda(Rc::downgrade(&lambda.local_lenv)))
+            }
+            _ => None,
+        }
+    }
+
+    fn is(&self, f: &Lval) -> bool {
+        match (self, &f.ltype) {
+            (Traced::Builtin(name), LvalType::LVAL_FUN(builtin)) => name == builtin.name(),
+            (Traced::Lambda(env), LvalType::LVAL_LAMBDA(lambda)) => {
+                std::ptr::eq(env.as_ptr(), Rc::as_ptr(&lambda.local_lenv))
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Prints the calls of the functions being traced, with their arguments and what
+/// they give back, indented by how many traced calls they're made from
+pub struct Ltracer {
+    traced: RefCell<Vec<Traced>>,
+    all: Cell<bool>,
+    depth: Cell<usize>,
+    output: RefCell<Box<dyn Write>>,
+}
+
+impl Ltracer {
+    /// Traces the calls of a builtin or a lambda
+    pub fn trace(&self, f: &Lval) {
+        if !self.traced.borrow().iter().any(|t| t.is(f)) {
+            self.traced.borrow_mut().extend(Traced::of(f));
+        }
+    }
+
+    /// Stops tracing a function, false if it wasn't
+    pub fn untrace(&self, f: &Lval) -> bool {
+        let mut traced = self.traced.borrow_mut();
+        let before = traced.len();
+        traced.retain(|t| !t.is(f));
+        traced.len() != before
+    }
+
+    /// Traces every call, as `--trace` does
+    pub fn trace_all(&self, all: bool) {
+        self.all.set(all);
+    }
+
+    /// Sets where the calls are printed to, stderr by default
+    pub fn set_output(&self, output: Box<dyn Write>) {
+        *self.output.borrow_mut() = output;
+    }
+
+    /// Whether a call of a function is traced
+    pub fn is_traced(&self, f: &Lval) -> bool {
+        self.all.get() || self.traced.borrow().iter().any(|t| t.is(f))
+    }
+
+    /// Prints a call about to be made, with its evaluated arguments
+    pub fn enter(&self, name: &str, args: &Lval) {
+        let mut call = vec![name.to_string()];
+        call.extend(args.cell.iter().map(|arg| arg.to_string()));
+        self.print(&format!("({})", call.join(" ")));
+        self.depth.set(self.depth.get() + 1);
+    }
+
+    /// Prints what a call gave back
+    pub fn leave(&self, name: &str, res: &Lval) {
+        self.depth.set(self.depth.get().saturating_sub(1));
+        self.print(&format!("{} returned {}", name, res));
+    }
+
+    /// Forgets the calls being made, after an evaluation that didn't end them
+    pub fn reset(&self) {
+        self.depth.set(0);
+    }
+
+    fn print(&self, text: &str) {
+        let depth = self.depth.get();
+        let mut output = self.output.borrow_mut();
+        // tracing doesn't get in the way of the evaluation when it can't print
+        let _ = writeln!(output, "{}{}: {}", "  ".repeat(depth), depth, text);
+        let _ = output.flush();
+    }
+}
+
+impl Default for Ltracer {
+    fn default() -> Self {
+        Ltracer {
+            traced: RefCell::new(Vec::new()),
+            all: Cell::new(false),
+            depth: Cell::new(0),
+            output: RefCell::new(Box::new(io::stderr())),
+        }
+    }
+}
+
+impl fmt::Debug for Ltracer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Ltracer {:?}", self.traced.borrow())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::eval::eval_rispreter;
+    use crate::lval::lval_context::CapturedOutput;
+    use crate::lval::lval_env::Lenv;
+
+    #[test]
+    fn tracer_prints_calls_and_results() {
+        let env = Lenv::new();
+        let output = CapturedOutput::new();
+        env.context().tracer().set_output(Box::new(output.clone()));
+        eval_rispreter(
+            &env,
+            "(fun {fact n} {if (== n 0) {1} {* n (fact (- n 1))}})",
+        );
+        eval_rispreter(&env, "(def {f} fact)");
+
+        assert_eq!("{fact}", eval_rispreter(&env, "(trace f)").to_string());
+        assert_eq!(2f64, eval_rispreter(&env, "(fact 2)"));
+        assert_eq!(
+            "0: (fact 2)
+  1: (fact 1)
+    2: (fact 0)
+    2: fact returned 1
+  1: fact returned 1
+0: fact returned 2
+",
+            output.contents()
+        );
+
+        assert_eq!("{fact}", eval_rispreter(&env, "(untrace f)").to_string());
+        eval_rispreter(&env, "(trace head)");
+        eval_rispreter(&env, "(fact 1)");
+        assert!(output.contents().ends_with("0: fact returned 2\n"));
+        eval_rispreter(&env, "(head {\"a\" 2})");
+        assert!(output
+            .contents()
+            .ends_with("0: (head {\"a\" 2})\n0: head returned {\"a\"}\n"));
+        assert_eq!("error", eval_rispreter(&env, "(trace 1)").type_name());
+    }
+
+    #[test]
+    fn tracer_follows_the_function_wherever_it_is_called_from() {
+        let env = Lenv::new();
+        let output = CapturedOutput::new();
+        env.context().tracer().set_output(Box::new(output.clone()));
+        eval_rispreter(&env, "(fun {sq x} {* x x})");
+        eval_rispreter(&env, "(fun {app f x} {f x})");
+        // the same body in another env is another function
+        eval_rispreter(&env, "(def {other} ((\\ {y x} {* x x}) 1))");
+
+        eval_rispreter(&env, "(trace sq)");
+        eval_rispreter(&env, "(map sq {2})");
+        eval_rispreter(&env, "(app sq 3)");
+        eval_rispreter(&env, "(app other 4)");
+        assert_eq!(
+            "0: (sq 2)\n0: sq returned 4\n0: (sq 3)\n0: sq returned 9\n",
+            output.contents()
+        );
+        assert_eq!("{}", eval_rispreter(&env, "(untrace other)").to_string());
+        assert_eq!("{sq}", eval_rispreter(&env, "(untrace sq)").to_string());
+    }
+}
@@ -1,6 +1,14 @@
 pub mod lval_builtin;
+pub mod lval_context;
+pub mod lval_coverage;
+pub mod lval_debug;
 pub mod lval_def;
+pub mod lval_doc;
 pub mod lval_env;
 pub mod lval_error;
 pub mod lval_eval;
+pub mod lval_image;
+pub mod lval_key;
 pub mod lval_lambda;
+pub mod lval_profile;
+pub mod lval_trace;
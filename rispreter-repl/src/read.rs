@@ -1,23 +1,27 @@
 use crate::lval::lval_builtin::Lbuiltin;
 use crate::lval::lval_def::*;
 use crate::lval::lval_error::Lerror;
+use crate::lval::lval_key::Lkey;
 
 use rispreter_parser::structure::{NumType, Prelude, Risp, TypedVec};
+use rispreter_parser::syntax::Node;
+use std::collections::{BTreeMap, BTreeSet};
 
 pub fn read(parsed: Option<Risp>) -> Lval {
     match parsed {
         Some(some) => match some {
             Risp::LComment => Lval::lval_sexpr(),
+            // a comment inside an expression isn't one of its elements
             Risp::Sexpr(lvals) => {
                 let mut sexpr = Lval::lval_sexpr();
-                for lval in lvals {
+                for lval in lvals.into_iter().filter(|lval| !is_comment(lval)) {
                     sexpr.add_cell(read(Some(lval)));
                 }
                 sexpr
             }
             Risp::Qexpr(lvals) => {
                 let mut qexpr = Lval::lval_qexpr();
-                for lval in lvals {
+                for lval in lvals.into_iter().filter(|lval| !is_comment(lval)) {
                     qexpr.add_cell(read(Some(lval)));
                 }
                 qexpr
@@ -25,6 +29,23 @@ pub fn read(parsed: Option<Risp>) -> Lval {
             Risp::LVec(v) => match v {
                 TypedVec::NumVec(v) => Lval::lval_int_vec(v),
             },
+            Risp::LMap(pairs) => {
+                let mut map = BTreeMap::new();
+                for (k, v) in pairs {
+                    match Lkey::from_lval(&read(Some(k))) {
+                        Ok(k) => map.insert(k, read(Some(v))),
+                        Err(err) => return err,
+                    };
+                }
+                Lval::lval_map(map)
+            }
+            Risp::LSet(lvals) => {
+                let set: Result<BTreeSet<Lkey>, Lval> = lvals
+                    .into_iter()
+                    .map(|v| Lkey::from_lval(&read(Some(v))))
+                    .collect();
+                set.map(Lval::lval_set).unwrap_or_else(|err| err)
+            }
             Risp::LNumber(numtype) => match numtype {
                 NumType::Float(f) => Lval::lval_num(f),
                 NumType::Int(i) => Lval::lval_num(i as f64),
@@ -46,6 +67,7 @@ pub fn read(parsed: Option<Risp>) -> Lval {
                 Prelude::Do => Lval::lval_fun(Lbuiltin::lbuiltin_do()),
                 Prelude::Let => Lval::lval_fun(Lbuiltin::lbuiltin_let()),
                 Prelude::Select => Lval::lval_fun(Lbuiltin::lbuiltin_select()),
+                Prelude::Match => Lval::lval_fun(Lbuiltin::lbuiltin_match()),
                 Prelude::Curry => Lval::lval_fun(Lbuiltin::lbuiltin_curry()),
                 Prelude::Uncurry => Lval::lval_fun(Lbuiltin::lbuiltin_uncurry()),
                 Prelude::Def => Lval::lval_fun(Lbuiltin::lbuiltin_def()),
@@ -62,6 +84,59 @@ pub fn read(parsed: Option<Risp>) -> Lval {
                 Prelude::Elemen => Lval::lval_fun(Lbuiltin::lbuiltin_elemen()),
                 Prelude::Map => Lval::lval_fun(Lbuiltin::lbuiltin_map()),
                 Prelude::Filter => Lval::lval_fun(Lbuiltin::lbuiltin_filter()),
+                Prelude::Assoc => Lval::lval_fun(Lbuiltin::lbuiltin_assoc()),
+                Prelude::Dissoc => Lval::lval_fun(Lbuiltin::lbuiltin_dissoc()),
+                Prelude::GetIn => Lval::lval_fun(Lbuiltin::lbuiltin_get_in()),
+                Prelude::Keys => Lval::lval_fun(Lbuiltin::lbuiltin_keys()),
+                Prelude::Vals => Lval::lval_fun(Lbuiltin::lbuiltin_vals()),
+                Prelude::Contains => Lval::lval_fun(Lbuiltin::lbuiltin_contains()),
+                Prelude::Merge => Lval::lval_fun(Lbuiltin::lbuiltin_merge()),
+                Prelude::Union => Lval::lval_fun(Lbuiltin::lbuiltin_union()),
+                Prelude::Intersection => Lval::lval_fun(Lbuiltin::lbuiltin_intersection()),
+                Prelude::Difference => Lval::lval_fun(Lbuiltin::lbuiltin_difference()),
+                Prelude::Subset => Lval::lval_fun(Lbuiltin::lbuiltin_subset()),
+                Prelude::SetToList => Lval::lval_fun(Lbuiltin::lbuiltin_set_to_list()),
+                Prelude::ListToSet => Lval::lval_fun(Lbuiltin::lbuiltin_list_to_set()),
+                Prelude::StrConcat => Lval::lval_fun(Lbuiltin::lbuiltin_str_concat()),
+                Prelude::StrLen => Lval::lval_fun(Lbuiltin::lbuiltin_str_len()),
+                Prelude::Substr => Lval::lval_fun(Lbuiltin::lbuiltin_substr()),
+                Prelude::StrSplit => Lval::lval_fun(Lbuiltin::lbuiltin_str_split()),
+                Prelude::StrJoin => Lval::lval_fun(Lbuiltin::lbuiltin_str_join()),
+                Prelude::StrTrim => Lval::lval_fun(Lbuiltin::lbuiltin_str_trim()),
+                Prelude::StrUpper => Lval::lval_fun(Lbuiltin::lbuiltin_str_upper()),
+                Prelude::StrLower => Lval::lval_fun(Lbuiltin::lbuiltin_str_lower()),
+                Prelude::StrIndexOf => Lval::lval_fun(Lbuiltin::lbuiltin_str_index_of()),
+                Prelude::StrReplace => Lval::lval_fun(Lbuiltin::lbuiltin_str_replace()),
+                Prelude::StartsWith => Lval::lval_fun(Lbuiltin::lbuiltin_starts_with()),
+                Prelude::EndsWith => Lval::lval_fun(Lbuiltin::lbuiltin_ends_with()),
+                Prelude::StrToChars => Lval::lval_fun(Lbuiltin::lbuiltin_str_to_chars()),
+                Prelude::CharsToStr => Lval::lval_fun(Lbuiltin::lbuiltin_chars_to_str()),
+                Prelude::Println => Lval::lval_fun(Lbuiltin::lbuiltin_println()),
+                Prelude::Print => Lval::lval_fun(Lbuiltin::lbuiltin_print()),
+                Prelude::Show => Lval::lval_fun(Lbuiltin::lbuiltin_show()),
+                Prelude::Format => Lval::lval_fun(Lbuiltin::lbuiltin_format()),
+                Prelude::ReadFile => Lval::lval_fun(Lbuiltin::lbuiltin_read_file()),
+                Prelude::WriteFile => Lval::lval_fun(Lbuiltin::lbuiltin_write_file()),
+                Prelude::AppendFile => Lval::lval_fun(Lbuiltin::lbuiltin_append_file()),
+                Prelude::FileExists => Lval::lval_fun(Lbuiltin::lbuiltin_file_exists()),
+                Prelude::ListDir => Lval::lval_fun(Lbuiltin::lbuiltin_list_dir()),
+                Prelude::MakeDir => Lval::lval_fun(Lbuiltin::lbuiltin_make_dir()),
+                Prelude::RemoveFile => Lval::lval_fun(Lbuiltin::lbuiltin_remove_file()),
+                Prelude::ReadLines => Lval::lval_fun(Lbuiltin::lbuiltin_read_lines()),
+                Prelude::Getenv => Lval::lval_fun(Lbuiltin::lbuiltin_getenv()),
+                Prelude::Setenv => Lval::lval_fun(Lbuiltin::lbuiltin_setenv()),
+                Prelude::Exit => Lval::lval_fun(Lbuiltin::lbuiltin_exit()),
+                Prelude::Run => Lval::lval_fun(Lbuiltin::lbuiltin_run()),
+                Prelude::Load => Lval::lval_fun(Lbuiltin::lbuiltin_load()),
+                Prelude::Import => Lval::lval_fun(Lbuiltin::lbuiltin_import()),
+                Prelude::Break => Lval::lval_fun(Lbuiltin::lbuiltin_break()),
+                Prelude::Trace => Lval::lval_fun(Lbuiltin::lbuiltin_trace()),
+                Prelude::Untrace => Lval::lval_fun(Lbuiltin::lbuiltin_untrace()),
+                Prelude::Profile => Lval::lval_fun(Lbuiltin::lbuiltin_profile()),
+                Prelude::Deftest => Lval::lval_fun(Lbuiltin::lbuiltin_deftest()),
+                Prelude::Assert => Lval::lval_fun(Lbuiltin::lbuiltin_assert()),
+                Prelude::AssertEq => Lval::lval_fun(Lbuiltin::lbuiltin_assert_eq()),
+                Prelude::AssertError => Lval::lval_fun(Lbuiltin::lbuiltin_assert_error()),
                 Prelude::Add => Lval::lval_fun(Lbuiltin::lbuiltin_add()),
                 Prelude::Sub => Lval::lval_fun(Lbuiltin::lbuiltin_sub()),
                 Prelude::Mul => Lval::lval_fun(Lbuiltin::lbuiltin_mul()),
@@ -86,3 +161,29 @@ pub fn read(parsed: Option<Risp>) -> Lval {
         }),
     }
 }
+
+/// Reads a form like `read`, with every value and the values inside its
+/// expressions given where they are in the `source` the coverage knows, from the
+/// syntax `node` the form was written as
+pub fn read_spanned(parsed: Risp, node: &Node, source: usize) -> Lval {
+    let mut lval = read(Some(parsed));
+    set_spans(&mut lval, node, source);
+    lval
+}
+
+fn set_spans(lval: &mut Lval, node: &Node, source: usize) {
+    lval.span = Some(Lspan {
+        source,
+        start: node.span.start,
+        end: node.span.end,
+    });
+    if let LvalType::LVAL_SEXPR | LvalType::LVAL_QEXPR = lval.ltype {
+        for (cell, node) in lval.cell.iter_mut().zip(node.forms()) {
+            set_spans(cell, node, source);
+        }
+    }
+}
+
+fn is_comment(parsed: &Risp) -> bool {
+    *parsed == Risp::LComment
+}
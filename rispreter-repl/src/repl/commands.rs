@@ -0,0 +1,267 @@
+use super::debug::{add_breakpoint, remove_breakpoint};
+use super::RispRepl;
+use crate::eval::{eval_rispreter, eval_rispreter_file};
+use crate::lval::lval_def::{Lval, LvalType};
+use crate::lval::lval_doc::builtin_doc;
+use crate::lval::lval_image::save_image;
+use std::path::Path;
+use std::time::Instant;
+
+const HELP: &str = ":help           shows this help
+:quit           leaves the REPL
+:env            lists the global bindings with their types
+:type expr      type of what an expression evaluates to
+:time expr      evaluates an expression, with its wall time and evaluation steps
+:load file      evaluates a .rspr file
+:reset          starts over with a new env and the prelude
+:doc sym        documentation of a builtin or a function
+:save file      writes the inputs evaluated so far to a file
+:save-image file  writes the global env to an image, to start with it again with --image
+:break [sym]    stops in the debugger when a function is called, or lists the breakpoints
+:unbreak [sym]  removes a breakpoint, or all of them
+:step expr      evaluates an expression stopping at every call, :help there for more";
+
+impl RispRepl {
+    /// Handles a `:command` line of the REPL, writing what it has to say to the
+    /// output of the session. Returns false when the REPL should quit
+    pub fn run_command(&self, line: &str) -> bool {
+        let line = line.trim();
+        // a command can also be ended like the Lisp inputs are
+        let line = line.strip_suffix('.').unwrap_or(line);
+        let (command, arg) = match line.find(char::is_whitespace) {
+            Some(at) => (&line[..at], line[at..].trim()),
+            None => (line, ""),
+        };
+        match (command, arg) {
+            (":help", _) => self.say(HELP),
+            (":quit", _) | (":q", _) => return false,
+            (":env", _) => {
+                let bindings: Vec<String> = self
+                    .env()
+                    .bindings()
+                    .iter()
+                    .map(|(name, val)| format!("{} : {}", name, val.type_name()))
+                    .collect();
+                self.say(&bindings.join("\n"));
+            }
+            (":reset", _) => {
+                let res = self.reset();
+                if let LvalType::LVAL_ERR(_) = res.ltype {
+                    self.say(&res.to_string());
+                }
+            }
+            (":break", name) => self.say(&add_breakpoint(self.env().context().debugger(), name)),
+            (":unbreak", name) => {
+                self.say(&remove_breakpoint(self.env().context().debugger(), name))
+            }
+            (":next", _) | (":n", _) | (":continue", _) | (":c", _) | (":abort", _) => {
+                self.say("Not stopped in the debugger, see :help")
+            }
+            (_, "")
+                if [
+                    ":type",
+                    ":time",
+                    ":load",
+                    ":doc",
+                    ":save",
+                    ":save-image",
+                    ":step",
+                ]
+                .contains(&command) =>
+            {
+                self.say(&format!("{} needs an argument, see :help", command))
+            }
+            (":type", expr) => self.say(eval_rispreter(&self.env(), expr).type_name()),
+            (":time", expr) => {
+                let context = self.env().context();
+                let steps = context.steps();
+                let start = Instant::now();
+                let res = self.eval_input(expr);
+                let elapsed = start.elapsed();
+                self.say(&format!(
+                    "{}\ntime: {:?}, steps: {}",
+                    res,
+                    elapsed,
+                    context.steps() - steps
+                ));
+            }
+            (":load", file) => {
+                let res = eval_rispreter_file(&self.env(), Path::new(file));
+                if let LvalType::LVAL_ERR(_) = res.ltype {
+                } else {
+                    let load = format!("(load {})", Lval::lval_string(file.to_string()));
+                    self.inputs.borrow_mut().push(load);
+                }
+                self.say(&res.to_string());
+            }
+            (":step", expr) => {
+                self.env().context().debugger().step();
+                let res = self.eval_input(expr);
+                self.say(&res.to_string());
+            }
+            (":doc", sym) => self.say(&self.doc(sym)),
+            (":save", file) => {
+                let mut program = self.inputs.borrow().join("\n");
+                program.push('\n');
+                match std::fs::write(file, program) {
+                    Ok(()) => self.say(&format!(
+                        "saved {} inputs to {}",
+                        self.inputs.borrow().len(),
+                        file
+                    )),
+                    Err(err) => self.say(&format!("Can't save to {}: {}", file, err)),
+                }
+            }
+            (":save-image", file) => match std::fs::write(file, save_image(&self.env())) {
+                Ok(()) => self.say(&format!("saved the env to {}", file)),
+                Err(err) => self.say(&format!("Can't save to {}: {}", file, err)),
+            },
+            _ => self.say(&format!("Unknown command '{}', see :help", command)),
+        }
+        true
+    }
+
+    fn doc(&self, sym: &str) -> String {
+        if let Some((usage, summary)) = builtin_doc(sym) {
+            return format!("{}\n  {}", usage, summary);
+        }
+        let val = eval_rispreter(&self.env(), sym);
+        match &val.ltype {
+            LvalType::LVAL_FUN(builtin) => match builtin_doc(builtin.name()) {
+                Some((usage, summary)) => format!("{}\n  {}", usage, summary),
+                None => format!("{} : builtin", sym),
+            },
+            LvalType::LVAL_LAMBDA(lambda) => {
+                let mut usage = vec![sym.to_string()];
+                usage.extend(lambda.formals.cell.iter().map(|formal| formal.to_string()));
+                format!("({})\n  {}", usage.join(" "), val)
+            }
+            LvalType::LVAL_ERR(_) => format!("No documentation for '{}'", sym),
+            _ => format!("{} : {} = {}", sym, val.type_name(), val),
+        }
+    }
+
+    fn say(&self, text: &str) {
+        // there's nowhere else to report a failing output to
+        let _ = self.env().context().write(&format!("{}\n", text));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lval::lval_context::CapturedOutput;
+    use crate::test_util::TempDir;
+
+    fn repl() -> (RispRepl, CapturedOutput) {
+        let repl = RispRepl::new();
+        repl.load_prelude();
+        let output = CapturedOutput::new();
+        repl.set_output(Box::new(output.clone()));
+        (repl, output)
+    }
+
+    #[test]
+    fn commands_inspect_the_session() {
+        let (repl, output) = repl();
+        repl.eval_input("(def {greeting} \"hi\")");
+        assert!(repl.run_command(":type (+ 1 2)"));
+        assert!(repl.run_command(":doc head"));
+        assert!(repl.run_command(":doc len."));
+        assert!(repl.run_command(":time (fib 5)"));
+        assert!(repl.run_command(":env"));
+        assert!(!repl.run_command(":quit"));
+
+        let out = output.contents();
+        assert!(out.starts_with("number\n(head {list})\n  "), "{}", out);
+        assert!(
+            out.contains("(len l)\n  (\\ {l} {if (== l {}) {0} {+ 1 (len (tail l))}})\n"),
+            "{}",
+            out
+        );
+        assert!(out.contains("5\ntime: "), "{}", out);
+        assert!(out.contains("greeting : string\n"), "{}", out);
+        assert!(out.contains("fib : lambda\n"), "{}", out);
+    }
+
+    #[test]
+    fn commands_save_reset_and_load() {
+        let dir = TempDir::new("save");
+        let file = dir.join("session.rspr").display().to_string();
+        let (repl, output) = repl();
+        repl.eval_input("(def {a} 20)");
+        repl.eval_input("(/ a 0)");
+        repl.eval_input("(fun {inc x} {+ x 1})");
+        repl.run_command(&format!(":save {}", file));
+        assert_eq!(
+            "(def {a} 20)\n(fun {inc x} {+ x 1})\n",
+            std::fs::read_to_string(&file).unwrap()
+        );
+
+        repl.run_command(":reset");
+        assert_eq!("error", eval_rispreter(&repl.env(), "a").type_name());
+        assert_eq!(55f64, eval_rispreter(&repl.env(), "(fib 10)"));
+
+        repl.run_command(&format!(":load {}", file));
+        assert_eq!(21f64, eval_rispreter(&repl.env(), "(inc a)"));
+        assert!(output.contents().starts_with("saved 2 inputs to "));
+    }
+
+    #[test]
+    fn commands_save_image() {
+        let dir = TempDir::new("image");
+        let file = dir.join("session.image");
+        let (repl, output) = repl();
+        repl.eval_input("(def {scale} 3)");
+        repl.eval_input("(fun {times a b} {* a b})");
+        repl.eval_input("(def {triple} (times scale))");
+        repl.run_command(&format!(":save-image {}", file.display()));
+        assert!(output.contents().starts_with("saved the env to "));
+
+        let restored = RispRepl::new();
+        assert_eq!(Lval::lval_sexpr(), restored.load_image(&file));
+        assert_eq!(12f64, eval_rispreter(&restored.env(), "(triple 4)"));
+        assert_eq!(55f64, eval_rispreter(&restored.env(), "(fib 10)"));
+    }
+
+    #[test]
+    fn commands_drive_the_debugger() {
+        let (repl, output) = repl();
+        let mut lines = vec![":step", "(* n 10)", ":continue", ":n", ":c"].into_iter();
+        repl.set_debugger_input(move || lines.next().map(String::from));
+        repl.eval_input("(fun {add-one n} {+ n 1})");
+        repl.run_command(":break add-one");
+        repl.run_command(":break");
+        repl.run_command(":continue");
+        assert_eq!(
+            Lval::lval_num(4f64),
+            repl.eval_input("(add-one (add-one 2))")
+        );
+
+        repl.run_command(":unbreak add-one");
+        repl.run_command(":step (add-one 1)");
+        let out = output.contents();
+        assert!(
+            out.starts_with("breakpoint on add-one\nadd-one\nNot stopped in the debugger"),
+            "{}",
+            out
+        );
+        assert!(
+            out.contains(
+                "breakpoint on add-one: (add-one 2)\n  calls: (top level)\n\
+                 step: (+ 2 1)\n  calls: add-one\n  n = 2\n\
+                 20\n\
+                 breakpoint on add-one: (add-one 3)\n"
+            ),
+            "{}",
+            out
+        );
+        assert!(
+            out.ends_with(
+                "removed the breakpoint on add-one\nstep: (add-one 1)\n  calls: (top level)\n2\n"
+            ),
+            "{}",
+            out
+        );
+    }
+}
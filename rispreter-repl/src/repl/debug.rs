@@ -0,0 +1,78 @@
+use crate::eval::eval_rispreter;
+use crate::lval::lval_debug::{Ldebugger, Lhandler, Lresume};
+
+/// Prompt of the REPL while it's stopped in the debugger
+pub const DEBUG_PROMPT: &str = "debug> ";
+
+const DEBUG_HELP: &str = ":step, :s       goes on to the next call, into the one being made
+:next, :n       goes on to the next call, over the one being made
+:continue, :c   goes on up to the next breakpoint
+:abort          gives up on the evaluation
+:where          shows the stop again
+:break [sym]    stops when a function is called, or lists the breakpoints
+:unbreak [sym]  removes a breakpoint, or all of them
+expr            evaluates an expression where the evaluation stopped";
+
+/// A handler for the stops of the debugger that shows them in the output of the
+/// session and asks `input` what to do, until it says how to go on. Running out
+/// of input goes on as `:continue`
+pub fn handler(mut input: impl FnMut() -> Option<String> + 'static) -> Lhandler {
+    Box::new(move |stop| {
+        let context = stop.env.context();
+        // there's nowhere else to report a failing output to
+        let say = |text: &str| {
+            let _ = context.write(&format!("{}\n", text));
+        };
+        say(&stop.to_string());
+        while let Some(line) = input() {
+            let line = line.trim();
+            let line = line.strip_suffix('.').unwrap_or(line);
+            let (command, arg) = match line.find(char::is_whitespace) {
+                Some(at) => (&line[..at], line[at..].trim()),
+                None => (line, ""),
+            };
+            match command {
+                "" => {}
+                ":step" | ":s" => return Lresume::Step,
+                ":next" | ":n" => return Lresume::Next,
+                ":continue" | ":c" => return Lresume::Continue,
+                ":abort" => return Lresume::Abort,
+                ":where" => say(&stop.to_string()),
+                ":break" => say(&add_breakpoint(context.debugger(), arg)),
+                ":unbreak" => say(&remove_breakpoint(context.debugger(), arg)),
+                ":help" => say(DEBUG_HELP),
+                command if command.starts_with(':') => say(&format!(
+                    "'{}' can't be used while stopped in the debugger, see :help",
+                    command
+                )),
+                _ => say(&eval_rispreter(&stop.env, line).to_string()),
+            }
+        }
+        Lresume::Continue
+    })
+}
+
+/// `:break`, adding a breakpoint on a function or listing them without one
+pub fn add_breakpoint(debugger: &Ldebugger, name: &str) -> String {
+    if name.is_empty() {
+        let breakpoints = debugger.breakpoints();
+        if breakpoints.is_empty() {
+            return "no breakpoints".to_string();
+        }
+        return breakpoints.join("\n");
+    }
+    debugger.add_breakpoint(name);
+    format!("breakpoint on {}", name)
+}
+
+/// `:unbreak`, removing the breakpoint on a function or all of them without one
+pub fn remove_breakpoint(debugger: &Ldebugger, name: &str) -> String {
+    if name.is_empty() {
+        debugger.clear_breakpoints();
+        "removed all the breakpoints".to_string()
+    } else if debugger.remove_breakpoint(name) {
+        format!("removed the breakpoint on {}", name)
+    } else {
+        format!("there's no breakpoint on {}", name)
+    }
+}
@@ -0,0 +1,193 @@
+use crate::lval::lval_def::{Lval, LvalType};
+use crate::lval::lval_doc::BUILTIN_DOCS;
+
+const RESET: &str = "\x1b[0m";
+const BUILTIN: &str = "\x1b[1;34m";
+const NUMBER: &str = "\x1b[33m";
+const STRING: &str = "\x1b[32m";
+const CHAR: &str = "\x1b[36m";
+const COMMENT: &str = "\x1b[90m";
+const ERROR: &str = "\x1b[31m";
+/// brackets take the color of their depth, so the matching ones look the same
+const BRACKETS: [&str; 4] = ["\x1b[95m", "\x1b[96m", "\x1b[93m", "\x1b[92m"];
+
+/// Width the results are laid out in before their elements go one per line
+pub const WIDTH: usize = 80;
+
+/// Colors an input with ANSI escapes, by the token it is made of: builtins,
+/// numbers and booleans, strings, chars, comments and brackets. Whatever it
+/// can't read, like an unclosed string, is colored up to the end
+pub fn highlight(input: &str) -> String {
+    let mut out = String::with_capacity(input.len() * 2);
+    let mut depth = 0;
+    let mut rest = input;
+    while let Some(c) = rest.chars().next() {
+        let (len, color) = match c {
+            ';' => (rest.find('\n').unwrap_or(rest.len()), Some(COMMENT)),
+            '"' => (quoted(rest, '"'), Some(STRING)),
+            '\'' => (quoted(rest, '\''), Some(CHAR)),
+            'r' if raw_string(rest).is_some() => (raw_string(rest).unwrap(), Some(STRING)),
+            '(' | '{' | '[' | '#' if opener(rest) > 0 => {
+                depth += 1;
+                (opener(rest), Some(BRACKETS[(depth - 1) % BRACKETS.len()]))
+            }
+            ')' | '}' | ']' => {
+                depth = depth.max(1) - 1;
+                (1, Some(BRACKETS[depth % BRACKETS.len()]))
+            }
+            c if c.is_whitespace() => (c.len_utf8(), None),
+            _ => {
+                let len = rest.find(ends_word).unwrap_or(rest.len()).max(1);
+                (len, word_color(&rest[..len]))
+            }
+        };
+        match color {
+            Some(color) => {
+                out.push_str(color);
+                out.push_str(&rest[..len]);
+                out.push_str(RESET);
+            }
+            None => out.push_str(&rest[..len]),
+        }
+        rest = &rest[len..];
+    }
+    out
+}
+
+/// Lays a value out as it prints, with the elements of the S-expressions and
+/// Q-expressions that don't fit in `width` one per line, indented by depth
+pub fn indent(lval: &Lval, width: usize) -> String {
+    let mut out = String::new();
+    layout(lval, width, 0, &mut out);
+    out
+}
+
+/// Lays a result out with `indent` and colors it, errors all in red
+pub fn pretty(lval: &Lval) -> String {
+    match lval.ltype {
+        LvalType::LVAL_ERR(_) => format!("{}{}{}", ERROR, lval, RESET),
+        _ => highlight(&indent(lval, WIDTH)),
+    }
+}
+
+fn layout(lval: &Lval, width: usize, depth: usize, out: &mut String) {
+    let flat = lval.to_string();
+    let (open, close) = match lval.ltype {
+        LvalType::LVAL_SEXPR => ("(", ")"),
+        LvalType::LVAL_QEXPR => ("{", "}"),
+        _ => return out.push_str(&flat),
+    };
+    if lval.cell.is_empty() || depth * 2 + flat.chars().count() <= width {
+        return out.push_str(&flat);
+    }
+    out.push_str(open);
+    for (i, child) in lval.cell.iter().enumerate() {
+        if i > 0 {
+            out.push('\n');
+            out.push_str(&"  ".repeat(depth + 1));
+        }
+        layout(child, width, depth + 1, out);
+    }
+    out.push_str(close);
+}
+
+/// Length of a string or char literal, up to its closing quote
+fn quoted(input: &str, quote: char) -> usize {
+    let mut chars = input.char_indices().skip(1);
+    while let Some((i, c)) = chars.next() {
+        if c == '\\' {
+            chars.next();
+        } else if c == quote {
+            return i + 1;
+        }
+    }
+    input.len()
+}
+
+/// Length of a raw string literal, `r"..."` or `r#"..."#`
+fn raw_string(input: &str) -> Option<usize> {
+    let hashes = input[1..].chars().take_while(|c| *c == '#').count();
+    if !input[1 + hashes..].starts_with('"') {
+        return None;
+    }
+    let closing = format!("\"{}", "#".repeat(hashes));
+    let start = 2 + hashes;
+    Some(match input[start..].find(&closing) {
+        Some(end) => start + end + closing.len(),
+        None => input.len(),
+    })
+}
+
+/// Length of an opening bracket, with the `#` of vectors, maps and sets
+fn opener(input: &str) -> usize {
+    ["#s{", "#{", "#[", "(", "{", "["]
+        .iter()
+        .find(|open| input.starts_with(*open))
+        .map_or(0, |open| open.len())
+}
+
+fn ends_word(c: char) -> bool {
+    c.is_whitespace() || "(){}[]\"';".contains(c)
+}
+
+fn word_color(word: &str) -> Option<&'static str> {
+    let numeric = word
+        .trim_start_matches('-')
+        .starts_with(|c: char| c.is_ascii_digit());
+    if (numeric && word.parse::<f64>().is_ok()) || word == "true" || word == "false" {
+        Some(NUMBER)
+    } else if BUILTIN_DOCS.iter().any(|(builtin, _, _)| *builtin == word) {
+        Some(BUILTIN)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eval::eval_rispreter;
+    use crate::lval::lval_env::Lenv;
+
+    #[test]
+    fn highlight_tokens() {
+        let colored = |color: &str, text: &str| format!("{}{}{}", color, text, RESET);
+        assert_eq!(
+            [
+                colored(BRACKETS[0], "("),
+                colored(BUILTIN, "str-concat"),
+                " ".to_string(),
+                colored(STRING, "\"a \\\" (\""),
+                " ".to_string(),
+                colored(CHAR, "'b'"),
+                " ".to_string(),
+                colored(STRING, "r#\"c\"#"),
+                " ".to_string(),
+                colored(BRACKETS[1], "#["),
+                colored(NUMBER, "-1.5"),
+                colored(BRACKETS[1], "]"),
+                " x ".to_string(),
+                colored(NUMBER, "true"),
+                colored(BRACKETS[0], ")"),
+                " ".to_string(),
+                colored(COMMENT, "; (not code)"),
+            ]
+            .concat(),
+            highlight("(str-concat \"a \\\" (\" 'b' r#\"c\"# #[-1.5] x true) ; (not code)")
+        );
+        assert_eq!(colored(STRING, "\"open"), highlight("\"open"));
+        assert_eq!("list-of", highlight("list-of"));
+    }
+
+    #[test]
+    fn indent_nested_results() {
+        let env = Lenv::new();
+        let lval = eval_rispreter(&env, "{a {b c d} {e {f g}}}");
+        assert_eq!("{a {b c d} {e {f g}}}", indent(&lval, 80));
+        assert_eq!("{a\n  {b c d}\n  {e {f g}}}", indent(&lval, 12));
+        assert_eq!(
+            "{a\n  {b\n    c\n    d}\n  {e\n    {f\n      g}}}",
+            indent(&lval, 4)
+        );
+    }
+}
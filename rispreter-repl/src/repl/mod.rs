@@ -1,9 +1,19 @@
-use crate::eval::eval_rispreter;
+use crate::eval::{eval_rispreter, eval_rispreter_file, eval_rispreter_program};
+use crate::lsp::LspServer;
+use crate::lval::lval_builtin::Lbuiltin;
+use crate::lval::lval_context::Lcapabilities;
+use crate::lval::lval_def::{Lval, LvalType};
 use crate::lval::lval_env::Lenv;
-use std::fs::File;
+use crate::lval::lval_error::Lerror;
+use crate::lval::lval_eval::lval_eval;
+use crate::lval::lval_image;
+use rispreter_parser::format::format_program;
+use std::cell::{Cell, RefCell};
 use std::io::prelude::*;
-use std::path::Path;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 
 use linefeed::*;
@@ -11,127 +21,774 @@ use std::io;
 
 use clap::App;
 
+mod commands;
+mod debug;
+pub mod highlight;
+
+/// The standard library, loaded once per session
+pub const PRELUDE: &str = include_str!("prelude.rspr");
+
+/// Personal startup file of the REPL, in the home directory
+pub const RC_FILE: &str = ".rispreterrc.rspr";
+
+/// Where the inputs of the REPL are kept between sessions, in the home directory
+pub const HISTORY_FILE: &str = ".rispreter_history";
+
+/// How many inputs the history file keeps
+pub const HISTORY_SIZE: usize = 1000;
+
+/// The prompt of the REPL, unless the rc file binds `repl-prompt` to a string.
+/// `{}` is replaced by the number of the input
+pub const PROMPT: &str = "[{}]> ";
+
+/// How the results of one-shot expressions are printed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PrintMode {
+    /// as `print` does, strings without quotes and nothing for `()`
+    Display,
+    /// one value per line, as the reader reads it back
+    Readable,
+}
+
+/// Where the standard library of a session comes from
+#[derive(Debug, Clone, PartialEq)]
+enum PreludeSource {
+    Shipped,
+    File(PathBuf),
+    Skipped,
+}
+
 #[derive(Default)]
 pub struct RispRepl {
-    env: Rc<Lenv>,
+    env: RefCell<Rc<Lenv>>,
+    prelude: RefCell<Option<PreludeSource>>,
+    inputs: RefCell<Vec<String>>,
+    color: Cell<bool>,
 }
 
 impl RispRepl {
     pub fn new() -> Self {
-        RispRepl { env: Lenv::new() }
+        let repl = RispRepl {
+            env: RefCell::new(Lenv::new()),
+            prelude: RefCell::new(None),
+            inputs: RefCell::new(Vec::new()),
+            color: Cell::new(false),
+        };
+        repl.bind_repl_builtins();
+        repl
+    }
+
+    /// Binds the builtins only the REPL has, like `out`
+    fn bind_repl_builtins(&self) {
+        for builtin in Lbuiltin::repl_builtins() {
+            self.env()
+                .def(builtin.name().to_string(), Lval::lval_fun(builtin))
+                .unwrap();
+        }
+    }
+
+    /// The global env of the session
+    pub fn env(&self) -> Rc<Lenv> {
+        Rc::clone(&self.env.borrow())
+    }
+
+    /// Sets where the output of `print` and `println` goes, stdout by default
+    pub fn set_output(&self, output: Box<dyn Write>) {
+        self.env().context().set_output(output);
+    }
+
+    /// Sets what the session is allowed to do outside the interpreter
+    pub fn set_capabilities(&self, capabilities: Lcapabilities) {
+        self.env().context().set_capabilities(capabilities);
+    }
+
+    /// Binds `args` to the arguments given to the script, as a Q-expression of strings
+    pub fn set_args(&self, args: Vec<String>) {
+        let mut q = Lval::lval_qexpr();
+        for arg in args {
+            q.add_cell(Lval::lval_string(arg));
+        }
+        self.env().def("args".to_string(), q).unwrap();
+    }
+
+    /// Sets if the inputs and results of the REPL are colored, and the nested
+    /// results laid out over several lines
+    pub fn set_color(&self, color: bool) {
+        self.color.set(color);
+    }
+
+    /// Sets the directories `import` looks modules up in
+    pub fn set_search_path(&self, search_path: Vec<PathBuf>) {
+        self.env().context().set_search_path(search_path);
+    }
+
+    /// Loads the standard library shipped with rispreter, unless the session
+    /// already has one
+    pub fn load_prelude(&self) -> Lval {
+        if self.prelude.borrow().is_some() {
+            return Lval::lval_sexpr();
+        }
+        *self.prelude.borrow_mut() = Some(PreludeSource::Shipped);
+        let res = eval_rispreter_program(&self.env(), PRELUDE);
+        self.env().context().set_prelude(self.env().bindings());
+        res
+    }
+
+    /// Loads a file as the standard library, instead of the shipped one
+    pub fn load_prelude_file(&self, path: &Path) -> Lval {
+        *self.prelude.borrow_mut() = Some(PreludeSource::File(path.to_path_buf()));
+        let res = eval_rispreter_file(&self.env(), path);
+        self.env().context().set_prelude(self.env().bindings());
+        res
+    }
+
+    /// Starts the session without a standard library
+    pub fn skip_prelude(&self) {
+        *self.prelude.borrow_mut() = Some(PreludeSource::Skipped);
+    }
+
+    /// Evaluates a startup file of the REPL, if it exists, so what it defines
+    /// is there for the session. Its errors don't stop the REPL
+    pub fn load_rc(&self, path: &Path) -> Lval {
+        if !path.exists() {
+            return Lval::lval_sexpr();
+        }
+        eval_rispreter_file(&self.env(), path)
+    }
+
+    /// The prompt to read the next input with, `repl-prompt` when it is a string,
+    /// with the number the input will have
+    pub fn prompt(&self) -> String {
+        let n = self.env().context().results() + 1;
+        let prompt = match self
+            .env()
+            .get("repl-prompt".to_string())
+            .map(|val| val.ltype)
+        {
+            Ok(LvalType::LVAL_STRING(prompt)) => prompt,
+            _ => PROMPT.to_string(),
+        };
+        prompt.replace("{}", &n.to_string())
+    }
+
+    /// Sets where the REPL reads what to do from when the evaluation stops in
+    /// the debugger, a line at a time. Without it the evaluation doesn't stop
+    pub fn set_debugger_input(&self, input: impl FnMut() -> Option<String> + 'static) {
+        self.env()
+            .context()
+            .debugger()
+            .set_handler(debug::handler(input));
+    }
+
+    /// Binds what an image saved with `:save-image` holds in the global env
+    pub fn load_image(&self, path: &Path) -> Lval {
+        match std::fs::read_to_string(path) {
+            Ok(image) => lval_image::load_image(&self.env(), &image),
+            Err(err) => Lval::lval_err(Lerror::IoError {
+                path: path.display().to_string(),
+                msg: err.to_string(),
+            }),
+        }
+    }
+
+    /// Starts the session over with a new env and the prelude it had. Script
+    /// arguments stay bound
+    pub fn reset(&self) -> Lval {
+        let env = self.env();
+        let args = env.get("args".to_string()).unwrap();
+        let context = env.context();
+        context.reset();
+        *self.env.borrow_mut() = Lenv::with_context(context);
+        self.inputs.borrow_mut().clear();
+        self.bind_repl_builtins();
+
+        let prelude = self.prelude.borrow_mut().take();
+        let res = match prelude {
+            Some(PreludeSource::Shipped) => self.load_prelude(),
+            Some(PreludeSource::File(path)) => self.load_prelude_file(&path),
+            Some(PreludeSource::Skipped) => {
+                self.skip_prelude();
+                Lval::lval_sexpr()
+            }
+            None => Lval::lval_sexpr(),
+        };
+        if let LvalType::LVAL_ERR(_) = args.ltype {
+        } else {
+            self.env().def("args".to_string(), args).unwrap();
+        }
+        res
+    }
+
+    /// Evaluates an input of the REPL, remembering it for `:save` unless it failed.
+    /// Its result is numbered for `out` and bound to `*1`, the ones before moving
+    /// to `*2` and `*3`, or to `*e` if it's an error
+    pub fn eval_input(&self, input: &str) -> Lval {
+        let env = self.env();
+        let res = eval_rispreter(&env, input);
+        // stepping ends with the input, the next one runs up to a breakpoint
+        env.context().debugger().stop_stepping();
+        env.context().add_result(res.clone());
+        if let LvalType::LVAL_ERR(_) = res.ltype {
+            env.def("*e".to_string(), res.clone()).unwrap();
+        } else {
+            self.inputs.borrow_mut().push(input.trim().to_string());
+            for (from, to) in &[("*2", "*3"), ("*1", "*2")] {
+                let val = env.get(from.to_string()).unwrap();
+                if let LvalType::LVAL_ERR(_) = val.ltype {
+                } else {
+                    env.def(to.to_string(), val).unwrap();
+                }
+            }
+            env.def("*1".to_string(), res.clone()).unwrap();
+        }
+        res
+    }
+
+    /// Evaluates a script, or stdin for `-`, without printing what each form gives
+    /// back. Returns the status the process should exit with: the one asked for
+    /// with `exit`, 1 if the script failed, with the error on stderr, or 0
+    pub fn run_script(&self, file: &str) -> i32 {
+        let res = if file == "-" {
+            let mut program = String::new();
+            match io::stdin().read_to_string(&mut program) {
+                Ok(_) => eval_rispreter_program(&self.env(), &program),
+                Err(err) => Lval::lval_err(Lerror::IoError {
+                    path: "<stdin>".to_string(),
+                    msg: err.to_string(),
+                }),
+            }
+        } else {
+            eval_rispreter_file(&self.env(), Path::new(file))
+        };
+        match res.ltype {
+            LvalType::LVAL_ERR(Lerror::Exit { code }) => code,
+            LvalType::LVAL_ERR(_) => {
+                eprintln!("{}", res);
+                1
+            }
+            _ => 0,
+        }
+    }
+
+    /// Runs a script as `run_script` does while profiling it. The table of the
+    /// functions goes to stderr, the folded stacks to the `folded` file
+    pub fn run_profile(&self, file: &str, folded: &Path) -> i32 {
+        let context = self.env().context();
+        let profiler = context.profiler();
+        profiler.start();
+        let status = self.run_script(file);
+        let profile = profiler.stop();
+        eprintln!("{}", profile);
+        if let Err(err) = std::fs::write(folded, profile.folded()) {
+            eprintln!("Can't write the stacks to {}: {}", folded.display(), err);
+            return 1;
+        }
+        status
+    }
+
+    /// Runs a script as `run_script` does while recording its coverage. The
+    /// listing of the files it evaluated goes to stderr, the lcov report to the
+    /// `lcov` file
+    pub fn run_coverage(&self, file: &str, lcov: &Path) -> i32 {
+        let context = self.env().context();
+        let coverage = context.coverage();
+        coverage.start();
+        let status = self.run_script(file);
+        let report = coverage.stop();
+        eprintln!("{}", report);
+        if let Err(err) = std::fs::write(lcov, report.lcov()) {
+            eprintln!("Can't write the coverage to {}: {}", lcov.display(), err);
+            return 1;
+        }
+        status
+    }
+
+    /// Runs the tests defined with `deftest` in `*_test.rspr` files, looked for in
+    /// directories or given directly. A file is evaluated once, and every test runs
+    /// in a session of its own with a copy of what the file bound, so it doesn't
+    /// see what the others did. Prints if every test passed, and returns 1 if one
+    /// failed
+    pub fn run_tests(&self, paths: &[&str]) -> i32 {
+        let files = match test_files(paths) {
+            Ok(files) => files,
+            Err(err) => {
+                eprintln!("Can't look for the tests: {}", err);
+                return 1;
+            }
+        };
+        let (mut passed, mut failed) = (0, 0);
+        for file in &files {
+            let (loaded, tests) = match self.load_tests(file) {
+                Ok(loaded) => loaded,
+                Err(err) => {
+                    let err = err.to_string().replace('\n', "\n  ");
+                    println!("FAILED {}\n  {}", file.display(), err);
+                    failed += 1;
+                    continue;
+                }
+            };
+            let bindings = loaded.env().bindings();
+            for (name, mut body) in tests {
+                let session = self.test_session();
+                for (sym, val) in &bindings {
+                    session.env().put(sym.clone(), val.clone()).unwrap();
+                }
+                body.ltype = LvalType::LVAL_SEXPR;
+                let res = lval_eval(&session.env(), &mut body);
+                if let LvalType::LVAL_ERR(err) = res.ltype {
+                    let err = err.to_string().replace('\n', "\n  ");
+                    println!("FAILED {}: {}\n  {}", file.display(), name, err);
+                    failed += 1;
+                } else {
+                    println!("ok     {}: {}", file.display(), name);
+                    passed += 1;
+                }
+            }
+        }
+        println!("{} passed; {} failed", passed, failed);
+        if failed > 0 {
+            1
+        } else {
+            0
+        }
+    }
+
+    /// A new session for tests, where modules are looked for and what they can do
+    /// as in this one
+    fn test_session(&self) -> RispRepl {
+        let session = RispRepl::new();
+        let context = self.env().context();
+        session.set_search_path(context.search_path());
+        session.set_capabilities(context.capabilities());
+        session
+    }
+
+    /// A new session with the file of tests evaluated in it, and the tests it
+    /// defines, or the error it gave back
+    fn load_tests(&self, file: &Path) -> Result<(RispRepl, Vec<(String, Lval)>), Lerror> {
+        let session = self.test_session();
+        let prelude = self.prelude.borrow().clone();
+        let res = match prelude {
+            Some(PreludeSource::File(path)) => session.load_prelude_file(&path),
+            Some(PreludeSource::Skipped) => {
+                session.skip_prelude();
+                Lval::lval_sexpr()
+            }
+            _ => session.load_prelude(),
+        };
+        if let LvalType::LVAL_ERR(err) = res.ltype {
+            return Err(err);
+        }
+        let res = eval_rispreter_file(&session.env(), file);
+        if let LvalType::LVAL_ERR(err) = res.ltype {
+            return Err(err);
+        }
+        let tests = session.env().context().take_tests();
+        Ok((session, tests))
+    }
+
+    /// Evaluates expressions one after the other and prints their results. Returns
+    /// the status the process should exit with, as `run_script` does
+    pub fn run_expressions(&self, exprs: &[&str], mode: PrintMode) -> i32 {
+        for expr in exprs {
+            let res = eval_rispreter_program(&self.env(), expr);
+            let text = match (&res.ltype, mode) {
+                (LvalType::LVAL_ERR(Lerror::Exit { code }), _) => return *code,
+                (LvalType::LVAL_ERR(_), _) => {
+                    eprintln!("{}", res);
+                    return 1;
+                }
+                (LvalType::LVAL_SEXPR, PrintMode::Display) if res.cell.is_empty() => continue,
+                (_, PrintMode::Display) => res.to_print_string(),
+                (_, PrintMode::Readable) => res.to_string(),
+            };
+            if self.env().context().write(&(text + "\n")).is_err() {
+                return 1;
+            }
+        }
+        0
     }
 
     pub fn run_instruction(&self, i: &str) {
-        let prelude = "(fun {flip f a b} {f b a})
-(fun {ghost & xs} {eval xs})
-(fun {comp f g x} {f (g x)})
-(fun {len l} { if (== l nil) {0} {+ 1 (len (tail l))} })
-(fun {foldl f z l} { if (== l nil) {z} {foldl f (f z (fst l)) (tail l)} })
-(fun {sum l} {foldl + 0 l})
-(fun {product l} {foldl * 1 l})
-(def {otherwise} true)
-(fun {case x & cs} { if (== cs nil) {error \"No Case Found\"} {if (== x (fst (fst cs))) {snd (fst cs)} { unpack case (join (list x) (tail cs))}} })
-(fun {fib n} { select { (== n 0) 0 } { (== n 1) 1 } { otherwise (+ (fib (- n 1)) (fib (- n 2))) } })
-".to_string();
-        for lines in prelude.lines() {
-            eval_rispreter(&self.env, &lines);
-        }
-        println!("{}", eval_rispreter(&self.env, i));
+        self.load_prelude();
+        println!("{}", eval_rispreter(&self.env(), i));
     }
 
     pub fn run(&self) -> io::Result<()> {
-        let prelude = "(fun {flip f a b} {f b a})
-(fun {ghost & xs} {eval xs})
-(fun {comp f g x} {f (g x)})
-(fun {len l} { if (== l nil) {0} {+ 1 (len (tail l))} })
-(fun {foldl f z l} { if (== l nil) {z} {foldl f (f z (fst l)) (tail l)} })
-(fun {sum l} {foldl + 0 l})
-(fun {product l} {foldl * 1 l})
-(def {otherwise} true)
-(fun {case x & cs} { if (== cs nil) {error \"No Case Found\"} {if (== x (fst (fst cs))) {snd (fst cs)} { unpack case (join (list x) (tail cs))}} })
-(fun {fib n} { select { (== n 0) 0 } { (== n 1) 1 } { otherwise (+ (fib (- n 1)) (fib (- n 2))) } })
-".to_string();
-
         //Lbuiltin::add_builtins(&self.env);
         let yaml = load_yaml!("cli.yml");
         let matches = App::from_yaml(yaml).get_matches();
+        if let Some(fmt) = matches.subcommand_matches("fmt") {
+            let width = match fmt.value_of("width").unwrap().parse() {
+                Ok(width) => width,
+                Err(_) => {
+                    eprintln!("--width needs a number of columns");
+                    std::process::exit(1);
+                }
+            };
+            let files: Vec<&str> = fmt.values_of("FILES").unwrap().collect();
+            std::process::exit(format_files(&files, width, fmt.is_present("check")));
+        }
+        // global options given after a subcommand only show up in its matches
+        let script = matches.subcommand_matches("run");
+        let lsp = matches.subcommand_matches("lsp");
+        let profile = matches.subcommand_matches("profile");
+        let coverage = matches.subcommand_matches("coverage");
+        let tests = matches.subcommand_matches("test");
+        let options = script
+            .or(lsp)
+            .or(profile)
+            .or(coverage)
+            .or(tests)
+            .unwrap_or(&matches);
         let target_file = matches.value_of("INPUT_FILE");
+        let mut search_path: Vec<PathBuf> = std::env::var_os("RISPRETER_PATH")
+            .map(|paths| std::env::split_paths(&paths).collect())
+            .unwrap_or_default();
+        if let Some(dirs) = options.values_of("include") {
+            search_path.extend(dirs.map(PathBuf::from));
+        }
+        self.set_search_path(search_path);
+        let one_shot = script.is_some()
+            || lsp.is_some()
+            || profile.is_some()
+            || coverage.is_some()
+            || tests.is_some()
+            || target_file == Some("-")
+            || matches.is_present("eval");
+        // before `args` is bound, so it isn't taken as part of the prelude
+        let prelude = if options.is_present("no-prelude") {
+            self.skip_prelude();
+            Lval::lval_sexpr()
+        } else if let Some(file) = options.value_of("prelude") {
+            self.load_prelude_file(Path::new(file))
+        } else {
+            self.load_prelude()
+        };
+        if let LvalType::LVAL_ERR(_) = prelude.ltype {
+            eprintln!("Error loading the prelude: {}", prelude);
+            if one_shot {
+                std::process::exit(1);
+            }
+        }
+        // over the prelude, and before the `args` of this session are bound
+        if let Some(image) = options.value_of("image") {
+            let res = self.load_image(Path::new(image));
+            if let LvalType::LVAL_ERR(_) = res.ltype {
+                eprintln!("Error loading the image '{}': {}", image, res);
+                if one_shot {
+                    std::process::exit(1);
+                }
+            }
+        }
+        self.set_args(
+            options
+                .values_of("ARGS")
+                .map(|args| args.map(String::from).collect())
+                .unwrap_or_default(),
+        );
+        // after the prelude, so its loading isn't traced
+        let context = self.env().context();
+        let tracer = context.tracer();
+        if let Some(file) = options.value_of("trace-file") {
+            match std::fs::File::create(file) {
+                Ok(file) => tracer.set_output(Box::new(file)),
+                Err(err) => {
+                    eprintln!("Can't write the trace to {}: {}", file, err);
+                    std::process::exit(1);
+                }
+            }
+        }
+        tracer.trace_all(options.is_present("trace"));
+        for file in options.values_of("library").into_iter().flatten() {
+            let res = eval_rispreter_file(&self.env(), Path::new(file));
+            if let LvalType::LVAL_ERR(_) = res.ltype {
+                eprintln!("Error loading '{}': {}", file, res);
+                if one_shot {
+                    std::process::exit(1);
+                }
+            }
+        }
+        if let Some(script) = script {
+            std::process::exit(self.run_script(script.value_of("FILE").unwrap()));
+        }
+        if let Some(profile) = profile {
+            std::process::exit(self.run_profile(
+                profile.value_of("FILE").unwrap(),
+                Path::new(profile.value_of("folded").unwrap()),
+            ));
+        }
+        if let Some(coverage) = coverage {
+            std::process::exit(self.run_coverage(
+                coverage.value_of("FILE").unwrap(),
+                Path::new(coverage.value_of("lcov").unwrap()),
+            ));
+        }
+        if let Some(tests) = tests {
+            let paths: Vec<&str> = tests.values_of("PATHS").unwrap().collect();
+            std::process::exit(self.run_tests(&paths));
+        }
+        if lsp.is_some() {
+            let stdin = io::stdin();
+            let status = LspServer::new(&self.env()).run(stdin.lock(), io::stdout())?;
+            std::process::exit(status);
+        }
+        if let Some(exprs) = matches.values_of("eval") {
+            let mode = match matches.value_of("print-mode") {
+                Some("readable") => PrintMode::Readable,
+                _ => PrintMode::Display,
+            };
+            std::process::exit(self.run_expressions(&exprs.collect::<Vec<_>>(), mode));
+        }
+        if target_file == Some("-") {
+            std::process::exit(self.run_script("-"));
+        }
+        if !options.is_present("no-rc") {
+            if let Some(rc) = home_dir().map(|home| home.join(RC_FILE)) {
+                let res = self.load_rc(&rc);
+                if let LvalType::LVAL_ERR(_) = res.ltype {
+                    eprintln!("Error loading '{}': {}", rc.display(), res);
+                }
+            }
+        }
+        self.set_color(match matches.value_of("color") {
+            Some("always") => true,
+            Some("never") => false,
+            _ => io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none(),
+        });
         match target_file {
             Some(filename) => {
-                let program = RispRepl::read_rispreter(filename);
-                println!("{}", program);
-                for lines in program.lines() {
-                    println!("{}", eval_rispreter(&self.env, &lines));
-                    // for child in self.env.children() {
-                    //     child.detach();
-                    // }
+                // the file is shown before it's loaded, `run` is the quiet way
+                if let Ok(program) = std::fs::read_to_string(filename) {
+                    println!("{}", program);
                 }
+                self.print_result(eval_rispreter_file(&self.env(), Path::new(filename)));
             }
             None => {
                 println!("Error in read file {:?}", target_file);
             }
         }
 
-        println!("loading prelude library: ");
-        for lines in prelude.lines() {
-            println!("{}", eval_rispreter(&self.env, &lines));
-        }
         println!("enjoy!");
         println!("_______________________________________________");
-        println!("ctrl-d do quit\nctrl-l to clear buffer");
+        println!("ctrl-d do quit\nctrl-l to clear buffer\n:help for the REPL commands");
         println!("_______________________________________________");
 
-        let interface = Interface::new("risp-repl")?;
+        let interface = Arc::new(Interface::new("risp-repl")?);
 
-        interface.set_prompt("> ")?;
+        interface.set_history_size(HISTORY_SIZE);
+        let history = home_dir().map(|home| home.join(HISTORY_FILE));
+        if let Some(history) = &history {
+            // there's no history yet on the first session
+            let _ = interface.load_history(history);
+        }
 
-        interface.define_function("enter-function", Arc::new(EnterFunction));
+        let enter = Arc::new(EnterFunction {
+            color: self.color.get(),
+            prompt_width: AtomicUsize::new(0),
+        });
+        let set_prompt = |prompt: String| {
+            enter
+                .prompt_width
+                .store(prompt.chars().count(), Ordering::Relaxed);
+            interface.set_prompt(&prompt)
+        };
+        interface.define_function("enter-function", enter.clone());
         interface.bind_sequence("\r", Command::from_str("enter-function"));
         interface.bind_sequence("\n", Command::from_str("enter-function"));
 
         interface.define_function("tab-function", Arc::new(TabFunction));
         interface.bind_sequence("\t", Command::from_str("tab-function"));
+        interface.set_variable("blink-matching-paren", "on");
+
+        let (debug_interface, debug_enter) = (Arc::clone(&interface), Arc::clone(&enter));
+        self.set_debugger_input(move || {
+            debug_enter
+                .prompt_width
+                .store(debug::DEBUG_PROMPT.chars().count(), Ordering::Relaxed);
+            debug_interface.set_prompt(debug::DEBUG_PROMPT).ok()?;
+            match debug_interface.read_line() {
+                Ok(ReadResult::Input(line)) => Some(line),
+                _ => None,
+            }
+        });
 
+        set_prompt(self.prompt())?;
+        // the status an `exit` asked for, the session ends once the history is saved
+        let mut status = None;
         while let ReadResult::Input(line) = interface.read_line()? {
-            println!("{}", eval_rispreter(&self.env, &line));
+            if line.trim_start().starts_with(':') {
+                if !self.run_command(&line) {
+                    break;
+                }
+            } else {
+                let res = self.eval_input(&line);
+                if let LvalType::LVAL_ERR(Lerror::Exit { code }) = res.ltype {
+                    status = Some(code);
+                } else {
+                    self.print_result(res);
+                }
+            }
             // for child in self.env.children() {
             //     child.detach();
             // }
             if !line.trim().is_empty() {
                 interface.add_history_unique(line);
             }
+            if status.is_some() {
+                break;
+            }
+            set_prompt(self.prompt())?;
         }
 
+        if let Some(history) = &history {
+            if let Err(err) = interface.save_history(history) {
+                eprintln!("Can't save the history to {}: {}", history.display(), err);
+            }
+        }
+        if let Some(code) = status {
+            std::process::exit(code);
+        }
         Ok(())
     }
 
-    fn read_rispreter(tmp: &str) -> String {
-        let filename = Path::new(tmp);
-        match File::open(Path::new(&filename)) {
-            Ok(mut fh) => {
-                let mut contents = String::new();
-                match fh.read_to_string(&mut contents) {
-                    Ok(_) => contents,
-                    Err(err) => {
-                        println!("There was an error reading file: {:?}", err);
-                        std::process::exit(1);
-                    }
-                }
-            }
+    /// Prints what was evaluated, or ends the process if it asked to `exit`
+    fn print_result(&self, lval: Lval) {
+        if let LvalType::LVAL_ERR(Lerror::Exit { code }) = lval.ltype {
+            std::process::exit(code);
+        }
+        if self.color.get() {
+            println!("{}", highlight::pretty(&lval));
+        } else {
+            println!("{}", lval);
+        }
+    }
+}
+
+/// Formats .rspr files in place, or stdin to stdout for `-`. With `check` they
+/// are only compared with how they'd be formatted. Returns the status the process
+/// should exit with, 1 if a file can't be read or parsed, or isn't formatted
+/// when checking
+pub fn format_files(files: &[&str], width: usize, check: bool) -> i32 {
+    let mut status = 0;
+    for file in files {
+        let mut source = String::new();
+        let read = if *file == "-" {
+            io::stdin().read_to_string(&mut source).map(|_| ())
+        } else {
+            std::fs::read_to_string(file).map(|text| source = text)
+        };
+        if let Err(err) = read {
+            eprintln!("Can't read {}: {}", file, err);
+            status = 1;
+            continue;
+        }
+        let formatted = match format_program(&source, width) {
+            Ok(formatted) => formatted,
             Err(err) => {
-                println!("File not found: {:?}", err);
-                std::process::exit(1);
+                eprintln!("Can't format {}: {}", file, err);
+                status = 1;
+                continue;
+            }
+        };
+        if check {
+            if formatted != source {
+                eprintln!("{} isn't formatted", file);
+                status = 1;
+            }
+        } else if *file == "-" {
+            print!("{}", formatted);
+        } else if formatted != source {
+            if let Err(err) = std::fs::write(file, formatted) {
+                eprintln!("Can't write {}: {}", file, err);
+                status = 1;
+            }
+        }
+    }
+    status
+}
+
+/// The `*_test.rspr` files in directories and the ones below them, and the files
+/// given, in order
+fn test_files(paths: &[&str]) -> io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for path in paths {
+        let path = Path::new(path);
+        if !path.is_dir() {
+            files.push(path.to_path_buf());
+            continue;
+        }
+        let mut dirs = vec![path.to_path_buf()];
+        while let Some(dir) = dirs.pop() {
+            for entry in std::fs::read_dir(&dir)? {
+                let path = entry?.path();
+                if path.is_dir() {
+                    dirs.push(path);
+                } else if path.to_string_lossy().ends_with("_test.rspr") {
+                    files.push(path);
+                }
             }
         }
     }
+    files.sort();
+    files.dedup();
+    Ok(files)
 }
 
-struct EnterFunction;
+/// Home directory of the user, where the rc and history files are
+fn home_dir() -> Option<PathBuf> {
+    std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .filter(|home| !home.is_empty())
+        .map(PathBuf::from)
+}
+
+struct EnterFunction {
+    /// redraws the accepted inputs highlighted
+    color: bool,
+    /// width of the prompt the input is read after
+    prompt_width: AtomicUsize,
+}
+
+impl EnterFunction {
+    /// Writes the input just accepted over itself, highlighted. The line editor
+    /// only draws plain text, so it's left where it was and gone back to
+    fn redraw<Term: Terminal>(&self, prompter: &mut Prompter<Term>) -> io::Result<()> {
+        let input = prompter.buffer().to_string();
+        let columns = prompter.screen_size().columns;
+        if columns == 0 {
+            // without the size of the screen there's no telling where the input is
+            return Ok(());
+        }
+        let prompt_width = self.prompt_width.load(Ordering::Relaxed);
+        let rows: usize = input
+            .split('\n')
+            .enumerate()
+            .map(|(i, line)| {
+                let width = line.chars().count() + if i == 0 { prompt_width } else { 0 };
+                width.div_ceil(columns).max(1)
+            })
+            .sum();
+        let mut redraw = format!("\x1b[{}A\r", rows);
+        if prompt_width > 0 {
+            redraw.push_str(&format!("\x1b[{}C", prompt_width));
+        }
+        redraw.push_str(&highlight::highlight(&input).replace('\n', "\r\n"));
+        redraw.push_str("\r\n");
+        // the prompt isn't drawn anymore, so the writer doesn't touch it
+        prompter.writer_append()?.write_str(&redraw)
+    }
+}
 
 impl<Term: Terminal> Function<Term> for EnterFunction {
     fn execute(&self, prompter: &mut Prompter<Term>, count: i32, _ch: char) -> io::Result<()> {
-        if prompter.buffer().ends_with('.') {
-            prompter.accept_input()
+        // meta-commands are a single line, they don't wait for the closing '.'
+        if prompter.buffer().ends_with('.') || prompter.buffer().trim_start().starts_with(':') {
+            prompter.accept_input()?;
+            if self.color {
+                self.redraw(prompter)?;
+            }
+            Ok(())
         } else if count > 0 {
             prompter.insert(count as usize, '\n')?;
             prompter.insert(2, ' ')
@@ -151,3 +808,173 @@ impl<Term: Terminal> Function<Term> for TabFunction {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    #[test]
+    fn prelude_loads_once() {
+        let repl = RispRepl::new();
+        assert_eq!(Lval::lval_sexpr(), repl.load_prelude());
+        assert_eq!(55f64, eval_rispreter(&repl.env(), "(fib 10)"));
+
+        eval_rispreter(&repl.env(), "(def {otherwise} false)");
+        repl.load_prelude();
+        assert_eq!(
+            Lval::lval_bool(false),
+            eval_rispreter(&repl.env(), "otherwise")
+        );
+    }
+
+    #[test]
+    fn run_script_exit_status() {
+        let dir = TempDir::new("run");
+        let script = |name: &str, program: &str| {
+            let path = dir.join(name);
+            std::fs::write(&path, program).unwrap();
+            path.display().to_string()
+        };
+
+        let repl = RispRepl::new();
+        repl.load_prelude();
+        let ok = script(
+            "ok.rspr",
+            "#!/usr/bin/env -S rispreter run\n(def {x} (fib 5))",
+        );
+        assert_eq!(0, repl.run_script(&ok));
+        assert_eq!(5f64, eval_rispreter(&repl.env(), "x"));
+        assert_eq!(
+            1,
+            repl.run_script(&script("fails.rspr", "(def {y} 1)\n(/ y 0)\n(def {y} 2)"))
+        );
+        assert_eq!(1f64, eval_rispreter(&repl.env(), "y"));
+        assert_eq!(3, repl.run_script(&script("exits.rspr", "(exit 3)")));
+    }
+
+    #[test]
+    fn run_tests_in_sessions_of_their_own() {
+        let dir = TempDir::new("test");
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        std::fs::write(
+            dir.join("lib.rspr"),
+            "(deftest \"not a test file\" {assert false})",
+        )
+        .unwrap();
+        let tests = dir.join("sub").join("count_test.rspr");
+        let log = dir.join("log").display().to_string();
+        std::fs::write(
+            &tests,
+            format!("(append-file {:?} \"loaded \")\n", log)
+                + "(def {n} 0)
+(deftest \"defines\" {do (assert-eq 0 n) (def {n} 1)})
+(deftest \"doesn't see it\" {assert-eq 0 n})
+(deftest \"fails\" {assert-error {+ n 1}})",
+        )
+        .unwrap();
+        let dir_path = dir.path().display().to_string();
+        assert_eq!(vec![tests.clone()], test_files(&[&dir_path]).unwrap());
+
+        let repl = RispRepl::new();
+        repl.load_prelude();
+        assert_eq!(1, repl.run_tests(&[&dir_path]));
+        assert_eq!("loaded ", std::fs::read_to_string(&log).unwrap());
+        std::fs::write(&tests, "(deftest \"passes\" {assert (== 1 1)})").unwrap();
+        assert_eq!(0, repl.run_tests(&[&dir_path]));
+    }
+
+    #[test]
+    fn format_files_checks_and_rewrites() {
+        let dir = TempDir::new("fmt");
+        let file = dir.join("double.rspr");
+        let path = file.display().to_string();
+        std::fs::write(&file, "; double\n(fun {double x}   { * 2 x }).\n").unwrap();
+        assert_eq!(1, format_files(&[&path], 80, true));
+        assert_eq!(0, format_files(&[&path], 80, false));
+        assert_eq!(
+            "; double\n(fun {double x} {* 2 x})\n",
+            std::fs::read_to_string(&file).unwrap()
+        );
+        assert_eq!(0, format_files(&[&path], 80, true));
+
+        std::fs::write(&file, "(fun {double x}").unwrap();
+        assert_eq!(1, format_files(&[&path], 80, false));
+        assert_eq!("(fun {double x}", std::fs::read_to_string(&file).unwrap());
+    }
+
+    #[test]
+    fn run_expressions_print_modes() {
+        use crate::lval::lval_context::CapturedOutput;
+
+        let repl = RispRepl::new();
+        repl.load_prelude();
+        let output = CapturedOutput::new();
+        repl.set_output(Box::new(output.clone()));
+        let exprs = ["(sum {1 2 3})", "(def {s} \"a b\")", "s"];
+        assert_eq!(0, repl.run_expressions(&exprs, PrintMode::Display));
+        assert_eq!(0, repl.run_expressions(&exprs, PrintMode::Readable));
+        assert_eq!("6\na b\n6\n()\n\"a b\"\n", output.contents());
+        assert_eq!(
+            1,
+            repl.run_expressions(&["(/ 1 0)", "(println 1)"], PrintMode::Display)
+        );
+        assert_eq!("6\na b\n6\n()\n\"a b\"\n", output.contents());
+    }
+
+    #[test]
+    fn rc_file_sets_the_prompt() {
+        let dir = TempDir::new("rc");
+        let rc = dir.join("rc.rspr");
+        let repl = RispRepl::new();
+        repl.load_prelude();
+        assert_eq!(Lval::lval_sexpr(), repl.load_rc(&rc));
+        assert_eq!("[1]> ", repl.prompt());
+
+        std::fs::write(
+            &rc,
+            "(def {repl-prompt} \"risp> \")\n(fun {sq x} {* x x})\n",
+        )
+        .unwrap();
+        repl.load_rc(&rc);
+        assert_eq!("risp> ", repl.prompt());
+        assert_eq!(25f64, eval_rispreter(&repl.env(), "(sq 5)"));
+    }
+
+    #[test]
+    fn results_are_numbered_and_bound() {
+        let repl = RispRepl::new();
+        assert_eq!("[1]> ", repl.prompt());
+        repl.eval_input("(+ 1 2)");
+        repl.eval_input("\"two\"");
+        repl.eval_input("(/ 1 0)");
+        repl.eval_input("{4}");
+        assert_eq!("[5]> ", repl.prompt());
+
+        assert_eq!(
+            Lval::lval_qexpr().add_cell_move(Lval::lval_num(4f64)),
+            repl.eval_input("*1")
+        );
+        assert_eq!(
+            Lval::lval_string("two".to_string()),
+            eval_rispreter(&repl.env(), "*3")
+        );
+        assert_eq!(
+            Lval::lval_err(Lerror::DivisionByZero),
+            eval_rispreter(&repl.env(), "*e")
+        );
+        assert_eq!(4f64, eval_rispreter(&repl.env(), "(+ 1 (out 1))"));
+        assert_eq!(
+            Lval::lval_err(Lerror::DivisionByZero),
+            eval_rispreter(&repl.env(), "(out 3)")
+        );
+        assert_eq!("error", eval_rispreter(&repl.env(), "(out 9)").type_name());
+
+        // `out` is only a name the REPL binds, programs can take it
+        assert_eq!(5f64, eval_rispreter(&Lenv::new(), "(do (def {out} 5) out)"));
+        repl.eval_input("(def {out} {1 2})");
+        assert_eq!("{1 2}", eval_rispreter(&repl.env(), "out").to_string());
+        repl.reset();
+        assert_eq!("builtin", eval_rispreter(&repl.env(), "out").type_name());
+    }
+}
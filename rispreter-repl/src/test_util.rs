@@ -0,0 +1,30 @@
+use std::path::{Path, PathBuf};
+
+/// A directory of its own under the temp dir, for a test to write files in. It's
+/// removed, with everything in it, when dropped, even when the test fails
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    /// `name` tells apart the directories of the tests running at the same time
+    pub fn new(name: &str) -> TempDir {
+        let dir = std::env::temp_dir().join(format!("rispreter-{}-{}", name, std::process::id()));
+        // left over by a run that was killed
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        TempDir(dir)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+
+    pub fn join<P: AsRef<Path>>(&self, path: P) -> PathBuf {
+        self.0.join(path)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}